@@ -1 +1,4 @@
-pub mod openbabel;
\ No newline at end of file
+#[cfg(feature = "openbabel")]
+pub mod openbabel;
+pub mod rs;
+pub mod similarity;
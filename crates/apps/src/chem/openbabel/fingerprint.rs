@@ -0,0 +1,90 @@
+//! OpenBabel Fingerprint Generator
+//! 
+//! Pattern based fingerprints (FP3, FP4, MACCS) read their SMARTS patterns from the OpenBabel data directory.
+//! Set BABEL_DATADIR to the directory holding patterns.txt, SMARTS_InteLigand.txt, MACCS.txt and plugindefines.txt,
+//! otherwise OpenBabel returns blank fingerprints for them.
+//! 
+
+/// Fingerprint generator for every OpenBabel fingerprint kind, calling the fingerprint plugin by its ID
+pub struct FingerprintGenerator {
+    kind: chiral_common::app::chem::openbabel::kinds::Fingerprint
+}
+
+impl FingerprintGenerator {
+    pub fn new(kind: &chiral_common::app::chem::kinds::Fingerprint) -> Self {
+        match kind {
+            chiral_common::app::chem::kinds::Fingerprint::OpenBabel { kind } => Self { kind: kind.to_owned() },
+            _ => panic!("Fingerprint kind {} is not an OpenBabel fingerprint", kind.to_string())
+        }
+    }
+
+    /// Generator of an OpenBabel fingerprint kind OpenBabel generates here, None for other kinds or unreadable pattern files
+    pub fn available(kind: &chiral_common::app::chem::kinds::Fingerprint) -> Option<Self> {
+        match kind {
+            chiral_common::app::chem::kinds::Fingerprint::OpenBabel { kind } => Some(Self { kind: kind.to_owned() }).filter(|fpg| fpg.is_available()),
+            _ => None
+        }
+    }
+
+    pub fn get_fingerprint(&self, mol: &openbabel::molecule::Molecule) -> chiral_common::app::chem::types::FingerprintData {
+        cxx::let_cxx_string!(fp_name = self.kind.name());
+        openbabel_sys::ob::OBFingerprint_get_fingerprint(&fp_name, &mol.ob_mol, self.kind.get_nbits())
+            .iter().cloned().collect()
+    }
+
+    pub fn get_fingerprint_for_smiles_vec(&self, smiles_vec: &[chiral_common::app::chem::types::SMILES]) -> Vec<chiral_common::app::chem::types::FingerprintData> {
+        smiles_vec.iter()
+            .map(|smiles| self.get_fingerprint(&openbabel::molecule::Molecule::new_from_smiles(smiles)))
+            .collect()
+    }
+
+    /// Whether OpenBabel generates real fingerprints of this kind, 
+    ///     pattern based fingerprints are blank or of the wrong size when their pattern files cannot be read
+    pub fn is_available(&self) -> bool {
+        let fp = self.get_fingerprint(&openbabel::molecule::Molecule::new_from_smiles("c1ccccc1CC(=O)O"));
+        fp.len() as u32 * u32::BITS == self.kind.get_nbits() && fp.iter().any(|w| *w != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn assert_kinds_differ(fpks: &[&str]) {
+        let mol = openbabel::molecule::Molecule::new_from_smiles("O=C(O)C[C@H](O)C[C@H](O)CCn2c(c(c(c2c1ccc(F)cc1)c3ccccc3)C(=O)Nc4ccccc4)C(C)C");
+        let fps: Vec<chiral_common::app::chem::types::FingerprintData> = fpks.iter()
+            .map(|fpk_str| {
+                let fpg = FingerprintGenerator::new(&chiral_common::app::chem::kinds::Fingerprint::from_str(fpk_str).unwrap());
+                assert!(fpg.is_available(), "{} not available", fpk_str);
+                fpg.get_fingerprint(&mol)
+            })
+            .collect();
+        for i in 0..fps.len() {
+            for j in (i + 1)..fps.len() {
+                assert_ne!(fps[i], fps[j], "{} and {} give the same fingerprint", fpks[i], fpks[j]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_kinds_differ() {
+        assert_kinds_differ(&["ob_fp2_1024", "ob_ecfp0_1024", "ob_ecfp2_1024", "ob_ecfp4_1024", "ob_ecfp6_1024", "ob_ecfp8_1024", "ob_ecfp10_1024"]);
+    }
+
+    #[test]
+    #[ignore = "needs the OpenBabel data directory in BABEL_DATADIR"]
+    fn test_pattern_kinds_differ() {
+        assert_kinds_differ(&["ob_fp2_1024", "ob_ecfp4_1024", "ob_fp3_64", "ob_fp4_512", "ob_maccs_256"]);
+    }
+
+    #[test]
+    fn test_size() {
+        let fpg = FingerprintGenerator::new(&chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_fp2(512));
+        let fp = fpg.get_fingerprint(&openbabel::molecule::Molecule::new_from_smiles("CC(=O)Nc1ccc(O)cc1"));
+        assert_eq!(fp.len(), 16);
+        assert!(fpg.is_available());
+        assert!(FingerprintGenerator::available(&chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_fp2(512)).is_some());
+        assert!(FingerprintGenerator::available(&chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048)).is_none());
+    }
+}
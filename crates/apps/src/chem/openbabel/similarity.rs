@@ -4,67 +4,24 @@
 use chiral_common::app::chem::openbabel::similarity::*;
 use chiral_common::traits::*;
 
-/// Tanimoto coefficient for Fingerprint
-fn similarity_tanimoto(fpd_1: &[u32], fpd_2: &[u32]) -> f32 {
-    let mut andbits: u32 = 0;
-    let mut orbits: u32 = 0;
-    for i in 0..fpd_1.len() {
-        let andfp: u32 = fpd_1[i] & fpd_2[i];
-        let orfp: u32 = fpd_1[i] | fpd_2[i];
-        andbits += andfp.count_ones();
-        orbits += orfp.count_ones();
-    }
-
-    if orbits > 0 {
-        andbits as f32 / orbits as f32
-    } else {
-        0.0
-    }
-}
-
-/// Data
-pub struct Data {
-    dsk: chiral_common::kinds::Dataset,
-    ids: Vec<chiral_common::data::types::EntryID>,
-    fps: Vec<chiral_common::app::chem::types::FingerprintData>
-}
-
-impl Data {
-    fn new(dsk: chiral_common::kinds::Dataset, (ids, smiles_vec): chiral_common::app::chem::types::IdSmilesPairs, fpk: &chiral_common::app::chem::kinds::Fingerprint) -> Self {
-        let fpk_ob = super::to_ob_fp_kind(fpk);
-        let fpg  = openbabel::fingerprint::FingerprintGenerator::new(fpk_ob);
-        let fps = fpg.get_fingerprint_for_smiles_vec(&smiles_vec);
-        Self { dsk, ids, fps }
-    }
-}
-
-impl TraitData for Data {
-    fn blank() -> Self {
-        Self { dsk: chiral_common::kinds::Dataset::Empty, ids: vec![], fps: vec![] }
-    }
-
-    fn len(&self) -> usize {
-        self.ids.len()
-    }
-}
-
 /// Operator
 pub struct Operator {
     fpk: chiral_common::app::chem::kinds::Fingerprint,
-    fpg: openbabel::fingerprint::FingerprintGenerator,
+    fpg: super::fingerprint::FingerprintGenerator,
 }
 
 impl chiral_common::traits::TraitOperator for Operator {
     type InputType = Input;
-    type DataType = Data;
+    type DataType = crate::chem::similarity::Data;
     type OutputType = Output; 
     type ReportType = Report;
 
     fn new(opk: &chiral_common::kinds::Operator) -> Self {
         match opk {
             chiral_common::kinds::Operator::OpenBabelSimilaritySearching(fpk) => {
-                let fpk_ob = super::to_ob_fp_kind(&fpk);
-                let fpg = openbabel::fingerprint::FingerprintGenerator::new(fpk_ob);
+                // runner::is_available rejects unavailable fingerprints before an operator is built
+                let fpg = super::fingerprint::FingerprintGenerator::available(fpk)
+                    .unwrap_or_else(|| panic!("OpenBabel fingerprint {} not available, set BABEL_DATADIR for pattern based fingerprints", fpk.to_string()));
                 Self { fpk: fpk.to_owned(), fpg }
             },
             _ => panic!("Operator Kind mismatch")
@@ -75,29 +32,25 @@ impl chiral_common::traits::TraitOperator for Operator {
         chiral_common::kinds::Operator::OpenBabelSimilaritySearching(self.fpk.to_owned())
     }
 
-    fn prepare_data(&self, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>) -> Option<Self::DataType> {
-        ds.lock().unwrap()
-            .get_id_smiles_pairs(dsk, div_index)
-            .map(|id_smiles_pairs| Self::DataType::new(dsk.to_owned(), id_smiles_pairs, &self.fpk))
+    fn prepare_data(&self, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &chiral_common::job::CancelToken) -> Result<Self::DataType, chiral_common::job::JobError> {
+        crate::chem::similarity::prepare_data(&self.fpk, dsk, div_index, ds, token, |smiles_vec| self.fpg.get_fingerprint_for_smiles_vec(smiles_vec))
     }
 
-    fn compute(&self, input: &Self::InputType, data: &Self::DataType, _div_index: &chiral_common::job::DividendIndex) -> Self::OutputType {
+    fn compute(&self, input: &Self::InputType, data: &Self::DataType, div_index: &chiral_common::job::DividendIndex, token: &chiral_common::job::CancelToken) -> Result<Self::OutputType, chiral_common::job::JobError> {
         let mol = openbabel::molecule::Molecule::new_from_smiles(&input.smiles);
+        if !mol.is_valid() || mol.num_atoms() == 0 {
+            return Err(chiral_common::job::JobError::new(chiral_common::job::ErrorKind::InvalidInput, format!("SMILES {} cannot be parsed", input.smiles).as_str()).at(div_index));
+        }
         let fp_target = self.fpg.get_fingerprint(&mol);
-        let results = data.fps.iter()
-            .map(|fp| similarity_tanimoto(fp, &fp_target))
-            .zip(data.ids.iter())
-            .filter(|(coeff, _)| *coeff > input.threshold)
-            .map(|(mr, id)| (mr, id.to_string()))
-            .collect();
-
-        Output { results }
+        let results = data.search_bitbound(input, &fp_target, token);
+
+        Ok(Output { results, top_k: input.top_k })
     }
 
     fn report(&self, job_id: chiral_common::job::ID, input: Self::InputType, data: &Self::DataType, output: Self::OutputType) -> Self::ReportType {
         Report {
             job_id,
-            cuk: chiral_common::kinds::ComputingUnit::new(self.get_kind(), data.dsk),
+            cuk: chiral_common::kinds::ComputingUnit::new(self.get_kind(), data.dsk.to_owned()),
             input, 
             output
         }
@@ -107,7 +60,15 @@ impl chiral_common::traits::TraitOperator for Operator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chiral_common::app::chem::kinds::Metric;
     use chiral_common::data::Dummy;
+    use chiral_common::app::chem::fingerprint_index::{FingerprintIndex, Key};
+    use crate::chem::similarity::Data;
+
+    fn data_new(dsk: chiral_common::kinds::Dataset, (ids, smiles_vec): chiral_common::app::chem::types::IdSmilesPairs, fpk: &chiral_common::app::chem::kinds::Fingerprint) -> Data {
+        let fpg = super::super::fingerprint::FingerprintGenerator::new(fpk);
+        Data::new(dsk, ids, fpg.get_fingerprint_for_smiles_vec(&smiles_vec))
+    }
 
     #[test]
     fn test_op() {
@@ -116,10 +77,10 @@ mod tests {
         let com_fpk = chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(2048);
         let opk = chiral_common::kinds::Operator::OpenBabelSimilaritySearching(com_fpk.to_owned());
         let op = Operator::new(&opk);
-        let data = Data::new(dsk.to_owned(), (doc_smiles.get_ids().to_vec(), doc_smiles.get_smiles_vec().to_owned()), &com_fpk);
+        let data = data_new(dsk.to_owned(), (doc_smiles.get_ids().to_vec(), doc_smiles.get_smiles_vec().to_owned()), &com_fpk);
         assert_eq!(data.len(), 4);
-        let input = Input { smiles: String::from("c1ccccc1"), threshold: 0.045 };
-        let output = op.compute(&input, &data, &(0, 1));
+        let input = Input { smiles: String::from("c1ccccc1"), threshold: 0.045, top_k: None, metric: Metric::Tanimoto };
+        let output = op.compute(&input, &data, &(0, 1), &chiral_common::job::CancelToken::default()).unwrap();
         assert_eq!(output.len(), 2); 
         let report = op.report("job_id".to_string(), input, &data, output);
         let serialized_report = report.ser_to();
@@ -127,5 +88,102 @@ mod tests {
         let cuk = chiral_common::kinds::ComputingUnit::new(chiral_common::kinds::Operator::OpenBabelSimilaritySearching(com_fpk), dsk);
         assert_eq!(report_deserialized.cuk, cuk);
         assert_eq!(report_deserialized.output.len(), 2);
+        let input_invalid = Input { smiles: String::from("c1cc(("), threshold: 0.0, top_k: None, metric: Metric::Tanimoto };
+        assert_eq!(op.compute(&input_invalid, &data, &(0, 1), &chiral_common::job::CancelToken::default()).unwrap_err().kind, chiral_common::job::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_top_k() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let doc_smiles = chiral_common::data::DocSMILES::dummy();
+        let com_fpk = chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(2048);
+        let opk = chiral_common::kinds::Operator::OpenBabelSimilaritySearching(com_fpk.to_owned());
+        let op = Operator::new(&opk);
+        let data = data_new(dsk.to_owned(), (doc_smiles.get_ids().to_vec(), doc_smiles.get_smiles_vec().to_owned()), &com_fpk);
+        let input_all = Input { smiles: String::from("CC(=O)Nc1ccc(O)cc1"), threshold: 0.0, top_k: None, metric: Metric::Tanimoto };
+        let mut output_all = op.compute(&input_all, &data, &(0, 4), &chiral_common::job::CancelToken::default()).unwrap();
+        let input = Input { smiles: String::from("CC(=O)Nc1ccc(O)cc1"), threshold: 0.0, top_k: Some(2), metric: Metric::Tanimoto };
+        let output = op.compute(&input, &data, &(0, 4), &chiral_common::job::CancelToken::default()).unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output.results[0], (1.0, "label_4".to_string()));
+        assert!(output.results[0].0 >= output.results[1].0);
+        // top-k of the merged outputs equals the top-k over all entries
+        let data_1 = data_new(dsk.to_owned(), (doc_smiles.extract_ids(&(0..2)), doc_smiles.extract_smiles_vec(&(0..2))), &com_fpk);
+        let data_2 = data_new(dsk.to_owned(), (doc_smiles.extract_ids(&(2..4)), doc_smiles.extract_smiles_vec(&(2..4))), &com_fpk);
+        let mut merged = Output::blank();
+        merged.append(&mut op.compute(&input, &data_1, &(0, 2), &chiral_common::job::CancelToken::default()).unwrap());
+        merged.append(&mut op.compute(&input, &data_2, &(2, 4), &chiral_common::job::CancelToken::default()).unwrap());
+        output_all.top_k = Some(2);
+        output_all.append(&mut Output::blank());
+        assert_eq!(merged, output_all);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let doc_smiles = chiral_common::data::DocSMILES::dummy();
+        let com_fpk = chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(2048);
+        let opk = chiral_common::kinds::Operator::OpenBabelSimilaritySearching(com_fpk.to_owned());
+        let op = Operator::new(&opk);
+        let data = data_new(dsk, (doc_smiles.get_ids().to_vec(), doc_smiles.get_smiles_vec().to_owned()), &com_fpk);
+        let compute = |metric: Metric| {
+            let input = Input { smiles: String::from("CC(=O)Nc1ccc(O)cc1"), threshold: 0.0, top_k: None, metric };
+            op.compute(&input, &data, &(0, 4), &chiral_common::job::CancelToken::default()).unwrap()
+        };
+        let tanimoto = compute(Metric::Tanimoto);
+        assert_eq!(tanimoto, compute(Metric::Tversky { alpha: 1.0, beta: 1.0 }));
+        let dice = compute(Metric::Dice);
+        assert_eq!(tanimoto.len(), dice.len());
+        for ((t, id_t), (d, id_d)) in tanimoto.results.iter().zip(dice.results.iter()) {
+            assert_eq!(id_t, id_d);
+            assert!(d >= t); // dice = 2t / (1 + t)
+        }
+        for metric in [Metric::Cosine, Metric::Sokal, Metric::RussellRao, Metric::Tversky { alpha: 0.9, beta: 0.1 }] {
+            let output = compute(metric);
+            assert!(output.results.iter().all(|(coeff, _)| (0.0..=1.0).contains(coeff)));
+            assert!(output.results.iter().any(|(coeff, id)| id == "label_4" && (metric == Metric::RussellRao || *coeff == 1.0)));
+        }
+    }
+
+    struct IndexedStore {
+        doc: chiral_common::data::DocSMILES,
+        dir: std::path::PathBuf,
+        source: std::path::PathBuf
+    }
+
+    impl TraitDataStore for IndexedStore {
+        fn get_id_smiles_pairs(&self, _dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex) -> Option<chiral_common::app::chem::types::IdSmilesPairs> {
+            let range = div_index.0..div_index.1;
+            Some((self.doc.extract_ids(&range), self.doc.extract_smiles_vec(&range)))
+        }
+
+        fn get_source_path(&self, _dsk: &chiral_common::kinds::Dataset) -> Option<std::path::PathBuf> { Some(self.source.to_owned()) }
+        fn get_index_dir(&self) -> Option<std::path::PathBuf> { Some(self.dir.to_owned()) }
+    }
+
+    #[test]
+    fn test_prepare_data_index() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let dir = crate::tests::temp_dir();
+        let source = dir.join("dummy.txt");
+        std::fs::write(&source, "dummy").unwrap();
+        let store = IndexedStore { doc: chiral_common::data::DocSMILES::dummy(), dir: dir.to_owned(), source };
+        let ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(store));
+        let com_fpk = chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(2048);
+        let op = Operator::new(&chiral_common::kinds::Operator::OpenBabelSimilaritySearching(com_fpk.to_owned()));
+        let key = Key::new(dsk.to_owned(), com_fpk, (1, 4));
+        // a stopped token ends the preparation before the first batch, nothing is indexed
+        let token_stopped = chiral_common::job::CancelToken::default();
+        token_stopped.cancel();
+        assert_eq!(op.prepare_data(&dsk, &(1, 4), ds.clone(), &token_stopped).unwrap().len(), 0);
+        assert!(!FingerprintIndex::filepath(&dir, &key).exists());
+        let token = chiral_common::job::CancelToken::default();
+        let data_built = op.prepare_data(&dsk, &(1, 4), ds.clone(), &token).unwrap();
+        assert!(FingerprintIndex::filepath(&dir, &key).exists());
+        let data_loaded = op.prepare_data(&dsk, &(1, 4), ds, &token).unwrap();
+        assert_eq!(data_loaded.len(), 3);
+        assert_eq!(data_loaded.ids, data_built.ids);
+        assert_eq!(data_loaded.fps, data_built.fps);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -8,7 +8,8 @@ use chiral_common::traits::*;
 pub struct Data {
     dsk: chiral_common::kinds::Dataset,
     ids: Vec<chiral_common::data::types::EntryID>,
-    mols: Vec<openbabel::molecule::Molecule>
+    mols: Vec<openbabel::molecule::Molecule>,
+    screen_fps: std::sync::OnceLock<Vec<chiral_common::app::chem::types::FingerprintData>> // generated by the first prescreened search
 }
 
 impl Data {
@@ -16,13 +17,22 @@ impl Data {
         let mols = smiles_vec.iter()
             .map(|smiles| openbabel::molecule::Molecule::new_from_smiles(smiles))
             .collect();
-        Self { dsk, ids, mols }
+        Self { dsk, ids, mols, screen_fps: std::sync::OnceLock::new() }
+    }
+
+    fn append(&mut self, (mut ids, smiles_vec): chiral_common::app::chem::types::IdSmilesPairs) {
+        self.ids.append(&mut ids);
+        self.mols.extend(smiles_vec.iter().map(|smiles| openbabel::molecule::Molecule::new_from_smiles(smiles)));
+    }
+
+    fn get_screen_fps(&self, prescreen: &Prescreen) -> &[chiral_common::app::chem::types::FingerprintData] {
+        self.screen_fps.get_or_init(|| self.mols.iter().map(|mol| prescreen.fpg.get_fingerprint(mol)).collect())
     }
 }
 
 impl TraitData for Data {
     fn blank() -> Self {
-        Self { dsk: chiral_common::kinds::Dataset::Empty, ids: vec![], mols: vec![] }
+        Self { dsk: chiral_common::kinds::Dataset::Empty, ids: vec![], mols: vec![], screen_fps: std::sync::OnceLock::new() }
     }
 
     fn len(&self) -> usize { self.ids.len() }
@@ -39,11 +49,72 @@ impl OpenBabelSSMatcher {
         Self { sp }
     }
 
+    /// SMARTS which cannot be parsed give an empty pattern
+    pub fn is_valid(&self) -> bool {
+        self.sp.num_atoms() > 0
+    }
+
     pub fn find_match(&self, mol: &openbabel::molecule::Molecule) -> MatchResult {
         self.sp.find_match(mol)
     }
 }
 
+/// Whether the SMARTS reads as a plain SMILES, the only queries whose FP2 fingerprint is a subset of the fingerprints of their matches
+///     no wildcards, logical operators, atom or bond primitives, and aromatic atoms only in rings
+fn is_screenable(smarts: &chiral_common::app::chem::types::SMARTS) -> bool {
+    let mut chars = smarts.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'B' | 'C' | 'N' | 'O' | 'P' | 'S' | 'F' | 'I' | 'b' | 'c' | 'n' | 'o' | 'p' | 's' 
+                | '0'..='9' | '%' | '(' | ')' | '-' | '=' | '#' | '/' | '\\' | '.' => (),
+            'l' | 'r' => (), // Cl, Br, a lone l or r fails to parse below
+            '[' => {
+                // isotope, element, hydrogen count and charge only
+                let bracket: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                let element = bracket.trim_start_matches(|c: char| c.is_ascii_digit());
+                let symbol_len = if element.chars().nth(1).is_some_and(|c| c.is_ascii_lowercase()) { 2 } else { 1 };
+                if element.is_empty() || element.starts_with('H') || !element.starts_with(|c: char| c.is_ascii_alphabetic()) { return false }
+                let rest = element.get(symbol_len..).unwrap_or("");
+                if !rest.chars().all(|c| c == 'H' || c == '+' || c == '-' || c.is_ascii_digit()) { return false }
+            }
+            _ => return false
+        }
+    }
+
+    match crate::chem::rs::molecule::Molecule::from_smiles(smarts) {
+        Ok(mol) => {
+            let aromatic_in_rings = (0..mol.len()).all(|i| !mol.atoms()[i].aromatic || mol.is_ring_atom(i));
+            // OpenBabel perceives rings of alternating double bonds as aromatic, the SMARTS reads them aliphatic
+            let aliphatic_ring_double_bonds = mol.bonds().iter().enumerate()
+                .any(|(b, bond)| bond.order == crate::chem::rs::molecule::BondOrder::Double && mol.is_ring_bond(b) && !mol.atoms()[bond.atoms.0].aromatic);
+            !mol.is_empty() && aromatic_in_rings && !aliphatic_ring_double_bonds
+        }
+        Err(_) => false
+    }
+}
+
+/// FP2 path fingerprint prescreen
+///     a molecule can only match when its screen bits are a superset of the screen bits of the query
+struct Prescreen {
+    fpg: super::fingerprint::FingerprintGenerator,
+    fp_query: chiral_common::app::chem::types::FingerprintData
+}
+
+impl Prescreen {
+    pub fn new(smarts: &chiral_common::app::chem::types::SMARTS) -> Option<Self> {
+        if !is_screenable(smarts) { return None }
+        let fpg = super::fingerprint::FingerprintGenerator::new(&chiral_common::app::chem::kinds::Fingerprint::kind_openbabel_fp2(1024));
+        let fp_query = fpg.get_fingerprint(&openbabel::molecule::Molecule::new_from_smiles(smarts));
+        Some(Self { fpg, fp_query })
+    }
+
+    pub fn may_match(&self, fp: &[u32]) -> bool {
+        self.fp_query.iter()
+            .zip(fp.iter())
+            .all(|(q, t)| q & !t == 0)
+    }
+}
+
 pub struct Operator {
 }
 
@@ -64,28 +135,57 @@ impl TraitOperator for Operator {
         chiral_common::kinds::Operator::OpenBabelSSMatching
     }
 
-    fn prepare_data(&self, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>) -> Option<Self::DataType> {
-        ds.lock().unwrap()
-            .get_id_smiles_pairs(dsk, div_index)
-            .map(|(ids, smiles_vec)| Self::DataType::new(dsk.to_owned(), (ids, smiles_vec)))
+    fn prepare_data(&self, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &chiral_common::job::CancelToken) -> Result<Self::DataType, chiral_common::job::JobError> {
+        let batches = ds.lock().unwrap()
+            .get_id_smiles_batches(dsk, div_index, chiral_common::data::BATCH_SIZE)
+            .ok_or_else(|| crate::chem::similarity::no_entries_error(dsk, div_index))?;
+        let mut data = Self::DataType::new(dsk.to_owned(), (vec![], vec![]));
+        for batch in batches {
+            if token.is_stopped() {
+                break;
+            }
+            data.append(batch.map_err(|e| crate::chem::similarity::read_error(dsk, div_index, e))?);
+        }
+        Ok(data)
     }
 
-    fn compute(&self, input: &Self::InputType, data: &Self::DataType, _div_index: &chiral_common::job::DividendIndex) -> Self::OutputType {
+    fn compute(&self, input: &Self::InputType, data: &Self::DataType, div_index: &chiral_common::job::DividendIndex, token: &chiral_common::job::CancelToken) -> Result<Self::OutputType, chiral_common::job::JobError> {
         let matcher = OpenBabelSSMatcher::new(&input.smarts);
-        let results = data.mols.iter()
-            .map(|mol| matcher.find_match(mol))
-            .zip(data.ids.iter())
-            .filter(|(mr, _)| mr.len() > 0)
-            .map(|(mr, id)| (mr, id.to_string()))
+        if !matcher.is_valid() {
+            return Err(chiral_common::job::JobError::new(chiral_common::job::ErrorKind::InvalidInput, format!("SMARTS {} cannot be parsed", input.smarts).as_str()).at(div_index));
+        }
+        let prescreen = if input.prescreen { Prescreen::new(&input.smarts) } else { None };
+        if input.prescreen && prescreen.is_none() {
+            chiral_common::logging::info(format!("SMARTS {} cannot be prescreened, matching every molecule", input.smarts).as_str());
+        }
+        let candidates: Vec<usize> = match prescreen.as_ref() {
+            Some(prescreen) => data.get_screen_fps(prescreen).iter()
+                .enumerate()
+                .filter(|(_, fp)| prescreen.may_match(fp))
+                .map(|(i, _)| i)
+                .collect(),
+            None => (0..data.len()).collect()
+        };
+        let (screened, screened_out) = match prescreen {
+            Some(_) => (data.len(), data.len() - candidates.len()),
+            None => (0, 0)
+        };
+        let results = candidates.into_iter()
+            .enumerate()
+            .take_while(|(n, _)| !token.should_stop(*n))
+            .map(|(_, i)| (matcher.find_match(&data.mols[i]), &data.ids[i]))
+            .filter(|(mr, _)| !mr.is_empty())
+            .map(|(mr, id)| (input.limit_mappings(mr), id.to_string()))
+            .take(input.max_hits.unwrap_or(usize::MAX))
             .collect();
 
-        Output { results }
+        Ok(Output { results, screened, screened_out, max_hits: input.max_hits })
     }
 
     fn report(&self, job_id: chiral_common::job::ID, input: Self::InputType, data: &Self::DataType, output: Self::OutputType) -> Self::ReportType {
         Report {
             job_id,
-            cuk: chiral_common::kinds::ComputingUnit::new(self.get_kind(), data.dsk),
+            cuk: chiral_common::kinds::ComputingUnit::new(self.get_kind(), data.dsk.to_owned()),
             input,
             output
         }
@@ -120,8 +220,8 @@ mod tests {
         assert_eq!(data.len(), 4);
         let opk = chiral_common::kinds::Operator::OpenBabelSSMatching;
         let op = Operator::new(&opk);
-        let input = Input { smarts: String::from("C(=O)O") };
-        let output = op.compute(&input, &data, &(0, 1));
+        let input = Input::new(String::from("C(=O)O"));
+        let output = op.compute(&input, &data, &(0, 1), &chiral_common::job::CancelToken::default()).unwrap();
         assert_eq!(output.len(), 2); 
         let report = op.report("job_id".to_string(), input, &data, output);
         let serialized_report = report.ser_to();
@@ -129,13 +229,127 @@ mod tests {
         let cuk = chiral_common::kinds::ComputingUnit::new(chiral_common::kinds::Operator::OpenBabelSSMatching, dsk);
         assert_eq!(report_deserialized.cuk, cuk);
         assert_eq!(report_deserialized.output.len(), 2);
-        let input_1 = Input { smarts: String::from("C(=O)O") };
-        let output_1 = op.compute(&input_1, &data, &(0, 1));
+        let input_1 = Input::new(String::from("C(=O)O"));
+        let output_1 = op.compute(&input_1, &data, &(0, 1), &chiral_common::job::CancelToken::default()).unwrap();
         let report_1 = op.report("job_id".to_string(), input_1, &data, output_1);
         assert_eq!(report_1.output.len(), 2); 
-        let input_2 = Input { smarts: String::from("C(=O)O") };
-        let output_2 = op.compute(&input_2, &data, &(0, 1));
+        let input_2 = Input::new(String::from("C(=O)O"));
+        let output_2 = op.compute(&input_2, &data, &(0, 1), &chiral_common::job::CancelToken::default()).unwrap();
         let report_2 = op.report("job_id".to_string(), input_2, &data, output_2);
         assert_eq!(report_2.output.len(), 2); 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_is_screenable() {
+        for smarts in ["c1ccccc1N=O", "C(=O)O", "O=CN", "[N+](=O)[O-]", "c1ccccc1Cl", "[13CH3]C(=O)Br", "c1cc[nH]c1"] {
+            assert!(is_screenable(&smarts.to_string()), "{}", smarts);
+        }
+        for smarts in ["O=CN*", "[#6]C", "C~O", "[C,N]O", "[!C]", "cc", "C1=CC=CC=C1", "[CH2;R]", "C@C", "[$(CO)]", "[H]C", "c:c", "C(", ""] {
+            assert!(!is_screenable(&smarts.to_string()), "{}", smarts);
+        }
+    }
+
+    fn assert_prescreen_same_as_unscreened(op: &Operator, data: &Data, smarts_vec: &[&str]) -> usize {
+        let mut screened_out = 0;
+        for smarts in smarts_vec {
+            let output = op.compute(&Input::new(smarts.to_string()), data, &(0, data.len()), &chiral_common::job::CancelToken::default()).unwrap();
+            assert_eq!((output.screened, output.screened_out), (0, 0));
+            let output_screened = op.compute(&Input { prescreen: true, ..Input::new(smarts.to_string()) }, data, &(0, data.len()), &chiral_common::job::CancelToken::default()).unwrap();
+            assert_eq!(output.results, output_screened.results, "{}", smarts);
+            screened_out += output_screened.screened_out;
+        }
+        screened_out
+    }
+
+    #[test]
+    fn test_prescreen() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let doc = chiral_common::data::DocSMILES::dummy();
+        let mut ids = doc.get_ids().to_vec();
+        let mut smiles_vec = doc.get_smiles_vec().to_vec();
+        for (i, smiles) in ["COc1cc([N+](=O)[O-])c(OC)cc1CC(C)N", "NCC(=O)NCC", "c1ccc2ccccc2c1", "C1CCCCC1CCl", "CC(=O)Oc1ccccc1C(=O)O"].iter().enumerate() {
+            ids.push(format!("extra_{}", i));
+            smiles_vec.push(smiles.to_string());
+        }
+        let data = Data::new(dsk, (ids, smiles_vec));
+        let op = Operator::new(&chiral_common::kinds::Operator::OpenBabelSSMatching);
+
+        let output = op.compute(&Input { prescreen: true, ..Input::new(String::from("c1ccccc1N=O")) }, &data, &(0, data.len()), &chiral_common::job::CancelToken::default()).unwrap();
+        assert_eq!(output.screened, data.len());
+        assert!(output.screened_out > 0);
+        assert!(output.screened_out + output.results.len() <= data.len());
+        // queries which cannot be screened run on every molecule
+        let output_unscreenable = op.compute(&Input { prescreen: true, ..Input::new(String::from("O=CN*")) }, &data, &(0, data.len()), &chiral_common::job::CancelToken::default()).unwrap();
+        assert_eq!((output_unscreenable.screened, output_unscreenable.screened_out), (0, 0));
+
+        let smarts_vec = ["c1ccccc1N=O", "C(=O)O", "O=CN", "c1ccccc1", "CCl", "C1CCCCC1", "c1ccc2ccccc2c1", "[N+](=O)[O-]", "CC(C)N", "OC", "C(=O)Oc1ccccc1", "O=CN*", "[#7]"];
+        assert!(assert_prescreen_same_as_unscreened(&op, &data, &smarts_vec) > 0);
+
+        // screen-out counts add up in the report
+        let mut merged = Output::blank();
+        merged.append(&mut op.compute(&Input { prescreen: true, ..Input::new(String::from("C(=O)O")) }, &data, &(0, data.len()), &chiral_common::job::CancelToken::default()).unwrap());
+        merged.append(&mut op.compute(&Input { prescreen: true, ..Input::new(String::from("C(=O)O")) }, &data, &(0, data.len()), &chiral_common::job::CancelToken::default()).unwrap());
+        assert_eq!(merged.screened, 2 * data.len());
+        let report = op.report("job_id".to_string(), Input { prescreen: true, ..Input::new(String::from("C(=O)O")) }, &data, merged.clone());
+        assert_eq!(Report::ser_from(&report.ser_to()).output, merged);
+    }
+
+    #[test]
+    fn test_prescreen_test_chembl() {
+        let dsk = chiral_common::kinds::Dataset::TestChembl;
+        let data_dir = std::path::PathBuf::from("../../../chiral-db-example-data/ChEMBL");
+        let doc = chiral_common::data::load_from_path::<chiral_common::data::DocSMILES>(&dsk, &data_dir);
+        assert_eq!(doc.len(), 10000);
+        let data = Data::new(dsk, (doc.extract_ids(&(0..2000)), doc.extract_smiles_vec(&(0..2000))));
+        let op = Operator::new(&chiral_common::kinds::Operator::OpenBabelSSMatching);
+        let smarts_vec = ["c1ccccc1N=O", "C(=O)O", "O=CN", "c1ccncc1", "S(=O)(=O)N", "C(F)(F)F", "c1ccc2[nH]ccc2c1"];
+        assert!(assert_prescreen_same_as_unscreened(&op, &data, &smarts_vec) > 0);
+    }
+
+    #[test]
+    fn test_match_options() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let data = Data::new(dsk, (vec!["id_1".to_string(), "id_2".to_string(), "id_3".to_string()], vec!["OC(=O)CC(=O)O".to_string(), "CC".to_string(), "CC(=O)O".to_string()]));
+        let op = Operator::new(&chiral_common::kinds::Operator::OpenBabelSSMatching);
+        let compute = |input: Input| op.compute(&input, &data, &(0, 3), &chiral_common::job::CancelToken::default()).unwrap();
+
+        let output_all = compute(Input::new(String::from("CC")));
+        assert_eq!(output_all.results.iter().map(|(_, id)| id.as_str()).collect::<Vec<&str>>(), vec!["id_1", "id_2", "id_3"]);
+        assert_eq!(output_all.results.iter().map(|(mr, _)| mr.len()).collect::<Vec<usize>>(), vec![4, 2, 2]); // each C-C bond in both directions
+        let output_unique = compute(Input { unique: true, ..Input::new(String::from("CC")) });
+        assert_eq!(output_unique.results.iter().map(|(mr, _)| mr.len()).collect::<Vec<usize>>(), vec![2, 1, 1]);
+        let output_limited = compute(Input { max_mappings: Some(1), ..Input::new(String::from("CC")) });
+        assert!(output_limited.results.iter().all(|(mr, _)| mr.len() == 1));
+        let output_has_match = compute(Input { has_match_only: true, ..Input::new(String::from("C(=O)O")) });
+        assert_eq!(output_has_match.results.iter().map(|(_, id)| id.as_str()).collect::<Vec<&str>>(), vec!["id_1", "id_3"]);
+        assert!(output_has_match.results.iter().all(|(mr, _)| mr.is_empty()));
+        let output_max_hits = compute(Input { max_hits: Some(2), ..Input::new(String::from("CC")) });
+        assert_eq!(output_max_hits.results, output_all.results[0..2].to_vec());
+        assert!(output_max_hits.is_complete());
+        assert!(!output_all.is_complete());
+        let token = chiral_common::job::CancelToken::default();
+        token.cancel();
+        assert!(op.compute(&Input::new(String::from("CC")), &data, &(0, 3), &token).unwrap().results.is_empty());
+    }
+
+    struct EmptyStore {}
+
+    impl TraitDataStore for EmptyStore {
+        fn get_id_smiles_pairs(&self, _dsk: &chiral_common::kinds::Dataset, _div_index: &chiral_common::job::DividendIndex) -> Option<chiral_common::app::chem::types::IdSmilesPairs> { None }
+    }
+
+    #[test]
+    fn test_errors() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let doc = chiral_common::data::DocSMILES::dummy();
+        let data = Data::new(dsk.to_owned(), (doc.get_ids().to_vec(), doc.get_smiles_vec().to_vec()));
+        let op = Operator::new(&chiral_common::kinds::Operator::OpenBabelSSMatching);
+        let error = op.compute(&Input::new(String::from("C((")), &data, &(0, 4), &chiral_common::job::CancelToken::default()).unwrap_err();
+        assert_eq!(error.kind, chiral_common::job::ErrorKind::InvalidInput);
+        assert_eq!(error.div_index, Some((0, 4)));
+        let ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(EmptyStore {}));
+        let error = op.prepare_data(&dsk, &(4, 8), ds, &chiral_common::job::CancelToken::default()).err().unwrap();
+        assert_eq!(error.kind, chiral_common::job::ErrorKind::DataPreparation);
+        assert_eq!(error.div_index, Some((4, 8)));
+    }
+}
@@ -0,0 +1,171 @@
+//! Rust Fingerprint Generator
+//!
+//! ECFP: Morgan algorithm, atom identifiers updated with the identifiers of the neighbours for diameter / 2 iterations,
+//!     initial atom invariants are heavy degree, total degree, atomic number, charge, hydrogen count, ring membership and aromaticity
+//! Path: every linear path of 0 to length bonds, in the direction giving the smaller sequence of atom and bond codes
+//!
+//! Bits are folded from a stable FNV-1a hash, so fingerprints are the same on every platform and every run.
+//!
+
+use super::molecule::Molecule;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn hash(values: &[u32]) -> u32 {
+    let h = values.iter()
+        .flat_map(|v| v.to_le_bytes())
+        .fold(FNV_OFFSET, |h, b| (h ^ b as u64).wrapping_mul(FNV_PRIME));
+    (h ^ (h >> 32)) as u32
+}
+
+fn set_bit(fp: &mut [u32], h: u32, nbits: u32) {
+    let bit = h % nbits;
+    fp[(bit / u32::BITS) as usize] |= 1 << (bit % u32::BITS);
+}
+
+fn atom_invariant(mol: &Molecule, index: usize) -> u32 {
+    let atom = &mol.atoms()[index];
+    let heavy_degree = mol.neighbours(index).len() as u32;
+    hash(&[
+        heavy_degree,
+        heavy_degree + atom.hydrogens as u32,
+        atom.atomic_number as u32,
+        atom.charge as i32 as u32,
+        atom.hydrogens as u32,
+        mol.is_ring_atom(index) as u32,
+        atom.aromatic as u32
+    ])
+}
+
+fn ecfp(mol: &Molecule, diameter: u32, nbits: u32) -> chiral_common::app::chem::types::FingerprintData {
+    let mut fp = vec![0; (nbits / u32::BITS) as usize];
+    let mut identifiers: Vec<u32> = (0..mol.len()).map(|i| atom_invariant(mol, i)).collect();
+    identifiers.iter().for_each(|id| set_bit(&mut fp, *id, nbits));
+    for iteration in 1..=diameter / 2 {
+        identifiers = (0..mol.len())
+            .map(|i| {
+                let mut neighbours: Vec<(u32, u32)> = mol.neighbours(i).iter()
+                    .map(|(j, b)| (mol.bonds()[*b].order.code(), identifiers[*j]))
+                    .collect();
+                neighbours.sort_unstable();
+                let mut values = vec![iteration, identifiers[i]];
+                values.extend(neighbours.into_iter().flat_map(|(b, id)| [b, id]));
+                hash(&values)
+            })
+            .collect();
+        identifiers.iter().for_each(|id| set_bit(&mut fp, *id, nbits));
+    }
+    fp
+}
+
+fn path(mol: &Molecule, length: u32, nbits: u32) -> chiral_common::app::chem::types::FingerprintData {
+    let mut fp = vec![0; (nbits / u32::BITS) as usize];
+    let atom_codes: Vec<u32> = mol.atoms().iter().map(|a| a.atomic_number as u32 * 2 + a.aromatic as u32).collect();
+
+    // depth first enumeration of simple paths starting at each atom
+    for start in 0..mol.len() {
+        let mut visited = vec![false; mol.len()];
+        let mut codes = vec![atom_codes[start]];
+        let mut stack: Vec<(usize, usize)> = vec![(start, 0)]; // (atom, next neighbour index)
+        visited[start] = true;
+        set_bit(&mut fp, hash(&codes), nbits);
+        while let Some((atom, next)) = stack.last().copied() {
+            match mol.neighbours(atom).get(next) {
+                Some(&(nbr, bond)) if stack.len() <= length as usize => {
+                    stack.last_mut().unwrap().1 += 1;
+                    if visited[nbr] { continue }
+                    visited[nbr] = true;
+                    codes.extend([mol.bonds()[bond].order.code() + 1000, atom_codes[nbr]]);
+                    stack.push((nbr, 0));
+                    // every path is enumerated from both ends, only the canonical direction is hashed
+                    let reversed: Vec<u32> = codes.iter().rev().copied().collect();
+                    if codes <= reversed {
+                        set_bit(&mut fp, hash(&codes), nbits);
+                    }
+                }
+                _ => {
+                    stack.pop();
+                    visited[atom] = false;
+                    if !stack.is_empty() {
+                        codes.truncate(codes.len() - 2);
+                    }
+                }
+            }
+        }
+    }
+    fp
+}
+
+/// Fingerprint generator for the fingerprint kinds of the Rust package
+pub struct FingerprintGenerator {
+    kind: chiral_common::app::chem::rs::kinds::Fingerprint
+}
+
+impl FingerprintGenerator {
+    pub fn new(kind: &chiral_common::app::chem::kinds::Fingerprint) -> Self {
+        match kind {
+            chiral_common::app::chem::kinds::Fingerprint::Rust { kind } => Self { kind: kind.to_owned() },
+            _ => panic!("Fingerprint kind {} is not a Rust fingerprint", kind.to_string())
+        }
+    }
+
+    pub fn get_fingerprint(&self, mol: &Molecule) -> chiral_common::app::chem::types::FingerprintData {
+        match self.kind {
+            chiral_common::app::chem::rs::kinds::Fingerprint::ECFP { diameter, nbits } => ecfp(mol, diameter, nbits),
+            chiral_common::app::chem::rs::kinds::Fingerprint::Path { length, nbits } => path(mol, length, nbits)
+        }
+    }
+
+    /// Blank fingerprint for SMILES which cannot be parsed
+    pub fn get_fingerprint_for_smiles(&self, smiles: &chiral_common::app::chem::types::SMILES) -> chiral_common::app::chem::types::FingerprintData {
+        match Molecule::from_smiles(smiles) {
+            Ok(mol) => self.get_fingerprint(&mol),
+            Err(e) => {
+                chiral_common::logging::warn(format!("SMILES {}: {}", smiles, e).as_str());
+                vec![0; (self.kind.get_nbits() / u32::BITS) as usize]
+            }
+        }
+    }
+
+    pub fn get_fingerprint_for_smiles_vec(&self, smiles_vec: &[chiral_common::app::chem::types::SMILES]) -> Vec<chiral_common::app::chem::types::FingerprintData> {
+        smiles_vec.iter()
+            .map(|smiles| self.get_fingerprint_for_smiles(smiles))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn fingerprint(fpk_str: &str, smiles: &str) -> chiral_common::app::chem::types::FingerprintData {
+        let fpg = FingerprintGenerator::new(&chiral_common::app::chem::kinds::Fingerprint::from_str(fpk_str).unwrap());
+        fpg.get_fingerprint_for_smiles(&smiles.to_string())
+    }
+
+    fn popcount(fp: &[u32]) -> u32 { fp.iter().map(|w| w.count_ones()).sum() }
+
+    #[test]
+    fn test_fingerprint() {
+        let lipitor = "O=C(O)C[C@H](O)C[C@H](O)CCn2c(c(c(c2c1ccc(F)cc1)c3ccccc3)C(=O)Nc4ccccc4)C(C)C";
+        for fpk_str in ["rs_ecfp0_1024", "rs_ecfp2_1024", "rs_ecfp4_2048", "rs_ecfp6_512", "rs_path1_1024", "rs_path7_1024"] {
+            let fp = fingerprint(fpk_str, lipitor);
+            let nbits = chiral_common::app::chem::kinds::Fingerprint::from_str(fpk_str).unwrap().get_nbits();
+            assert_eq!(fp.len() as u32 * u32::BITS, nbits);
+            assert!(popcount(&fp) > 0);
+            // independent of the atom order in the SMILES
+            assert_eq!(fingerprint(fpk_str, "CC(=O)Nc1ccc(O)cc1"), fingerprint(fpk_str, "Oc1ccc(NC(C)=O)cc1"));
+        }
+
+        // more iterations and longer paths set more bits
+        assert!(popcount(&fingerprint("rs_ecfp4_2048", lipitor)) > popcount(&fingerprint("rs_ecfp2_2048", lipitor)));
+        assert!(popcount(&fingerprint("rs_path7_2048", lipitor)) > popcount(&fingerprint("rs_path3_2048", lipitor)));
+        // carbon, nitrogen and the C-N path
+        assert_eq!(popcount(&fingerprint("rs_path1_1024", "CN")), 3);
+        assert_ne!(fingerprint("rs_ecfp4_1024", "c1ccccc1O"), fingerprint("rs_ecfp4_1024", "c1ccccc1N"));
+        // blank for invalid SMILES
+        assert_eq!(popcount(&fingerprint("rs_ecfp4_1024", "C1CC")), 0);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod molecule;
+pub mod fingerprint;
+pub mod similarity;
@@ -0,0 +1,481 @@
+//! Molecular graph parsed from SMILES
+//!
+//! Supports the organic subset, bracket atoms (isotope, chirality, hydrogen count, charge, atom class),
+//! branches, ring closures (including %nn) and disconnected components.
+//! Chirality and bond direction are parsed but not kept, fingerprints do not use them.
+//!
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SmilesError {
+    #[error("unexpected character '{1}' at position {0}")]
+    UnexpectedCharacter(usize, char),
+    #[error("unknown element '{1}' at position {0}")]
+    UnknownElement(usize, String),
+    #[error("unclosed bracket atom at position {0}")]
+    UnclosedBracket(usize),
+    #[error("unbalanced branch at position {0}")]
+    UnbalancedBranch(usize),
+    #[error("unclosed ring {0}")]
+    UnclosedRing(u32),
+    #[error("bond without atom at position {0}")]
+    DanglingBond(usize),
+    #[error("charge out of range at position {0}")]
+    ChargeOutOfRange(usize),
+    #[error("isotope out of range at position {0}")]
+    IsotopeOutOfRange(usize),
+    #[error("hydrogen count out of range at position {0}")]
+    HydrogensOutOfRange(usize),
+    #[error("ring closure of an atom to itself at position {0}")]
+    SelfRingClosure(usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BondOrder {
+    Single,
+    Double,
+    Triple,
+    Quadruple,
+    Aromatic
+}
+
+impl BondOrder {
+    /// Contribution to the valence, aromatic bonds count as single bonds
+    fn valence(&self) -> u32 {
+        match self {
+            Self::Single | Self::Aromatic => 1,
+            Self::Double => 2,
+            Self::Triple => 3,
+            Self::Quadruple => 4
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::Single => 1,
+            Self::Double => 2,
+            Self::Triple => 3,
+            Self::Quadruple => 4,
+            Self::Aromatic => 5
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atom {
+    pub atomic_number: u8,
+    pub aromatic: bool,
+    pub charge: i8,
+    pub isotope: u16,
+    pub hydrogens: u8, // explicit for bracket atoms, implicit from the default valences otherwise
+    bracket: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bond {
+    pub atoms: (usize, usize),
+    pub order: BondOrder
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Molecule {
+    atoms: Vec<Atom>,
+    bonds: Vec<Bond>,
+    neighbours: Vec<Vec<(usize, usize)>>, // (atom, bond) pairs for each atom
+    ring_bonds: Vec<bool>
+}
+
+const ELEMENTS: [&str; 118] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl", "Ar", "K", "Ca",
+    "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As", "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr",
+    "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In", "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd",
+    "Pm", "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg",
+    "Tl", "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk", "Cf", "Es", "Fm",
+    "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og"
+];
+
+fn atomic_number(symbol: &str) -> Option<u8> {
+    match symbol {
+        "*" => Some(0),
+        _ => ELEMENTS.iter().position(|e| *e == symbol).map(|i| i as u8 + 1)
+    }
+}
+
+/// Default valences of the organic subset
+fn default_valences(atomic_number: u8) -> &'static [u32] {
+    match atomic_number {
+        5 => &[3],
+        6 => &[4],
+        7 | 15 => &[3, 5],
+        8 => &[2],
+        16 => &[2, 4, 6],
+        9 | 17 | 35 | 53 => &[1],
+        _ => &[]
+    }
+}
+
+/// first character in uppercase, by char since symbols in brackets may not be ASCII
+fn capitalize(symbol: &str) -> String {
+    let mut chars = symbol.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    mol: &'a mut Molecule
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> { self.chars.get(self.pos).copied() }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+
+    /// Optional count, the default without digits and the error when it does not fit
+    fn count<T: TryFrom<u32>>(&mut self, default: T, error: SmilesError) -> Result<T, SmilesError> {
+        let digits = self.pos;
+        match self.number() {
+            Some(n) => T::try_from(n).or(Err(error)),
+            None if self.pos == digits => Ok(default),
+            None => Err(error)
+        }
+    }
+
+    fn add_atom(&mut self, symbol: &str, aromatic: bool, position: usize) -> Result<usize, SmilesError> {
+        let symbol_cap = if aromatic { capitalize(symbol) } else { symbol.to_string() };
+        let atomic_number = atomic_number(&symbol_cap).ok_or(SmilesError::UnknownElement(position, symbol.to_string()))?;
+        self.mol.atoms.push(Atom { atomic_number, aromatic, charge: 0, isotope: 0, hydrogens: 0, bracket: false });
+        self.mol.neighbours.push(vec![]);
+        Ok(self.mol.atoms.len() - 1)
+    }
+
+    fn organic_atom(&mut self) -> Result<usize, SmilesError> {
+        let start = self.pos;
+        let c = self.peek().unwrap();
+        let two: String = self.chars[start..(start + 2).min(self.chars.len())].iter().collect();
+        if two == "Cl" || two == "Br" {
+            self.pos += 2;
+            return self.add_atom(&two, false, start);
+        }
+        self.pos += 1;
+        match c {
+            'B' | 'C' | 'N' | 'O' | 'P' | 'S' | 'F' | 'I' | '*' => self.add_atom(&c.to_string(), false, start),
+            'b' | 'c' | 'n' | 'o' | 'p' | 's' => self.add_atom(&c.to_string(), true, start),
+            _ => Err(SmilesError::UnexpectedCharacter(start, c))
+        }
+    }
+
+    fn bracket_atom(&mut self) -> Result<usize, SmilesError> {
+        let start = self.pos;
+        self.pos += 1; // [
+        let isotope: u16 = self.count(0, SmilesError::IsotopeOutOfRange(self.pos))?;
+
+        // element symbol, aromatic symbols are lowercase
+        let symbol_start = self.pos;
+        let c = self.peek().ok_or(SmilesError::UnclosedBracket(start))?;
+        let aromatic = c.is_ascii_lowercase();
+        self.pos += 1;
+        let mut symbol = c.to_string();
+        if let Some(c2) = self.peek() {
+            let candidate = format!("{}{}", symbol, c2);
+            let candidate_cap = capitalize(&candidate);
+            let two_letter = if aromatic { ["se", "as", "te"].contains(&candidate.as_str()) } else { c2.is_ascii_lowercase() && atomic_number(&candidate_cap).is_some() };
+            if two_letter {
+                symbol = candidate;
+                self.pos += 1;
+            }
+        }
+        let index = self.add_atom(&symbol, aromatic, symbol_start)?;
+
+        // chirality, including the classes TH, AL, SP, TB and OH with their number
+        if self.peek() == Some('@') {
+            while self.peek() == Some('@') {
+                self.pos += 1;
+            }
+            let class: String = self.chars[self.pos..(self.pos + 2).min(self.chars.len())].iter().collect();
+            if ["TH", "AL", "SP", "TB", "OH"].contains(&class.as_str()) {
+                self.pos += 2;
+                self.number();
+            }
+        }
+
+        // hydrogens
+        let mut hydrogens = 0;
+        if self.peek() == Some('H') {
+            self.pos += 1;
+            hydrogens = self.count(1, SmilesError::HydrogensOutOfRange(self.pos - 1))?;
+        }
+
+        // charge
+        let mut charge: i8 = 0;
+        while let Some(sign) = self.peek().filter(|c| *c == '+' || *c == '-') {
+            let sign_pos = self.pos;
+            self.pos += 1;
+            let unit: i8 = if sign == '+' { 1 } else { -1 };
+            let count: i8 = self.count(1, SmilesError::ChargeOutOfRange(sign_pos))?;
+            charge = charge.checked_add(unit * count).ok_or(SmilesError::ChargeOutOfRange(sign_pos))?;
+        }
+
+        // atom class
+        if self.peek() == Some(':') {
+            self.pos += 1;
+            self.number();
+        }
+
+        if self.peek() != Some(']') {
+            return Err(SmilesError::UnclosedBracket(start));
+        }
+        self.pos += 1;
+
+        let atom = &mut self.mol.atoms[index];
+        atom.isotope = isotope;
+        atom.hydrogens = hydrogens;
+        atom.charge = charge;
+        atom.bracket = true;
+        Ok(index)
+    }
+
+    fn bond_order(c: char) -> Option<BondOrder> {
+        match c {
+            '-' | '/' | '\\' => Some(BondOrder::Single),
+            '=' => Some(BondOrder::Double),
+            '#' => Some(BondOrder::Triple),
+            '$' => Some(BondOrder::Quadruple),
+            ':' => Some(BondOrder::Aromatic),
+            _ => None
+        }
+    }
+
+    fn add_bond(&mut self, a: usize, b: usize, order: Option<BondOrder>) {
+        let order = order.unwrap_or(if self.mol.atoms[a].aromatic && self.mol.atoms[b].aromatic { BondOrder::Aromatic } else { BondOrder::Single });
+        self.mol.bonds.push(Bond { atoms: (a, b), order });
+        let bond = self.mol.bonds.len() - 1;
+        self.mol.neighbours[a].push((b, bond));
+        self.mol.neighbours[b].push((a, bond));
+    }
+
+    fn parse(&mut self) -> Result<(), SmilesError> {
+        let mut previous: Option<usize> = None;
+        let mut branches: Vec<Option<usize>> = vec![];
+        let mut rings: std::collections::HashMap<u32, (usize, Option<BondOrder>)> = std::collections::HashMap::new();
+        let mut bond: Option<BondOrder> = None;
+
+        while let Some(c) = self.peek() {
+            let position = self.pos;
+            match c {
+                '(' => {
+                    if previous.is_none() { return Err(SmilesError::UnbalancedBranch(position)) }
+                    branches.push(previous);
+                    self.pos += 1;
+                }
+                ')' => {
+                    previous = branches.pop().ok_or(SmilesError::UnbalancedBranch(position))?;
+                    self.pos += 1;
+                }
+                '.' => {
+                    previous = None;
+                    self.pos += 1;
+                }
+                '0'..='9' | '%' => {
+                    let atom = previous.ok_or(SmilesError::UnexpectedCharacter(position, c))?;
+                    let ring = if c == '%' {
+                        // exactly two digits follow
+                        let digits = self.chars.get((self.pos + 1)..(self.pos + 3)).filter(|d| d.iter().all(|d| d.is_ascii_digit()));
+                        let digits = digits.ok_or(SmilesError::UnexpectedCharacter(position, c))?;
+                        self.pos += 3;
+                        digits[0].to_digit(10).unwrap() * 10 + digits[1].to_digit(10).unwrap()
+                    } else {
+                        self.pos += 1;
+                        c.to_digit(10).unwrap()
+                    };
+                    match rings.remove(&ring) {
+                        Some((other, _)) if other == atom => return Err(SmilesError::SelfRingClosure(position)),
+                        Some((other, other_bond)) => self.add_bond(other, atom, bond.or(other_bond)),
+                        None => { rings.insert(ring, (atom, bond)); }
+                    }
+                    bond = None;
+                }
+                '[' | 'B' | 'C' | 'N' | 'O' | 'P' | 'S' | 'F' | 'I' | '*' | 'b' | 'c' | 'n' | 'o' | 'p' | 's' => {
+                    let atom = if c == '[' { self.bracket_atom()? } else { self.organic_atom()? };
+                    if let Some(prev) = previous {
+                        self.add_bond(prev, atom, bond);
+                    } else if bond.is_some() {
+                        return Err(SmilesError::DanglingBond(position));
+                    }
+                    bond = None;
+                    previous = Some(atom);
+                }
+                _ => match Self::bond_order(c) {
+                    Some(order) => {
+                        bond = Some(order);
+                        self.pos += 1;
+                    }
+                    None => return Err(SmilesError::UnexpectedCharacter(position, c))
+                }
+            }
+        }
+
+        if !branches.is_empty() { return Err(SmilesError::UnbalancedBranch(self.pos)) }
+        if let Some(ring) = rings.keys().min() { return Err(SmilesError::UnclosedRing(*ring)) }
+        if bond.is_some() { return Err(SmilesError::DanglingBond(self.pos)) }
+        Ok(())
+    }
+}
+
+impl Molecule {
+    pub fn from_smiles(smiles: &str) -> Result<Self, SmilesError> {
+        let mut mol = Self::default();
+        Parser { chars: smiles.trim().chars().collect(), pos: 0, mol: &mut mol }.parse()?;
+        mol.assign_implicit_hydrogens();
+        mol.ring_bonds = mol.find_ring_bonds();
+        Ok(mol)
+    }
+
+    fn assign_implicit_hydrogens(&mut self) {
+        for i in 0..self.atoms.len() {
+            if self.atoms[i].bracket { continue }
+            let valence: u32 = self.neighbours[i].iter().map(|(_, b)| self.bonds[*b].order.valence()).sum();
+            let valences = default_valences(self.atoms[i].atomic_number);
+            let hydrogens = if self.atoms[i].aromatic {
+                // one valence taken by the aromatic system
+                valences.first().map_or(0, |v| v.saturating_sub(valence + 1))
+            } else {
+                valences.iter().find(|v| **v >= valence).map_or(0, |v| v - valence)
+            };
+            self.atoms[i].hydrogens = hydrogens as u8;
+        }
+    }
+
+    /// Bonds in rings are those which are not bridges, found by the lowest reachable DFS order
+    fn find_ring_bonds(&self) -> Vec<bool> {
+        let n = self.atoms.len();
+        let mut order = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut is_bridge = vec![false; self.bonds.len()];
+        let mut counter = 0;
+        for root in 0..n {
+            if order[root] != usize::MAX { continue }
+            // iterative DFS: (atom, bond to parent, next neighbour index)
+            let mut stack: Vec<(usize, Option<usize>, usize)> = vec![(root, None, 0)];
+            order[root] = counter;
+            low[root] = counter;
+            counter += 1;
+            while let Some((atom, parent_bond, next)) = stack.last().copied() {
+                if let Some(&(nbr, bond)) = self.neighbours[atom].get(next) {
+                    stack.last_mut().unwrap().2 += 1;
+                    if Some(bond) == parent_bond { continue }
+                    if order[nbr] == usize::MAX {
+                        order[nbr] = counter;
+                        low[nbr] = counter;
+                        counter += 1;
+                        stack.push((nbr, Some(bond), 0));
+                    } else {
+                        low[atom] = low[atom].min(order[nbr]);
+                    }
+                } else {
+                    stack.pop();
+                    if let (Some(bond), Some(&(parent, _, _))) = (parent_bond, stack.last()) {
+                        low[parent] = low[parent].min(low[atom]);
+                        if low[atom] > order[parent] {
+                            is_bridge[bond] = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        is_bridge.into_iter().map(|b| !b).collect()
+    }
+
+    pub fn atoms(&self) -> &[Atom] { &self.atoms }
+    pub fn bonds(&self) -> &[Bond] { &self.bonds }
+    pub fn neighbours(&self, atom: usize) -> &[(usize, usize)] { &self.neighbours[atom] }
+    pub fn is_ring_bond(&self, bond: usize) -> bool { self.ring_bonds[bond] }
+    pub fn is_ring_atom(&self, atom: usize) -> bool { self.neighbours[atom].iter().any(|(_, b)| self.ring_bonds[*b]) }
+    pub fn len(&self) -> usize { self.atoms.len() }
+    pub fn is_empty(&self) -> bool { self.atoms.is_empty() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let benzene = Molecule::from_smiles("c1ccccc1").unwrap();
+        assert_eq!(benzene.len(), 6);
+        assert_eq!(benzene.bonds().len(), 6);
+        assert!(benzene.atoms().iter().all(|a| a.aromatic && a.hydrogens == 1));
+        assert!(benzene.bonds().iter().all(|b| b.order == BondOrder::Aromatic));
+        assert!((0..6).all(|b| benzene.is_ring_bond(b)));
+
+        let aspirin = Molecule::from_smiles("O=C(C)Oc1ccccc1C(=O)O").unwrap();
+        assert_eq!(aspirin.len(), 13);
+        assert_eq!(aspirin.atoms().iter().map(|a| a.hydrogens as u32).sum::<u32>(), 8);
+        assert_eq!(aspirin.bonds().iter().filter(|b| b.order == BondOrder::Double).count(), 2);
+        assert_eq!((0..aspirin.bonds().len()).filter(|b| aspirin.is_ring_bond(*b)).count(), 6);
+
+        let salt = Molecule::from_smiles("[Na+].[Cl-]").unwrap();
+        assert_eq!(salt.len(), 2);
+        assert_eq!(salt.bonds().len(), 0);
+        assert_eq!(salt.atoms()[0].charge, 1);
+        assert_eq!(salt.atoms()[1].atomic_number, 17);
+
+        let pyrrole = Molecule::from_smiles("c1cc[nH]c1").unwrap();
+        assert_eq!(pyrrole.atoms()[3].hydrogens, 1);
+        let thiophene = Molecule::from_smiles("c1ccsc1").unwrap();
+        assert_eq!(thiophene.atoms()[3].hydrogens, 0);
+
+        let lipitor = Molecule::from_smiles("O=C(O)C[C@H](O)C[C@H](O)CCn2c(c(c(c2c1ccc(F)cc1)c3ccccc3)C(=O)Nc4ccccc4)C(C)C").unwrap();
+        assert_eq!(lipitor.len(), 41);
+        assert_eq!(lipitor.atoms()[4].hydrogens, 1);
+
+        let isotope = Molecule::from_smiles("[13CH3]C(=O)[O-]").unwrap();
+        assert_eq!(isotope.atoms()[0].isotope, 13);
+        assert_eq!(isotope.atoms()[0].hydrogens, 3);
+        assert_eq!(isotope.atoms()[3].charge, -1);
+
+        let ring_closure = Molecule::from_smiles("C%10CC%10").unwrap();
+        assert_eq!(ring_closure.bonds().len(), 3);
+        let nitro = Molecule::from_smiles("COc1cc([N+](=O)[O-])c(OC)cc1CC(C)N").unwrap();
+        assert_eq!(nitro.atoms()[5].charge, 1);
+        let selenophene = Molecule::from_smiles("c1cc[se]c1").unwrap();
+        assert_eq!(selenophene.atoms()[3].atomic_number, 34);
+        let chlorine = Molecule::from_smiles("ClCBr").unwrap();
+        assert_eq!(chlorine.atoms().iter().map(|a| a.atomic_number).collect::<Vec<u8>>(), vec![17, 6, 35]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Molecule::from_smiles("C1CC"), Err(SmilesError::UnclosedRing(1)));
+        assert_eq!(Molecule::from_smiles("C(C"), Err(SmilesError::UnbalancedBranch(3)));
+        assert_eq!(Molecule::from_smiles("CC)"), Err(SmilesError::UnbalancedBranch(2)));
+        assert_eq!(Molecule::from_smiles("C[Xx]"), Err(SmilesError::UnknownElement(2, "X".to_string())));
+        assert_eq!(Molecule::from_smiles("C[CH3"), Err(SmilesError::UnclosedBracket(1)));
+        assert_eq!(Molecule::from_smiles("CC="), Err(SmilesError::DanglingBond(3)));
+        assert_eq!(Molecule::from_smiles("C?"), Err(SmilesError::UnexpectedCharacter(1, '?')));
+        assert_eq!(Molecule::from_smiles("[é]"), Err(SmilesError::UnknownElement(1, "é".to_string())));
+        assert_eq!(Molecule::from_smiles("[Cé]"), Err(SmilesError::UnclosedBracket(0)));
+        assert_eq!(Molecule::from_smiles("[C-128]"), Err(SmilesError::ChargeOutOfRange(2)));
+        assert_eq!(Molecule::from_smiles("[C+127+1]"), Err(SmilesError::ChargeOutOfRange(6)));
+        assert_eq!(Molecule::from_smiles("[N+200]"), Err(SmilesError::ChargeOutOfRange(2)));
+        assert_eq!(Molecule::from_smiles("[N+99999999999]"), Err(SmilesError::ChargeOutOfRange(2)));
+        assert_eq!(Molecule::from_smiles("[N+127]").unwrap().atoms[0].charge, 127);
+        assert_eq!(Molecule::from_smiles("[70000C]"), Err(SmilesError::IsotopeOutOfRange(1)));
+        assert_eq!(Molecule::from_smiles("[99999999999C]"), Err(SmilesError::IsotopeOutOfRange(1)));
+        assert_eq!(Molecule::from_smiles("[CH256]"), Err(SmilesError::HydrogensOutOfRange(2)));
+        assert_eq!(Molecule::from_smiles("C11"), Err(SmilesError::SelfRingClosure(2)));
+        assert_eq!(Molecule::from_smiles("C%11%11"), Err(SmilesError::SelfRingClosure(4)));
+        assert_eq!(Molecule::from_smiles("C%1CC%1"), Err(SmilesError::UnexpectedCharacter(1, '%')));
+        assert_eq!(Molecule::from_smiles("CCC%1"), Err(SmilesError::UnexpectedCharacter(3, '%')));
+        assert_eq!(Molecule::from_smiles("C%+1CC%+1"), Err(SmilesError::UnexpectedCharacter(1, '%')));
+        assert_eq!(Molecule::from_smiles("C%123CC%12C3").unwrap().bonds().len(), 5);
+    }
+}
@@ -0,0 +1,91 @@
+//! Rust Similarity Search
+//!     same input, output and report as the OpenBabel similarity search, with fingerprints of the Rust package
+//! 
+
+use chiral_common::app::chem::openbabel::similarity::*;
+use chiral_common::traits::*;
+
+/// Operator
+pub struct Operator {
+    fpk: chiral_common::app::chem::kinds::Fingerprint,
+    fpg: super::fingerprint::FingerprintGenerator,
+}
+
+impl chiral_common::traits::TraitOperator for Operator {
+    type InputType = Input;
+    type DataType = crate::chem::similarity::Data;
+    type OutputType = Output; 
+    type ReportType = Report;
+
+    fn new(opk: &chiral_common::kinds::Operator) -> Self {
+        match opk {
+            chiral_common::kinds::Operator::RustSimilaritySearching(fpk) => {
+                Self { fpk: fpk.to_owned(), fpg: super::fingerprint::FingerprintGenerator::new(fpk) }
+            },
+            _ => panic!("Operator Kind mismatch")
+        }
+    }
+
+    fn get_kind(&self) -> chiral_common::kinds::Operator {
+        chiral_common::kinds::Operator::RustSimilaritySearching(self.fpk.to_owned())
+    }
+
+    fn prepare_data(&self, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &chiral_common::job::CancelToken) -> Result<Self::DataType, chiral_common::job::JobError> {
+        crate::chem::similarity::prepare_data(&self.fpk, dsk, div_index, ds, token, |smiles_vec| self.fpg.get_fingerprint_for_smiles_vec(smiles_vec))
+    }
+
+    fn compute(&self, input: &Self::InputType, data: &Self::DataType, div_index: &chiral_common::job::DividendIndex, token: &chiral_common::job::CancelToken) -> Result<Self::OutputType, chiral_common::job::JobError> {
+        let mol = super::molecule::Molecule::from_smiles(&input.smiles)
+            .map_err(|e| chiral_common::job::JobError::new(chiral_common::job::ErrorKind::InvalidInput, format!("SMILES {}: {}", input.smiles, e).as_str()).at(div_index))?;
+        let fp_target = self.fpg.get_fingerprint(&mol);
+        let results = data.search_bitbound(input, &fp_target, token);
+
+        Ok(Output { results, top_k: input.top_k })
+    }
+
+    fn report(&self, job_id: chiral_common::job::ID, input: Self::InputType, data: &Self::DataType, output: Self::OutputType) -> Self::ReportType {
+        Report {
+            job_id,
+            cuk: chiral_common::kinds::ComputingUnit::new(self.get_kind(), data.dsk.to_owned()),
+            input, 
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chiral_common::app::chem::kinds::Metric;
+    use chiral_common::data::Dummy;
+
+    #[test]
+    fn test_op() {
+        let dsk = chiral_common::kinds::Dataset::Dummy;
+        let doc_smiles = chiral_common::data::DocSMILES::dummy();
+        let com_fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+        let opk = chiral_common::kinds::Operator::similarity_searching(com_fpk.to_owned());
+        let op = Operator::new(&opk);
+        let fpg = super::super::fingerprint::FingerprintGenerator::new(&com_fpk);
+        let data = crate::chem::similarity::Data::new(dsk.to_owned(), doc_smiles.get_ids().to_vec(), fpg.get_fingerprint_for_smiles_vec(doc_smiles.get_smiles_vec()));
+        assert_eq!(data.len(), 4);
+        let input = Input { smiles: String::from("CC(=O)Nc1ccc(O)cc1"), threshold: 0.0, top_k: Some(2), metric: Metric::Tanimoto };
+        let output = op.compute(&input, &data, &(0, 4), &chiral_common::job::CancelToken::default()).unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output.results[0], (1.0, "label_4".to_string()));
+        let report = op.report("job_id".to_string(), input, &data, output);
+        let report_deserialized = Report::ser_from(&report.ser_to());
+        assert_eq!(report_deserialized.cuk, chiral_common::kinds::ComputingUnit::new(chiral_common::kinds::Operator::RustSimilaritySearching(com_fpk), dsk));
+        assert_eq!(report_deserialized.output.len(), 2);
+        let input_invalid = Input { smiles: String::from("C1CC"), threshold: 0.0, top_k: None, metric: Metric::Tanimoto };
+        let error = op.compute(&input_invalid, &data, &(0, 4), &chiral_common::job::CancelToken::default()).unwrap_err();
+        assert_eq!(error.kind, chiral_common::job::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "invalid input error at dividend 0..4: SMILES C1CC: unclosed ring 1");
+        // a stopped token returns the hits gathered so far, none here
+        let token = chiral_common::job::CancelToken::default();
+        token.cancel();
+        let input_all = Input { smiles: String::from("CC(=O)Nc1ccc(O)cc1"), threshold: 0.0, top_k: None, metric: Metric::Tanimoto };
+        assert_eq!(op.compute(&input_all, &data, &(0, 4), &token).unwrap().len(), 0);
+        assert_eq!(op.compute(&input_invalid, &data, &(0, 4), &token).unwrap_err().kind, chiral_common::job::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,280 @@
+//! Fingerprint Similarity Search
+//!     shared by the similarity searching operators of every fingerprint package
+//!
+
+use chiral_common::app::chem::openbabel::similarity::Input;
+use chiral_common::traits::*;
+use chiral_common::app::chem::fingerprint_index::{FingerprintIndex, Fingerprints, Key};
+
+/// Number of bits set in a fingerprint
+fn popcount(fpd: &[u32]) -> u32 {
+    fpd.iter().map(|w| w.count_ones()).sum()
+}
+
+/// Similarity coefficient between the query fingerprint and a dataset entry fingerprint, given their popcounts
+fn similarity(metric: &chiral_common::app::chem::kinds::Metric, fpd_query: &[u32], n_query: u32, fpd_entry: &[u32], n_entry: u32) -> f32 {
+    let andbits = fpd_query.iter()
+        .zip(fpd_entry.iter())
+        .map(|(q, e)| (q & e).count_ones())
+        .sum();
+    metric.coefficient(andbits, n_query, n_entry, fpd_query.len() as u32 * u32::BITS)
+}
+
+/// Hit kept in the bounded heap of the top-k mode, ordered so that the worst hit is at the top
+struct Hit<'a> {
+    coeff: f32,
+    id: &'a chiral_common::data::types::EntryID
+}
+
+impl<'a> PartialEq for Hit<'a> {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == std::cmp::Ordering::Equal }
+}
+
+impl<'a> Eq for Hit<'a> {}
+
+impl<'a> PartialOrd for Hit<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
+}
+
+impl<'a> Ord for Hit<'a> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other.coeff.total_cmp(&self.coeff).then_with(|| self.id.cmp(other.id))
+    }
+}
+
+/// Bounded heap keeping the k hits with the highest coefficients
+struct TopK<'a> {
+    k: usize,
+    heap: std::collections::BinaryHeap<Hit<'a>>
+}
+
+impl<'a> TopK<'a> {
+    fn new(k: usize) -> Self {
+        Self { k, heap: std::collections::BinaryHeap::with_capacity(k + 1) }
+    }
+
+    fn push(&mut self, coeff: f32, id: &'a chiral_common::data::types::EntryID) {
+        self.heap.push(Hit { coeff, id });
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+
+    /// lowest coefficient kept, once k hits are collected
+    fn worst(&self) -> Option<f32> {
+        if self.heap.len() == self.k {
+            self.heap.peek().map(|hit| hit.coeff)
+        } else {
+            None
+        }
+    }
+
+    fn into_results(self) -> Vec<(f32, chiral_common::data::types::EntryID)> {
+        self.heap.into_sorted_vec().into_iter()
+            .map(|hit| (hit.coeff, hit.id.to_string()))
+            .collect()
+    }
+}
+
+/// Fingerprints of a dividend, grouped by popcount for the BitBound search
+///     the fingerprints loaded from the fingerprint index stay in the memory-mapped file
+pub struct Data {
+    pub dsk: chiral_common::kinds::Dataset,
+    pub ids: Vec<chiral_common::data::types::EntryID>,
+    pub fps: Fingerprints,
+    popcounts: Vec<u32>,
+    order: Vec<usize>, // entry indices sorted by popcount
+    buckets: Vec<(u32, std::ops::Range<usize>)> // popcount and the range of `order` sharing it
+}
+
+impl Data {
+    pub fn new(dsk: chiral_common::kinds::Dataset, ids: Vec<chiral_common::data::types::EntryID>, fps: impl Into<Fingerprints>) -> Self {
+        let fps = fps.into();
+        let popcounts: Vec<u32> = fps.iter().map(popcount).collect();
+        let mut order: Vec<usize> = (0..fps.len()).collect();
+        order.sort_by_key(|&i| popcounts[i]);
+        let mut buckets: Vec<(u32, std::ops::Range<usize>)> = vec![];
+        for (pos, &i) in order.iter().enumerate() {
+            match buckets.last_mut() {
+                Some((n, range)) if *n == popcounts[i] => range.end = pos + 1,
+                _ => buckets.push((popcounts[i], pos..pos + 1))
+            }
+        }
+
+        Self { dsk, ids, fps, popcounts, order, buckets }
+    }
+
+    /// Similarity coefficients above the threshold for the entries of one bucket, as (entry index, coefficient)
+    fn bucket_hits<'a>(&'a self, input: &'a Input, fp_query: &'a [u32], n_query: u32, range: &std::ops::Range<usize>, token: &'a chiral_common::job::CancelToken) -> impl Iterator<Item = (usize, f32)> + 'a {
+        self.order[range.to_owned()].iter()
+            .enumerate()
+            .take_while(|(n, _)| !token.should_stop(*n))
+            .map(move |(_, &i)| (i, similarity(&input.metric, fp_query, n_query, self.fps.get(i), self.popcounts[i])))
+            .filter(|(_, coeff)| *coeff > input.threshold)
+    }
+
+    /// Compare the query against every entry
+    #[cfg(test)]
+    fn search_brute_force(&self, input: &Input, fp_query: &[u32]) -> Vec<(f32, chiral_common::data::types::EntryID)> {
+        let n_query = popcount(fp_query);
+        let hits = self.fps.iter()
+            .zip(self.popcounts.iter())
+            .map(|(fp, n_entry)| similarity(&input.metric, fp_query, n_query, fp, *n_entry))
+            .zip(self.ids.iter())
+            .filter(|(coeff, _)| *coeff > input.threshold);
+        match input.top_k {
+            Some(k) => {
+                let mut top_k = TopK::new(k);
+                hits.for_each(|(coeff, id)| top_k.push(coeff, id));
+                top_k.into_results()
+            }
+            None => hits.map(|(coeff, id)| (coeff, id.to_string())).collect()
+        }
+    }
+
+    /// BitBound search (Swamidass & Baldi, 2007)
+    ///     the coefficient of every metric grows with the common bits, which cannot exceed min(n_query, n_entry),
+    ///     so a bucket of entries is skipped when this bound does not pass the threshold
+    ///     or, in top-k mode, cannot beat the worst hit kept
+    ///     the search stops with the hits found so far once the token is stopped
+    pub fn search_bitbound(&self, input: &Input, fp_query: &[u32], token: &chiral_common::job::CancelToken) -> Vec<(f32, chiral_common::data::types::EntryID)> {
+        let n_query = popcount(fp_query);
+        let nbits = fp_query.len() as u32 * u32::BITS;
+        let mut buckets: Vec<(f32, &std::ops::Range<usize>)> = self.buckets.iter()
+            .map(|(n_entry, range)| (input.metric.coefficient(n_query.min(*n_entry), n_query, *n_entry, nbits), range))
+            .filter(|(bound, _)| *bound > input.threshold)
+            .collect();
+
+        match input.top_k {
+            Some(k) => {
+                buckets.sort_by(|(bound_1, _), (bound_2, _)| bound_2.total_cmp(bound_1));
+                let mut top_k = TopK::new(k);
+                for (bound, range) in buckets {
+                    if top_k.worst().is_some_and(|worst| bound < worst) || token.is_stopped() {
+                        break;
+                    }
+                    for (i, coeff) in self.bucket_hits(input, fp_query, n_query, range, token) {
+                        top_k.push(coeff, &self.ids[i]);
+                    }
+                }
+                top_k.into_results()
+            }
+            None => {
+                let mut hits: Vec<(usize, f32)> = buckets.iter()
+                    .flat_map(|(_, range)| self.bucket_hits(input, fp_query, n_query, range, token))
+                    .collect();
+                hits.sort_by_key(|(i, _)| *i); // keep the dataset order of the brute-force search
+                hits.into_iter()
+                    .map(|(i, coeff)| (coeff, self.ids[i].to_string()))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl TraitData for Data {
+    fn blank() -> Self {
+        Self::new(chiral_common::kinds::Dataset::Empty, vec![], vec![])
+    }
+
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// Load the fingerprints of a dividend from the fingerprint index, or generate and index them
+///     a stopped token ends the generation with the fingerprints of the batches done, which are not indexed
+pub fn prepare_data<F>(fpk: &chiral_common::app::chem::kinds::Fingerprint, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &chiral_common::job::CancelToken, generate: F) -> Result<Data, chiral_common::job::JobError>
+    where F: Fn(&[chiral_common::app::chem::types::SMILES]) -> Vec<chiral_common::app::chem::types::FingerprintData> 
+{
+    // the index is loaded and verified without holding the data store
+    let index = {
+        let ds = ds.lock().unwrap();
+        ds.get_index_dir()
+            .zip(ds.get_source_path(dsk))
+            .map(|(dir, source)| (dir, source, Key::new(dsk.to_owned(), fpk.to_owned(), div_index.to_owned())))
+    };
+    if let Some((dir, source, key)) = index.as_ref() {
+        match FingerprintIndex::load(dir, key, source) {
+            Ok(Some(fpi)) => return Ok(Data::new(dsk.to_owned(), fpi.ids, fpi.fps)),
+            Ok(None) => (),
+            Err(e) => chiral_common::logging::warn(format!("{}, rebuilding {}", e, key.filename()).as_str())
+        }
+    }
+
+    let batches = ds.lock().unwrap().get_id_smiles_batches(dsk, div_index, chiral_common::data::BATCH_SIZE).ok_or_else(|| no_entries_error(dsk, div_index))?;
+    // only the fingerprints are kept from the SMILES of each batch
+    let (mut ids, mut fps) = (vec![], vec![]);
+    for batch in batches {
+        let (mut batch_ids, smiles_vec) = batch.map_err(|e| read_error(dsk, div_index, e))?;
+        if token.is_stopped() {
+            return Ok(Data::new(dsk.to_owned(), ids, fps));
+        }
+        fps.append(&mut generate(&smiles_vec));
+        ids.append(&mut batch_ids);
+    }
+    let data = Data::new(dsk.to_owned(), ids, fps);
+    if let Some((dir, source, key)) = index.as_ref() {
+        if let Err(e) = FingerprintIndex::save(dir, key, source, &data.ids, &data.fps) {
+            chiral_common::logging::warn(format!("{}, writing {}", e, key.filename()).as_str());
+        }
+    }
+
+    Ok(data)
+}
+
+/// Error of a data store without the entries of a dividend
+pub fn no_entries_error(dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex) -> chiral_common::job::JobError {
+    chiral_common::job::JobError::new(chiral_common::job::ErrorKind::DataPreparation, format!("no entries of dataset {}", dsk).as_str()).at(div_index)
+}
+
+/// Error of a dataset file failing to be read in the middle of a dividend
+pub fn read_error(dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, e: std::io::Error) -> chiral_common::job::JobError {
+    chiral_common::job::JobError::new(chiral_common::job::ErrorKind::DataPreparation, format!("{} reading dataset {}", e, dsk).as_str()).at(div_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chiral_common::app::chem::kinds::Metric;
+    use chiral_common::data::Dummy;
+
+    fn data_new(dsk: chiral_common::kinds::Dataset, (ids, smiles_vec): chiral_common::app::chem::types::IdSmilesPairs) -> Data {
+        let fpg = crate::chem::rs::fingerprint::FingerprintGenerator::new(&chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048));
+        Data::new(dsk, ids, fpg.get_fingerprint_for_smiles_vec(&smiles_vec))
+    }
+
+    fn assert_bitbound_same_as_brute_force(data: &Data) {
+        let fpg = crate::chem::rs::fingerprint::FingerprintGenerator::new(&chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048));
+        for smiles in ["CC(=O)Nc1ccc(O)cc1", "c1ccccc1N=O", "O=C(O)CCc1ccccc1"] {
+            let fp_query = fpg.get_fingerprint_for_smiles(&smiles.to_string());
+            for metric in [Metric::Tanimoto, Metric::Dice, Metric::Cosine, Metric::Sokal, Metric::RussellRao, Metric::Tversky { alpha: 0.9, beta: 0.1 }] {
+                for threshold in [0.0, 0.1, 0.3, 0.6, 0.9] {
+                    for top_k in [None, Some(1), Some(3), Some(50)] {
+                        let input = Input { smiles: smiles.to_string(), threshold, top_k, metric };
+                        assert_eq!(data.search_bitbound(&input, &fp_query, &chiral_common::job::CancelToken::default()), data.search_brute_force(&input, &fp_query));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitbound_dummy() {
+        let doc_smiles = chiral_common::data::DocSMILES::dummy();
+        let data = data_new(chiral_common::kinds::Dataset::Dummy, (doc_smiles.get_ids().to_vec(), doc_smiles.get_smiles_vec().to_owned()));
+        assert_eq!(data.order.len(), 4);
+        assert_eq!(data.buckets.iter().map(|(_, range)| range.len()).sum::<usize>(), 4);
+        assert_bitbound_same_as_brute_force(&data);
+    }
+
+    #[test]
+    fn test_bitbound_test_chembl() {
+        let dsk = chiral_common::kinds::Dataset::TestChembl;
+        let data_dir = std::path::PathBuf::from("../../../chiral-db-example-data/ChEMBL");
+        let doc_smiles = chiral_common::data::load_from_path::<chiral_common::data::DocSMILES>(&dsk, &data_dir);
+        assert_eq!(doc_smiles.len(), 10000);
+        let data = data_new(dsk, (doc_smiles.extract_ids(&(0..2000)), doc_smiles.extract_smiles_vec(&(0..2000))));
+        assert_bitbound_same_as_brute_force(&data);
+    }
+}
@@ -1 +1,12 @@
 pub mod chem;
+pub mod runner;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    /// new directory under the system temporary directory, shared by the tests of the crate
+    pub(crate) fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("chiral_apps_{}", chiral_common::utils::generate_id(8)));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
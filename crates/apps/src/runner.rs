@@ -0,0 +1,140 @@
+//! Run an operator on one dividend from the serialized job input
+//!
+
+use chiral_common::traits::*;
+
+/// Serialized output of a dividend, with the number of results
+pub type DividendOutput = (SerializedFormat, usize);
+
+fn run<O>(opk: &chiral_common::kinds::Operator, input_ser: &SerializedFormat, dsk: &chiral_common::kinds::Dataset, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &chiral_common::job::CancelToken) -> Result<DividendOutput, chiral_common::job::JobError>
+    where O: TraitOperator, O::InputType: serde::de::DeserializeOwned, O::OutputType: TraitOutput
+{
+    // a malformed input is rejected before any panic could be taken for a retryable computation error
+    let input: O::InputType = serde_json::from_str(input_ser)
+        .map_err(|e| chiral_common::job::JobError::new(chiral_common::job::ErrorKind::InvalidInput, format!("input cannot be parsed: {}", e).as_str()).at(div_index))?;
+    let op = O::new(opk);
+    let data = op.prepare_data(dsk, div_index, ds, token)?;
+    let output = op.compute(&input, &data, div_index, token)?;
+    Ok((output.ser_to(), output.len()))
+}
+
+/// Operators without an implementation in this build, e.g. OpenBabel ones without the openbabel feature
+fn unavailable(opk: &chiral_common::kinds::Operator, div_index: &chiral_common::job::DividendIndex) -> chiral_common::job::JobError {
+    chiral_common::job::JobError::new(chiral_common::job::ErrorKind::InvalidInput, format!("operator {} is not available", opk).as_str()).at(div_index)
+}
+
+#[cfg(feature = "openbabel")]
+fn is_fingerprint_available(fpk: &chiral_common::app::chem::kinds::Fingerprint) -> bool {
+    crate::chem::openbabel::fingerprint::FingerprintGenerator::available(fpk).is_some()
+}
+
+#[cfg(not(feature = "openbabel"))]
+fn is_fingerprint_available(_fpk: &chiral_common::app::chem::kinds::Fingerprint) -> bool {
+    false
+}
+
+/// Whether the operator runs in this build and environment, e.g. pattern based OpenBabel fingerprints need BABEL_DATADIR
+pub fn is_available(opk: &chiral_common::kinds::Operator) -> bool {
+    match opk {
+        chiral_common::kinds::Operator::OpenBabelSimilaritySearching(fpk) => is_fingerprint_available(fpk),
+        chiral_common::kinds::Operator::OpenBabelSSMatching => cfg!(feature = "openbabel"),
+        chiral_common::kinds::Operator::RustSimilaritySearching(_) => true,
+        chiral_common::kinds::Operator::ReCGenBuild => false
+    }
+}
+
+/// Prepare the data and compute one dividend of a job, a panic of the operator is turned into a computation error
+pub fn run_dividend(req: &chiral_common::job::Requirement, div_index: &chiral_common::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &chiral_common::job::CancelToken) -> Result<DividendOutput, chiral_common::job::JobError> {
+    let (opk, input_ser, dsk) = (req.get_opk(), req.get_ji(), req.get_dsk());
+    if !is_available(opk) {
+        return Err(unavailable(opk, div_index));
+    }
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        match opk {
+            #[cfg(feature = "openbabel")]
+            chiral_common::kinds::Operator::OpenBabelSimilaritySearching(_) => run::<crate::chem::openbabel::similarity::Operator>(opk, input_ser, dsk, div_index, ds, token),
+            #[cfg(feature = "openbabel")]
+            chiral_common::kinds::Operator::OpenBabelSSMatching => run::<crate::chem::openbabel::substructure::Operator>(opk, input_ser, dsk, div_index, ds, token),
+            chiral_common::kinds::Operator::RustSimilaritySearching(_) => run::<crate::chem::rs::similarity::Operator>(opk, input_ser, dsk, div_index, ds, token),
+            _ => Err(unavailable(opk, div_index))
+        }
+    }))
+    .unwrap_or_else(|payload| Err(chiral_common::job::JobError::from_panic(payload, div_index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chiral_common::data::Dummy;
+
+    fn store() -> std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>> {
+        let mut store = chiral_common::data::DocStoreSMILES::new();
+        store.insert(chiral_common::kinds::Dataset::Dummy, chiral_common::data::DocSMILES::dummy());
+        std::sync::Arc::new(std::sync::Mutex::new(store))
+    }
+
+    #[test]
+    fn test_run_dividend() {
+        let fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+        let input = chiral_common::app::chem::openbabel::similarity::Input { smiles: String::from("CC(=O)Nc1ccc(O)cc1"), threshold: 0.5, top_k: None, metric: chiral_common::app::chem::kinds::Metric::Tanimoto };
+        let req = chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::similarity_searching(fpk.to_owned()), chiral_common::kinds::Dataset::Dummy);
+        let token = chiral_common::job::CancelToken::default();
+        let (output_ser, len) = run_dividend(&req, &(0, 4), store(), &token).unwrap();
+        let output = chiral_common::app::chem::openbabel::similarity::Output::ser_from(&output_ser);
+        assert_eq!(len, 1);
+        assert_eq!(output.results[0].1, "label_4");
+
+        let req_recgen = chiral_common::job::Requirement::new("{}".to_string(), chiral_common::kinds::Operator::ReCGenBuild, chiral_common::kinds::Dataset::Empty);
+        assert!(!is_available(req_recgen.get_opk()));
+        assert_eq!(run_dividend(&req_recgen, &(0, 0), store(), &token).unwrap_err().kind, chiral_common::job::ErrorKind::InvalidInput);
+
+        // an OpenBabel search on a fingerprint OpenBabel does not generate is rejected instead of panicking
+        let req_mismatch = chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::OpenBabelSimilaritySearching(fpk.to_owned()), chiral_common::kinds::Dataset::Dummy);
+        assert!(!is_available(req_mismatch.get_opk()));
+        assert_eq!(run_dividend(&req_mismatch, &(0, 4), store(), &token).unwrap_err().kind, chiral_common::job::ErrorKind::InvalidInput);
+
+        // a malformed input is invalid, hence not retried
+        let req_malformed = chiral_common::job::Requirement::new("c1ccccc1".to_string(), req.get_opk().to_owned(), chiral_common::kinds::Dataset::Dummy);
+        let error = run_dividend(&req_malformed, &(0, 4), store(), &token).unwrap_err();
+        assert_eq!(error.kind, chiral_common::job::ErrorKind::InvalidInput);
+        assert_eq!(error.div_index, Some((0, 4)));
+    }
+
+    #[test]
+    fn test_run_dividend_pubchem() {
+        let dir = crate::tests::temp_dir();
+        let path = dir.join("CID-SMILES");
+        std::fs::write(&path, "2244\tCC(=O)OC1=CC=CC=C1C(=O)O\n1983\tCC(=O)NC1=CC=C(C=C1)O\n3672\tCC(C)CC1=CC=C(C=C1)C(C)C(=O)O\n").unwrap();
+        let mut store = chiral_common::data::DataStore::new();
+        store.insert_file(chiral_common::kinds::Dataset::PubChem, path.to_owned()).unwrap();
+        let ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(store));
+        let token = chiral_common::job::CancelToken::default();
+
+        let fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+        let input = chiral_common::app::chem::openbabel::similarity::Input { smiles: String::from("CC(=O)NC1=CC=C(C=C1)O"), threshold: 0.9, top_k: None, metric: chiral_common::app::chem::kinds::Metric::Tanimoto };
+        let req = chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::similarity_searching(fpk), chiral_common::kinds::Dataset::PubChem);
+        let (output_ser, _) = run_dividend(&req, &(1, 3), ds.clone(), &token).unwrap();
+        let output = chiral_common::app::chem::openbabel::similarity::Output::ser_from(&output_ser);
+        assert_eq!(output.results.iter().map(|(_, cid)| cid.as_str()).collect::<Vec<&str>>(), vec!["1983"]);
+
+        #[cfg(feature = "openbabel")]
+        {
+            let mut input = chiral_common::app::chem::openbabel::substructure::Input::new("C(=O)[OH]".to_string());
+            input.has_match_only = true;
+            let req = chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::OpenBabelSSMatching, chiral_common::kinds::Dataset::PubChem);
+            let (output_ser, len) = run_dividend(&req, &(0, 3), ds.clone(), &token).unwrap();
+            let output = chiral_common::app::chem::openbabel::substructure::Output::ser_from(&output_ser);
+            let mut cids: Vec<&str> = output.results.iter().map(|(_, cid)| cid.as_str()).collect();
+            cids.sort();
+            assert_eq!((len, cids), (2, vec!["2244", "3672"]));
+        }
+
+        // a line which cannot be read, or a file removed after its insertion, fails the data preparation
+        std::fs::write(&path, b"2244\tCC(=O)OC1=CC=CC=C1C(=O)O\n1983\tCC(=O)NC1=CC=C(C=C1)O\n\xff\n").unwrap();
+        let error = run_dividend(&req, &(1, 3), ds.clone(), &token).unwrap_err();
+        assert_eq!((error.kind, error.div_index), (chiral_common::job::ErrorKind::DataPreparation, Some((1, 3))));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(run_dividend(&req, &(1, 3), ds, &token).unwrap_err().kind, chiral_common::job::ErrorKind::DataPreparation);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+//! Typed client of the chiral job API
+//!
+//! ```no_run
+//! let client = chiral_client::Client::new("http://127.0.0.1:8080");
+//! let fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+//! let input = chiral_common::app::chem::openbabel::similarity::Input { smiles: "CC(=O)Nc1ccc(O)cc1".to_string(), threshold: 0.5, top_k: Some(10), metric: chiral_common::app::chem::kinds::Metric::Tanimoto };
+//! let job = client.submit_similarity(&input, fpk, chiral_common::kinds::Dataset::TestChembl).unwrap();
+//! client.wait(job.get_id()).unwrap();
+//! let report = client.fetch_report::<chiral_common::app::chem::openbabel::similarity::Report>(job.get_id()).unwrap();
+//! ```
+//!
+
+use chiral_common::traits::Serialization;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    /// the server answered with an error status, e.g. 404 for an unknown job
+    #[error("server error {code}: {message}")]
+    Api { code: u16, message: String },
+    #[error("request not sent: {0}")]
+    Transport(String),
+    #[error("invalid response: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+    #[error("job {0} failed: {1}")]
+    JobFailed(chiral_common::job::ID, chiral_common::job::JobError),
+    #[error("job {0} not finished in {1:?}")]
+    Timeout(chiral_common::job::ID, std::time::Duration)
+}
+
+impl From<ureq::Error> for ClientError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(code, response) => {
+                let body = response.into_string().unwrap_or_default();
+                let message = serde_json::from_str::<serde_json::Value>(&body).ok()
+                    .and_then(|v| v["error"].as_str().map(String::from))
+                    .unwrap_or(body);
+                Self::Api { code, message }
+            }
+            ureq::Error::Transport(t) => Self::Transport(t.to_string())
+        }
+    }
+}
+
+/// Progress events read from a server-sent event stream
+pub struct EventStream {
+    lines: std::io::Lines<std::io::BufReader<Box<dyn std::io::Read + Send + Sync>>>
+}
+
+impl Iterator for EventStream {
+    type Item = Result<chiral_common::event::Event, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            match line {
+                Ok(line) => {
+                    if let Some(data) = line.strip_prefix("data: ") {
+                        return Some(serde_json::from_str(data).map_err(ClientError::from));
+                    }
+                }
+                Err(e) => return Some(Err(ClientError::Transport(e.to_string())))
+            }
+        }
+        None
+    }
+}
+
+pub struct Client {
+    base_url: String,
+    agent: ureq::Agent,
+    poll_interval: std::time::Duration,
+    wait_timeout: std::time::Duration
+}
+
+impl Client {
+    /// base url of the server, e.g. "http://127.0.0.1:8080"
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(std::time::Duration::from_secs(30)).build(),
+            poll_interval: std::time::Duration::from_millis(500),
+            wait_timeout: std::time::Duration::from_secs(3600)
+        }
+    }
+
+    /// timeout of a single HTTP request
+    pub fn with_request_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: std::time::Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// maximum time of wait() for a job to finish
+    pub fn with_wait_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.wait_timeout = timeout;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(response: ureq::Response) -> Result<T, ClientError> {
+        let body = response.into_string().map_err(|e| ClientError::Transport(e.to_string()))?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub fn submit(&self, req: &chiral_common::job::Requirement) -> Result<chiral_common::job::Job, ClientError> {
+        let response = self.agent.post(&self.url("/jobs"))
+            .set("Content-Type", "application/json")
+            .send_string(&req.ser_to())?;
+        Self::parse(response)
+    }
+
+    pub fn submit_similarity(&self, input: &chiral_common::app::chem::openbabel::similarity::Input, fpk: chiral_common::app::chem::kinds::Fingerprint, dsk: chiral_common::kinds::Dataset) -> Result<chiral_common::job::Job, ClientError> {
+        self.submit(&chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::similarity_searching(fpk), dsk))
+    }
+
+    pub fn submit_substructure(&self, input: &chiral_common::app::chem::openbabel::substructure::Input, dsk: chiral_common::kinds::Dataset) -> Result<chiral_common::job::Job, ClientError> {
+        self.submit(&chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::OpenBabelSSMatching, dsk))
+    }
+
+    pub fn job(&self, id: &chiral_common::job::ID) -> Result<chiral_common::job::Job, ClientError> {
+        Self::parse(self.agent.get(&self.url(&format!("/jobs/{}", id))).call()?)
+    }
+
+    pub fn jobs(&self) -> Result<Vec<chiral_common::job::Job>, ClientError> {
+        Self::parse(self.agent.get(&self.url("/jobs")).call()?)
+    }
+
+    pub fn cancel(&self, id: &chiral_common::job::ID) -> Result<chiral_common::job::Job, ClientError> {
+        Self::parse(self.agent.post(&self.url(&format!("/jobs/{}/cancel", id))).call()?)
+    }
+
+    /// poll the job until it is final with its report saved, a failed job is returned as an error
+    pub fn wait(&self, id: &chiral_common::job::ID) -> Result<chiral_common::job::Job, ClientError> {
+        let time_start = std::time::Instant::now();
+        loop {
+            let job = self.job(id)?;
+            if let chiral_common::job::Status::Failed(error) = job.get_status() {
+                return Err(ClientError::JobFailed(id.to_owned(), error.to_owned()));
+            }
+            if job.get_status().is_final() && job.is_report_ready() {
+                return Ok(job);
+            }
+            if time_start.elapsed() >= self.wait_timeout {
+                return Err(ClientError::Timeout(id.to_owned(), self.wait_timeout));
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+
+    fn stream(&self, path: &str) -> Result<EventStream, ClientError> {
+        // no overall timeout for a stream, the server sends a comment at least every 15 seconds
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(std::time::Duration::from_secs(30))
+            .timeout_read(std::time::Duration::from_secs(60))
+            .build();
+        let response = agent.get(&self.url(path)).call()?;
+        Ok(EventStream { lines: std::io::BufRead::lines(std::io::BufReader::new(response.into_reader())) })
+    }
+
+    /// progress events of the job from its creation, ending once the job is settled
+    pub fn job_events(&self, id: &chiral_common::job::ID) -> Result<EventStream, ClientError> {
+        self.stream(&format!("/jobs/{}/events", id))
+    }
+
+    /// progress events of all the jobs from now on
+    pub fn events(&self) -> Result<EventStream, ClientError> {
+        self.stream("/events")
+    }
+
+    /// report of a finished job, e.g. fetch_report::<similarity::Report>(id)
+    pub fn fetch_report<R: serde::de::DeserializeOwned>(&self, id: &chiral_common::job::ID) -> Result<R, ClientError> {
+        Self::parse(self.agent.get(&self.url(&format!("/jobs/{}/report", id))).call()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chiral_server::test_support::{start_dummy_server, temp_dir};
+
+    fn test_client(server: &chiral_server::http::ApiServer) -> Client {
+        Client::new(format!("http://{}/", server.address()).as_str())
+            .with_poll_interval(std::time::Duration::from_millis(10))
+            .with_wait_timeout(std::time::Duration::from_secs(10))
+    }
+
+    fn similarity_input(smiles: &str) -> chiral_common::app::chem::openbabel::similarity::Input {
+        chiral_common::app::chem::openbabel::similarity::Input { smiles: smiles.to_string(), threshold: 0.0, top_k: Some(2), metric: chiral_common::app::chem::kinds::Metric::Tanimoto }
+    }
+
+    #[test]
+    fn test_similarity() {
+        let dir = temp_dir();
+        let server = start_dummy_server(&dir, 2);
+        let client = test_client(&server);
+
+        let fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+        let job = client.submit_similarity(&similarity_input("CC(=O)Nc1ccc(O)cc1"), fpk, chiral_common::kinds::Dataset::Dummy).unwrap();
+        let job = client.wait(job.get_id()).unwrap();
+        assert!(job.is_status(chiral_common::job::Status::Completed));
+        let report = client.fetch_report::<chiral_common::app::chem::openbabel::similarity::Report>(job.get_id()).unwrap();
+        assert_eq!(&report.job_id, job.get_id());
+        assert_eq!(report.output.results.len(), 2);
+        assert_eq!(report.output.results[0], (1.0, "label_4".to_string()));
+        assert_eq!(client.jobs().unwrap().len(), 1);
+        let events: Vec<chiral_common::event::Event> = client.job_events(job.get_id()).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(events.first().unwrap().kind, chiral_common::event::EventKind::Status { status: chiral_common::job::Status::Created });
+        assert_eq!(events.last().unwrap().kind, chiral_common::event::EventKind::ReportReady);
+
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_substructure() {
+        let dir = temp_dir();
+        let server = start_dummy_server(&dir, 1);
+        let client = test_client(&server);
+
+        let mut input = chiral_common::app::chem::openbabel::substructure::Input::new("C(=O)O".to_string());
+        input.has_match_only = true;
+        let job = client.submit_substructure(&input, chiral_common::kinds::Dataset::Dummy).unwrap();
+        let job = client.wait(job.get_id()).unwrap();
+        let report = client.fetch_report::<chiral_common::app::chem::openbabel::substructure::Report>(job.get_id()).unwrap();
+        assert_eq!(report.input, input);
+        let mut ids: Vec<String> = report.output.results.into_iter().map(|(_, id)| id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["label_1", "label_2"]);
+
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = temp_dir();
+        let server = start_dummy_server(&dir, 1);
+        let client = test_client(&server);
+        let fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+
+        assert!(matches!(client.job(&"missing".to_string()), Err(ClientError::Api { code: 404, .. })));
+        assert!(matches!(client.fetch_report::<serde_json::Value>(&"missing".to_string()), Err(ClientError::Api { code: 404, .. })));
+        let error = client.submit_similarity(&similarity_input("CC"), fpk.to_owned(), chiral_common::kinds::Dataset::Chembl30).unwrap_err();
+        assert!(matches!(&error, ClientError::Api { code: 400, message } if message.contains("not available")));
+
+        // an invalid query SMILES fails the job
+        let job = client.submit_similarity(&similarity_input("C1CC"), fpk.to_owned(), chiral_common::kinds::Dataset::Dummy).unwrap();
+        match client.wait(job.get_id()) {
+            Err(ClientError::JobFailed(id, error)) => {
+                assert_eq!(&id, job.get_id());
+                assert_eq!(error.kind, chiral_common::job::ErrorKind::InvalidInput);
+            }
+            result => panic!("unexpected {:?}", result)
+        }
+        server.stop();
+
+        // without workers the job stays queued
+        let server = start_dummy_server(&dir, 0);
+        let client = test_client(&server).with_wait_timeout(std::time::Duration::from_millis(50));
+        let job = client.submit_similarity(&similarity_input("CC"), fpk, chiral_common::kinds::Dataset::Dummy).unwrap();
+        assert!(matches!(client.wait(job.get_id()), Err(ClientError::Timeout(..))));
+        assert!(matches!(client.fetch_report::<serde_json::Value>(job.get_id()), Err(ClientError::Api { code: 409, .. })));
+        assert!(client.cancel(job.get_id()).unwrap().is_status(chiral_common::job::Status::Cancelled));
+        let address = server.address();
+        server.stop();
+
+        // the server is gone
+        let client = Client::new(format!("http://{}", address).as_str()).with_request_timeout(std::time::Duration::from_secs(1));
+        assert!(matches!(client.jobs(), Err(ClientError::Transport(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+//! Persistent Fingerprint Index
+//!     fingerprints of a dividend stored on disk, keyed by dataset, fingerprint kind and dividend index
+//!     the index records the size and modification time of the source file, and is stale once they change
+//!
+//! File layout (little endian)
+//!     magic, version, key, source size, source modification time, entry count, payload checksum (crc32), payload
+//!     payload: the length-prefixed ids, the word counts of the fingerprints, padding to 4 bytes, the fingerprint words
+//!
+//! The fingerprint words are read from the memory-mapped file in place, so a loaded index holds only the ids
+//! and the offsets of the fingerprints in memory.
+//!
+
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"CHIRFPIX";
+const VERSION: u32 = 2;
+const EXTENSION: &str = "fpi";
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("fingerprint index io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not a fingerprint index file")]
+    BadMagic,
+    #[error("fingerprint index version {0} not supported, expecting {VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("fingerprint index truncated")]
+    Truncated,
+    #[error("fingerprint index key mismatch")]
+    KeyMismatch,
+    #[error("fingerprint index checksum mismatch")]
+    ChecksumMismatch,
+    #[error("fingerprint index entry id is not utf8")]
+    InvalidID
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    dsk: crate::kinds::Dataset,
+    fpk: crate::app::chem::kinds::Fingerprint,
+    div_index: crate::job::DividendIndex
+}
+
+impl Key {
+    pub fn new(dsk: crate::kinds::Dataset, fpk: crate::app::chem::kinds::Fingerprint, div_index: crate::job::DividendIndex) -> Self {
+        Self { dsk, fpk, div_index }
+    }
+
+    pub fn filename(&self) -> String {
+        format!("{}_{}_{}_{}.{}", self.dsk, self.fpk.to_string(), self.div_index.0, self.div_index.1, EXTENSION)
+    }
+
+    /// bytes written by write_to
+    fn len(&self) -> usize {
+        let mut buf = vec![];
+        self.write_to(&mut buf);
+        buf.len()
+    }
+
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        write_bytes(buf, self.dsk.to_string().as_bytes());
+        write_bytes(buf, self.fpk.to_string().as_bytes());
+        buf.extend_from_slice(&(self.div_index.0 as u64).to_le_bytes());
+        buf.extend_from_slice(&(self.div_index.1 as u64).to_le_bytes());
+    }
+}
+
+/// Size and modification time of the source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SourceStamp {
+    len: u64,
+    secs: u64,
+    nanos: u32
+}
+
+impl std::fmt::Display for SourceStamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}.{:09}", self.len, self.secs, self.nanos)
+    }
+}
+
+impl SourceStamp {
+    pub(crate) fn new(source: &std::path::Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(source)?;
+        let modified = metadata.modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self { len: metadata.len(), secs: modified.as_secs(), nanos: modified.subsec_nanos() })
+    }
+}
+
+/// bytes to add after pos to align it to 4 bytes
+fn padding(pos: usize) -> usize {
+    (4 - pos % 4) % 4
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], IndexError> {
+        let bytes = self.buf.get(self.pos..self.pos + n).ok_or(IndexError::Truncated)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, IndexError> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    fn u64(&mut self) -> Result<u64, IndexError> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+
+    fn bytes(&mut self) -> Result<&'a [u8], IndexError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn rest(&self) -> &'a [u8] { &self.buf[self.pos..] }
+}
+
+#[derive(Debug)]
+enum Words {
+    Owned(Vec<u32>),
+    /// words from the byte offset of the mapped file, aligned to 4 bytes
+    Mapped { mmap: memmap2::Mmap, start: usize, len: usize }
+}
+
+/// Fingerprint words of the entries of a dividend, in memory or mapped from an index file
+#[derive(Debug)]
+pub struct Fingerprints {
+    words: Words,
+    /// the words of entry i are offsets[i]..offsets[i + 1]
+    offsets: Vec<usize>
+}
+
+impl Fingerprints {
+    fn words(&self) -> &[u32] {
+        match &self.words {
+            Words::Owned(words) => words,
+            // Safety: the range is inside the mapping and aligned to 4 bytes, checked at load on a little endian target,
+            //     and index files are only replaced by rename, never modified in place
+            Words::Mapped { mmap, start, len } => unsafe { std::slice::from_raw_parts(mmap.as_ptr().add(*start) as *const u32, *len) }
+        }
+    }
+
+    pub fn get(&self, i: usize) -> &[u32] {
+        &self.words()[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u32]> {
+        let words = self.words();
+        self.offsets.windows(2).map(move |w| &words[w[0]..w[1]])
+    }
+
+    pub fn len(&self) -> usize { self.offsets.len() - 1 }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    pub fn is_mapped(&self) -> bool { matches!(self.words, Words::Mapped { .. }) }
+}
+
+impl From<Vec<crate::app::chem::types::FingerprintData>> for Fingerprints {
+    fn from(fps: Vec<crate::app::chem::types::FingerprintData>) -> Self {
+        let mut offsets = Vec::with_capacity(fps.len() + 1);
+        offsets.push(0);
+        for fp in fps.iter() {
+            offsets.push(offsets.last().unwrap() + fp.len());
+        }
+        Self { words: Words::Owned(fps.concat()), offsets }
+    }
+}
+
+impl PartialEq for Fingerprints {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+/// Fingerprints of a dividend
+pub struct FingerprintIndex {
+    pub ids: Vec<crate::data::types::EntryID>,
+    pub fps: Fingerprints
+}
+
+impl FingerprintIndex {
+    pub fn filepath(dir: &std::path::Path, key: &Key) -> std::path::PathBuf {
+        dir.join(key.filename())
+    }
+
+    /// Load the index from a memory-mapped file
+    ///     Ok(None) if the file does not exist or the source file has changed since it was written
+    pub fn load(dir: &std::path::Path, key: &Key, source: &std::path::Path) -> Result<Option<Self>, IndexError> {
+        let file = match std::fs::File::open(Self::filepath(dir, key)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into())
+        };
+        // Safety: index files are only replaced by rename, never modified in place
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let mut reader = Reader { buf: &mmap, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC { return Err(IndexError::BadMagic) }
+        let version = reader.u32()?;
+        if version != VERSION { return Err(IndexError::UnsupportedVersion(version)) }
+        let mut key_bytes = vec![];
+        key.write_to(&mut key_bytes);
+        if reader.take(key_bytes.len())? != key_bytes.as_slice() { return Err(IndexError::KeyMismatch) }
+        let stamp = SourceStamp { len: reader.u64()?, secs: reader.u64()?, nanos: reader.u32()? };
+        if stamp != SourceStamp::new(source)? { return Ok(None) }
+        let count = reader.u64()? as usize;
+        let checksum = reader.u32()?;
+        if crc32fast::hash(reader.rest()) != checksum { return Err(IndexError::ChecksumMismatch) }
+
+        let mut ids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let id = std::str::from_utf8(reader.bytes()?).map_err(|_| IndexError::InvalidID)?;
+            ids.push(id.to_string());
+        }
+        let mut offsets = Vec::with_capacity(count + 1);
+        offsets.push(0);
+        for _ in 0..count {
+            offsets.push(offsets.last().unwrap() + reader.u32()? as usize);
+        }
+        reader.take(padding(reader.pos))?;
+        let (start, len) = (reader.pos, *offsets.last().unwrap());
+        let bytes = reader.take(len * 4)?;
+        let words = if cfg!(target_endian = "little") && (mmap.as_ptr() as usize + start).is_multiple_of(4) {
+            Words::Mapped { mmap, start, len }
+        } else {
+            Words::Owned(bytes.chunks_exact(4).map(|w| u32::from_le_bytes(w.try_into().unwrap())).collect())
+        };
+
+        Ok(Some(Self { ids, fps: Fingerprints { words, offsets } }))
+    }
+
+    /// Write the index, replacing any existing file
+    pub fn save(dir: &std::path::Path, key: &Key, source: &std::path::Path, ids: &[crate::data::types::EntryID], fps: &Fingerprints) -> Result<(), IndexError> {
+        let mut payload = vec![];
+        for id in ids.iter() {
+            write_bytes(&mut payload, id.as_bytes());
+        }
+        for fp in fps.iter() {
+            payload.extend_from_slice(&(fp.len() as u32).to_le_bytes());
+        }
+        let header_len = MAGIC.len() + 4 + key.len() + 8 + 8 + 4 + 8 + 4;
+        payload.resize(payload.len() + padding(header_len + payload.len()), 0);
+        for w in fps.words().iter() {
+            payload.extend_from_slice(&w.to_le_bytes());
+        }
+
+        let stamp = SourceStamp::new(source)?;
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        key.write_to(&mut header);
+        header.extend_from_slice(&stamp.len.to_le_bytes());
+        header.extend_from_slice(&stamp.secs.to_le_bytes());
+        header.extend_from_slice(&stamp.nanos.to_le_bytes());
+        header.extend_from_slice(&(ids.len() as u64).to_le_bytes());
+        header.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        debug_assert_eq!(header.len(), header_len);
+
+        std::fs::create_dir_all(dir)?;
+        let filepath = Self::filepath(dir, key);
+        let filepath_tmp = filepath.with_extension(format!("{}.{}", EXTENSION, crate::utils::generate_id(8)));
+        std::fs::write(&filepath_tmp, [header, payload].concat())?;
+        std::fs::rename(&filepath_tmp, &filepath)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let dir = crate::utils::tests::temp_dir();
+        let source = dir.join("source.txt");
+        std::fs::write(&source, "id\tsmiles\nid_1\tc1ccccc1\n").unwrap();
+        let key = Key::new(crate::kinds::Dataset::TestChembl, crate::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(64), (0, 2));
+        assert_eq!(key.filename(), "test_chembl_ob_ecfp4_64_0_2.fpi");
+        assert!(FingerprintIndex::load(&dir, &key, &source).unwrap().is_none());
+
+        let ids = vec!["id_1".to_string(), "id_2".to_string()];
+        let fps = Fingerprints::from(vec![vec![1, 2, 3], vec![u32::MAX, 0, 7]]);
+        FingerprintIndex::save(&dir, &key, &source, &ids, &fps).unwrap();
+        let loaded = FingerprintIndex::load(&dir, &key, &source).unwrap().unwrap();
+        assert_eq!(loaded.ids, ids);
+        assert_eq!(loaded.fps, fps);
+        assert_eq!(loaded.fps.get(1), &[u32::MAX, 0, 7]);
+        assert_eq!(loaded.fps.is_mapped(), cfg!(target_endian = "little"));
+        assert!(!fps.is_mapped());
+
+        // another key does not match the file
+        let key_other = Key::new(crate::kinds::Dataset::TestChembl, crate::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(64), (2, 4));
+        std::fs::copy(FingerprintIndex::filepath(&dir, &key), FingerprintIndex::filepath(&dir, &key_other)).unwrap();
+        assert!(matches!(FingerprintIndex::load(&dir, &key_other, &source), Err(IndexError::KeyMismatch)));
+
+        // corrupted payload
+        let filepath = FingerprintIndex::filepath(&dir, &key);
+        let mut content = std::fs::read(&filepath).unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 0xff;
+        std::fs::write(&filepath, &content).unwrap();
+        assert!(matches!(FingerprintIndex::load(&dir, &key, &source), Err(IndexError::ChecksumMismatch)));
+
+        // stale once the source file changes
+        FingerprintIndex::save(&dir, &key, &source, &ids, &fps).unwrap();
+        std::fs::write(&source, "id\tsmiles\nid_1\tc1ccccc1\nid_2\tCCO\n").unwrap();
+        assert!(FingerprintIndex::load(&dir, &key, &source).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
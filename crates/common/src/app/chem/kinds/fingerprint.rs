@@ -6,7 +6,8 @@ use serde::{Serialize, Deserialize};
 pub enum ParseStringError {
     NotThreeParts(String),
     NotIntegerNbits(String),
-    TypeNotFound((String, String))
+    TypeNotFound((String, String)),
+    UnsupportedNbits((String, u32))
 }
 
 impl std::error::Error for ParseStringError {
@@ -14,7 +15,8 @@ impl std::error::Error for ParseStringError {
         match *self {
             ParseStringError::NotThreeParts(_) => None,
             ParseStringError::NotIntegerNbits(_) => None,
-            ParseStringError::TypeNotFound(_) => None
+            ParseStringError::TypeNotFound(_) => None,
+            ParseStringError::UnsupportedNbits(_) => None
         }
     }
 }
@@ -24,14 +26,16 @@ impl std::fmt::Display for ParseStringError {
         match self {
             Self::NotThreeParts(input_str) => format!("Input str {} shall be in format (package)_(fingperint)_(nbits), eg. ob_ecfp2_512", input_str).fmt(f),
             Self::NotIntegerNbits(nbits_str) => format!("str {} cannot be integer for parameter nbits", nbits_str).fmt(f),
-            Self::TypeNotFound((pkg_str, fp_str)) => format!("Cannot find package {} or fingerprint  {}", pkg_str, fp_str).fmt(f)
+            Self::TypeNotFound((pkg_str, fp_str)) => format!("Cannot find package {} or fingerprint  {}", pkg_str, fp_str).fmt(f),
+            Self::UnsupportedNbits((fp_str, nbits)) => format!("nbits {} not supported by fingerprint {}, eg. fp2 takes powers of two up to 1024, ecfp multiples of 32, fp3 64, fp4 512, maccs 256", nbits, fp_str).fmt(f)
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]  
 pub enum Kind {
-    OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint }
+    OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint },
+    Rust { kind: crate::app::chem::rs::kinds::Fingerprint }
 }
 
 impl std::default::Default for Kind {
@@ -44,6 +48,7 @@ impl Kind {
     pub fn kind_openbabel_fp2(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::FP2 { nbits } } }
     pub fn kind_openbabel_fp3(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::FP3 { nbits } } }
     pub fn kind_openbabel_fp4(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::FP4 { nbits } } }
+    pub fn kind_openbabel_maccs(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::MACCS { nbits } } }
     pub fn kind_openbabel_ecfp0(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::ECFP0 { nbits } } }
     pub fn kind_openbabel_ecfp2(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::ECFP2 { nbits } } }
     pub fn kind_openbabel_ecfp4(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::ECFP4 { nbits } } }
@@ -51,19 +56,39 @@ impl Kind {
     pub fn kind_openbabel_ecfp8(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::ECFP8 { nbits } } }
     pub fn kind_openbabel_ecfp10(nbits: u32) -> Kind { Kind::OpenBabel { kind: crate::app::chem::openbabel::kinds::Fingerprint::ECFP10 { nbits } } }
 
+    pub fn kind_rust_ecfp(diameter: u32, nbits: u32) -> Kind { Kind::Rust { kind: crate::app::chem::rs::kinds::Fingerprint::ECFP { diameter, nbits } } }
+    pub fn kind_rust_path(length: u32, nbits: u32) -> Kind { Kind::Rust { kind: crate::app::chem::rs::kinds::Fingerprint::Path { length, nbits } } }
+
+    pub fn get_nbits(&self) -> u32 {
+        match self {
+            Self::OpenBabel { kind } => kind.get_nbits(),
+            Self::Rust { kind } => kind.get_nbits()
+        }
+    }
+
     pub fn new(pkg_str: &str, fp_str: &str, nbits: u32) -> Result<Self, ParseStringError> {
-        match (pkg_str, fp_str) {
+        let kind = match (pkg_str, fp_str) {
             ("ob", "fp2") => Ok(Self::kind_openbabel_fp2(nbits)),
             ("ob", "fp3") => Ok(Self::kind_openbabel_fp3(nbits)),
             ("ob", "fp4") => Ok(Self::kind_openbabel_fp4(nbits)),
+            ("ob", "maccs") => Ok(Self::kind_openbabel_maccs(nbits)),
             ("ob", "ecfp0") => Ok(Self::kind_openbabel_ecfp0(nbits)),
             ("ob", "ecfp2") => Ok(Self::kind_openbabel_ecfp2(nbits)),
             ("ob", "ecfp4") => Ok(Self::kind_openbabel_ecfp4(nbits)),
             ("ob", "ecfp6") => Ok(Self::kind_openbabel_ecfp6(nbits)),
             ("ob", "ecfp8") => Ok(Self::kind_openbabel_ecfp8(nbits)),
             ("ob", "ecfp10") => Ok(Self::kind_openbabel_ecfp10(nbits)),
+            ("rs", _) => crate::app::chem::rs::kinds::Fingerprint::new(fp_str, nbits)
+                .map(|kind| Self::Rust { kind })
+                .ok_or(ParseStringError::TypeNotFound((pkg_str.to_string(), fp_str.to_string()))),
             _ => Err(ParseStringError::TypeNotFound((pkg_str.to_string(), fp_str.to_string())))
-        } 
+        }?;
+
+        match &kind {
+            Self::OpenBabel { kind: ob_kind } if !ob_kind.is_valid_nbits() => Err(ParseStringError::UnsupportedNbits((fp_str.to_string(), nbits))),
+            Self::Rust { kind: rs_kind } if !rs_kind.is_valid() => Err(ParseStringError::UnsupportedNbits((fp_str.to_string(), nbits))),
+            _ => Ok(kind)
+        }
     }
 }
 
@@ -87,6 +112,7 @@ impl std::string::ToString for Kind {
                     crate::app::chem::openbabel::kinds::Fingerprint::FP2 { nbits } => format!("ob_fp2_{}", nbits),
                     crate::app::chem::openbabel::kinds::Fingerprint::FP3 { nbits } => format!("ob_fp3_{}", nbits),
                     crate::app::chem::openbabel::kinds::Fingerprint::FP4 { nbits } => format!("ob_fp4_{}", nbits),
+                    crate::app::chem::openbabel::kinds::Fingerprint::MACCS { nbits } => format!("ob_maccs_{}", nbits),
                     crate::app::chem::openbabel::kinds::Fingerprint::ECFP0 { nbits } => format!("ob_ecfp0_{}", nbits),
                     crate::app::chem::openbabel::kinds::Fingerprint::ECFP2 { nbits } => format!("ob_ecfp2_{}", nbits),
                     crate::app::chem::openbabel::kinds::Fingerprint::ECFP4 { nbits } => format!("ob_ecfp4_{}", nbits),
@@ -95,6 +121,7 @@ impl std::string::ToString for Kind {
                     crate::app::chem::openbabel::kinds::Fingerprint::ECFP10 { nbits } => format!("ob_ecfp10_{}", nbits),
                 }
             }
+            Self::Rust { kind } => format!("rs_{}_{}", kind.name(), kind.get_nbits())
         }
     }
 }
@@ -118,5 +145,25 @@ mod tests {
         assert_eq!(fp4, Err(ParseStringError::TypeNotFound(("rdkit".to_string(), "fp4".to_string()))));
         let fp5 = Kind::from_str("ob_fp5_512");
         assert_eq!(fp5, Err(ParseStringError::TypeNotFound(("ob".to_string(), "fp5".to_string()))));
+        let fp6 = Kind::from_str("ob_maccs_256");
+        assert_eq!(fp6, Ok(Kind::kind_openbabel_maccs(256)));
+        assert_eq!(fp6.unwrap().to_string(), "ob_maccs_256");
+        assert_eq!(Kind::from_str("ob_maccs_166"), Err(ParseStringError::UnsupportedNbits(("maccs".to_string(), 166))));
+        assert_eq!(Kind::from_str("ob_fp3_1024"), Err(ParseStringError::UnsupportedNbits(("fp3".to_string(), 1024))));
+        assert_eq!(Kind::from_str("ob_fp3_64"), Ok(Kind::kind_openbabel_fp3(64)));
+        assert_eq!(Kind::from_str("ob_fp2_2048"), Err(ParseStringError::UnsupportedNbits(("fp2".to_string(), 2048))));
+        assert_eq!(Kind::from_str("ob_fp2_512"), Ok(Kind::kind_openbabel_fp2(512)));
+        assert_eq!(Kind::from_str("ob_ecfp4_100"), Err(ParseStringError::UnsupportedNbits(("ecfp4".to_string(), 100))));
+        assert_eq!(Kind::from_str("ob_ecfp4_2048"), Ok(Kind::kind_openbabel_ecfp4(2048)));
+        let fp7 = Kind::from_str("rs_ecfp4_2048");
+        assert_eq!(fp7, Ok(Kind::kind_rust_ecfp(4, 2048)));
+        assert_eq!(fp7.unwrap().to_string(), "rs_ecfp4_2048");
+        let fp8 = Kind::from_str("rs_path7_1024");
+        assert_eq!(fp8, Ok(Kind::kind_rust_path(7, 1024)));
+        assert_eq!(fp8.unwrap().to_string(), "rs_path7_1024");
+        assert_eq!(Kind::from_str("rs_ecfp3_1024"), Err(ParseStringError::UnsupportedNbits(("ecfp3".to_string(), 1024))));
+        assert_eq!(Kind::from_str("rs_path7_1000"), Err(ParseStringError::UnsupportedNbits(("path7".to_string(), 1000))));
+        assert_eq!(Kind::from_str("rs_fp2_1024"), Err(ParseStringError::TypeNotFound(("rs".to_string(), "fp2".to_string()))));
+        assert_eq!(Kind::from_str("rs_ecfp_1024"), Err(ParseStringError::TypeNotFound(("rs".to_string(), "ecfp".to_string()))));
     }
 }
\ No newline at end of file
@@ -0,0 +1,183 @@
+//! Similarity Metric Kind
+
+use serde::{Serialize, Deserialize};
+use chiral_derive::Serialization;
+use crate::traits::{Serialization, SerializedFormat};
+
+#[derive(PartialEq, Debug)]
+pub enum ParseStringError {
+    NotFloatParameter(String),
+    WrongParameterCount(String),
+    InvalidParameter(String),
+    TypeNotFound(String)
+}
+
+impl std::error::Error for ParseStringError {}
+
+impl std::fmt::Display for ParseStringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFloatParameter(param_str) => format!("str {} cannot be float for metric parameter", param_str).fmt(f),
+            Self::WrongParameterCount(input_str) => format!("Input str {} shall be in format tversky_(alpha)_(beta), eg. tversky_0.9_0.1", input_str).fmt(f),
+            Self::InvalidParameter(input_str) => format!("Input str {} shall have finite non-negative alpha and beta, not both 0", input_str).fmt(f),
+            Self::TypeNotFound(metric_str) => format!("Cannot find similarity metric {}", metric_str).fmt(f)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "Unchecked")]
+pub enum Kind {
+    #[default]
+    Tanimoto,
+    Dice,
+    Cosine,
+    Sokal,
+    RussellRao,
+    /// asymmetric, alpha weights the bits only in the query, beta the bits only in the entry
+    Tversky { alpha: f32, beta: f32 }
+}
+
+/// Kind as deserialized, before its parameters are validated
+#[derive(Deserialize)]
+enum Unchecked {
+    Tanimoto,
+    Dice,
+    Cosine,
+    Sokal,
+    RussellRao,
+    Tversky { alpha: f32, beta: f32 }
+}
+
+impl TryFrom<Unchecked> for Kind {
+    type Error = ParseStringError;
+
+    fn try_from(unchecked: Unchecked) -> Result<Self, Self::Error> {
+        let kind = match unchecked {
+            Unchecked::Tanimoto => Self::Tanimoto,
+            Unchecked::Dice => Self::Dice,
+            Unchecked::Cosine => Self::Cosine,
+            Unchecked::Sokal => Self::Sokal,
+            Unchecked::RussellRao => Self::RussellRao,
+            Unchecked::Tversky { alpha, beta } => Self::Tversky { alpha, beta }
+        };
+        kind.validate()?;
+        Ok(kind)
+    }
+}
+
+impl Kind {
+    /// Tversky weights shall be finite, non-negative and not both 0,
+    /// so that coefficients stay within [0, 1], which the BitBound pruning relies on
+    pub fn validate(&self) -> Result<(), ParseStringError> {
+        match self {
+            Self::Tversky { alpha, beta } => {
+                let valid = |w: f32| w.is_finite() && w >= 0.0;
+                if !valid(*alpha) || !valid(*beta) || alpha + beta == 0.0 {
+                    return Err(ParseStringError::InvalidParameter(self.to_string()));
+                }
+                Ok(())
+            },
+            _ => Ok(())
+        }
+    }
+
+    /// Coefficient from bit counts
+    ///     common: bits set in both fingerprints
+    ///     n_query, n_entry: bits set in the query and the dataset entry
+    ///     nbits: length of the fingerprint in bits
+    pub fn coefficient(&self, common: u32, n_query: u32, n_entry: u32, nbits: u32) -> f32 {
+        let (c, a, b) = (common as f32, n_query as f32, n_entry as f32);
+        let (numerator, denominator) = match self {
+            Self::Tanimoto => (c, a + b - c),
+            Self::Dice => (2.0 * c, a + b),
+            Self::Cosine => (c, (a * b).sqrt()),
+            Self::Sokal => (c, 2.0 * a + 2.0 * b - 3.0 * c),
+            Self::RussellRao => (c, nbits as f32),
+            Self::Tversky { alpha, beta } => (c, alpha * (a - c) + beta * (b - c) + c)
+        };
+
+        if denominator > 0.0 {
+            numerator / denominator
+        } else {
+            0.0
+        }
+    }
+}
+
+impl std::str::FromStr for Kind {
+    type Err = ParseStringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split('_').collect::<Vec<&str>>();
+        match parts[0] {
+            "tanimoto" if parts.len() == 1 => Ok(Self::Tanimoto),
+            "dice" if parts.len() == 1 => Ok(Self::Dice),
+            "cosine" if parts.len() == 1 => Ok(Self::Cosine),
+            "sokal" if parts.len() == 1 => Ok(Self::Sokal),
+            "russell" if parts.len() == 2 && parts[1] == "rao" => Ok(Self::RussellRao),
+            "tversky" => {
+                if parts.len() != 3 { return Err(ParseStringError::WrongParameterCount(s.to_string())) }
+                let alpha: f32 = parts[1].parse().or(Err(ParseStringError::NotFloatParameter(parts[1].to_string())))?;
+                let beta: f32 = parts[2].parse().or(Err(ParseStringError::NotFloatParameter(parts[2].to_string())))?;
+                let kind = Self::Tversky { alpha, beta };
+                kind.validate().or(Err(ParseStringError::InvalidParameter(s.to_string())))?;
+                Ok(kind)
+            }
+            _ => Err(ParseStringError::TypeNotFound(s.to_string()))
+        }
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tanimoto => write!(f, "tanimoto"),
+            Self::Dice => write!(f, "dice"),
+            Self::Cosine => write!(f, "cosine"),
+            Self::Sokal => write!(f, "sokal"),
+            Self::RussellRao => write!(f, "russell_rao"),
+            Self::Tversky { alpha, beta } => write!(f, "tversky_{}_{}", alpha, beta)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_strum() {
+        assert_eq!(Kind::from_str("tanimoto"), Ok(Kind::Tanimoto));
+        assert_eq!(Kind::from_str("russell_rao"), Ok(Kind::RussellRao));
+        let tversky = Kind::from_str("tversky_0.9_0.1");
+        assert_eq!(tversky, Ok(Kind::Tversky { alpha: 0.9, beta: 0.1 }));
+        assert_eq!(tversky.unwrap().to_string(), "tversky_0.9_0.1");
+        assert_eq!(Kind::from_str("tversky_0.9"), Err(ParseStringError::WrongParameterCount("tversky_0.9".to_string())));
+        assert_eq!(Kind::from_str("tversky_a_0.1"), Err(ParseStringError::NotFloatParameter("a".to_string())));
+        for invalid in ["tversky_-0.1_0.5", "tversky_NaN_0.5", "tversky_0.5_inf", "tversky_0_0"] {
+            assert_eq!(Kind::from_str(invalid), Err(ParseStringError::InvalidParameter(invalid.to_string())));
+        }
+        assert_eq!(Kind::from_str("tversky_0_1"), Ok(Kind::Tversky { alpha: 0.0, beta: 1.0 }));
+        assert_eq!(Kind::from_str("jaccard"), Err(ParseStringError::TypeNotFound("jaccard".to_string())));
+        assert_eq!(Kind::ser_from(&Kind::Dice.ser_to()), Kind::Dice);
+        let tversky = Kind::Tversky { alpha: 0.9, beta: 0.1 };
+        assert_eq!(serde_json::from_str::<Kind>(&tversky.ser_to()).unwrap(), tversky);
+        assert!(serde_json::from_str::<Kind>(r#"{"Tversky":{"alpha":-1.0,"beta":0.5}}"#).is_err());
+        assert!(serde_json::from_str::<Kind>(r#"{"Tversky":{"alpha":0.0,"beta":0.0}}"#).is_err());
+    }
+
+    #[test]
+    fn test_coefficient() {
+        // 3 common bits, 4 bits in query, 5 bits in entry, 16 bits in total
+        assert_eq!(Kind::Tanimoto.coefficient(3, 4, 5, 16), 0.5);
+        assert_eq!(Kind::Dice.coefficient(3, 4, 5, 16), 6.0 / 9.0);
+        assert_eq!(Kind::Cosine.coefficient(3, 4, 5, 16), 3.0 / 20.0_f32.sqrt());
+        assert_eq!(Kind::Sokal.coefficient(3, 4, 5, 16), 3.0 / 9.0);
+        assert_eq!(Kind::RussellRao.coefficient(3, 4, 5, 16), 3.0 / 16.0);
+        assert_eq!(Kind::Tversky { alpha: 1.0, beta: 1.0 }.coefficient(3, 4, 5, 16), Kind::Tanimoto.coefficient(3, 4, 5, 16));
+        assert_eq!(Kind::Tversky { alpha: 1.0, beta: 0.0 }.coefficient(3, 4, 5, 16), 0.75);
+        assert_eq!(Kind::Tanimoto.coefficient(0, 0, 0, 16), 0.0);
+    }
+}
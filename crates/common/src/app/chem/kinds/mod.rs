@@ -1,3 +1,5 @@
 mod fingerprint;
+mod metric;
 
-pub use fingerprint::Kind as Fingerprint;
\ No newline at end of file
+pub use fingerprint::Kind as Fingerprint;
+pub use metric::Kind as Metric;
\ No newline at end of file
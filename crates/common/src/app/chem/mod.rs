@@ -3,6 +3,8 @@
 
 pub mod kinds;
 pub mod types;
+pub mod fingerprint_index;
 
 pub mod openbabel;
+pub mod rs;
 pub mod recgen;
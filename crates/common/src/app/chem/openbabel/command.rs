@@ -10,8 +10,22 @@ use crate::command::HELP_TEMPLATE;
 const EXAMPLES: &str = "\
 \nRun OpenBabel substructure matching with input molecule 'c1cccc1N=O' on dataset 'test_chembl'
 ob ss --dataset test_chembl --smarts c1ccccc1N=O
+\nRun OpenBabel substructure matching, ruling out molecules by FP2 path fingerprints first
+ob ss --dataset test_chembl --smarts c1ccccc1N=O --prescreen
+\nRun OpenBabel substructure matching, listing the first 100 matching molecules without atom mappings
+ob ss --dataset test_chembl --smarts C(=O)O --has-match --max-hits 100
+\nRun OpenBabel substructure matching, keeping at most 2 unique atom mappings per molecule
+ob ss --dataset test_chembl --smarts C(=O)O --unique --max-mappings 2
+\nRun OpenBabel substructure matching ahead of normal priority jobs
+ob ss --dataset test_chembl --smarts c1ccccc1N=O --priority high
 \nRun fingerprint based similarity search with input molecule 'c1cccc1N=O' on dataset 'test_chembl', using OpenBabel ECFP4 fingerprint, setting minimal tanimoto coefficient to 0.25
 ob sim --dataset test_chembl --smiles c1ccccc1N=O --fingerprint ob_ecfp4_1024 --threshold 0.25
+\nRun fingerprint based similarity search with the asymmetric Tversky metric (alpha 0.9, beta 0.1)
+ob sim --dataset test_chembl --smiles c1ccccc1N=O --fingerprint ob_ecfp4_1024 --metric tversky_0.9_0.1 --threshold 0.5
+\nRun fingerprint based similarity search with the pure Rust ECFP4 fingerprint, without the OpenBabel library
+ob sim --dataset test_chembl --smiles c1ccccc1N=O --fingerprint rs_ecfp4_2048 --threshold 0.25
+\nRun fingerprint based similarity search keeping only the 10 most similar entries
+ob sim --dataset test_chembl --smiles c1ccccc1N=O --fingerprint ob_ecfp4_1024 --top-k 10
 ";
 
 pub fn set() -> clap::Command {
@@ -34,6 +48,39 @@ pub fn set() -> clap::Command {
                         .long("smarts")
                         .required(true),
                 )
+                .arg(
+                    clap::Arg::new("prescreen")
+                        .long("prescreen")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("rule out molecules with FP2 path fingerprints before the SMARTS match"),
+                )
+                .arg(
+                    clap::Arg::new("has_match")
+                        .long("has-match")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("report the matching molecules only, without atom mappings"),
+                )
+                .arg(
+                    clap::Arg::new("unique")
+                        .long("unique")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("keep one atom mapping for each set of matched atoms"),
+                )
+                .arg(
+                    clap::Arg::new("max_mappings")
+                        .long("max-mappings")
+                        .help("maximum number of atom mappings kept per molecule"),
+                )
+                .arg(
+                    clap::Arg::new("max_hits")
+                        .long("max-hits")
+                        .help("stop once this number of matching molecules is found"),
+                )
+                .arg(
+                    clap::Arg::new("priority")
+                        .long("priority")
+                        .help("low, normal (default) or high"),
+                )
         )
         .subcommand(
             clap::Command::new("sim")
@@ -56,15 +103,37 @@ pub fn set() -> clap::Command {
                 .arg(
                     clap::Arg::new("threshold")
                         .long("threshold")
-                        .required(true),
+                        .required_unless_present("top_k"),
+                )
+                .arg(
+                    clap::Arg::new("metric")
+                        .long("metric")
+                        .help("tanimoto (default), dice, cosine, sokal, russell_rao or tversky_(alpha)_(beta)"),
+                )
+                .arg(
+                    clap::Arg::new("top_k")
+                        .long("top-k")
+                        .help("keep only the k most similar entries"),
+                )
+                .arg(
+                    clap::Arg::new("priority")
+                        .long("priority")
+                        .help("low, normal (default) or high"),
                 )
         )
         .help_template(HELP_TEMPLATE)
 }
 
+/// a built-in dataset, or a custom one registered in the dataset registry of DATASET_CUSTOM_PATH
 fn dataset(matches: &clap::ArgMatches) -> Result<crate::kinds::Dataset> {
     let dsk_string = matches.get_one::<String>("dataset").ok_or(crate::command::CommandLineError::ArgumentNotFound("dataset".to_string()))?;
-    crate::kinds::Dataset::from_str(dsk_string).map_err(|e| e.into())
+    if let Ok(dsk) = crate::kinds::Dataset::from_str(dsk_string) {
+        return Ok(dsk);
+    }
+    let env_key = crate::kinds::Dataset::Custom(dsk_string.to_owned()).env_key().to_string();
+    let dir = std::env::var(&env_key).with_context(|| format!("dataset {} is not built-in, set {} to the directory of the dataset registry", dsk_string, env_key))?;
+    let registry = crate::data::DatasetRegistry::open(&std::path::Path::new(&dir).join(crate::data::DatasetRegistry::FILENAME))?;
+    Ok(registry.resolve(dsk_string)?)
 }
 
 fn smiles(matches: &clap::ArgMatches) -> Result<&crate::app::chem::types::SMILES> {
@@ -76,8 +145,35 @@ fn smarts(matches: &clap::ArgMatches) -> Result<&crate::app::chem::types::SMARTS
 }
 
 fn threshold(matches: &clap::ArgMatches) -> Result<f32> {
-    let th_str = matches.get_one::<String>("threshold").ok_or(crate::command::CommandLineError::ArgumentNotFound("threshold".to_string()))?;
-    th_str.parse::<f32>().context("OpenBabel similarity search - get argument threshold error: ")
+    match matches.get_one::<String>("threshold") {
+        Some(th_str) => th_str.parse::<f32>().context("OpenBabel similarity search - get argument threshold error: "),
+        None if matches.contains_id("top_k") => Ok(0.0),
+        None => Err(crate::command::CommandLineError::ArgumentNotFound("threshold".to_string()).into())
+    }
+}
+
+fn top_k(matches: &clap::ArgMatches) -> Result<Option<usize>> {
+    matches.get_one::<String>("top_k")
+        .map(|k_str| k_str.parse::<usize>()
+            .context("OpenBabel similarity search - get argument top-k error: ")
+            .and_then(|k| match k {
+                0 => Err(anyhow::anyhow!("OpenBabel similarity search - argument top-k shall be at least 1")),
+                k => Ok(k)
+            }))
+        .transpose()
+}
+
+fn max_count(matches: &clap::ArgMatches, id: &str) -> Result<Option<usize>> {
+    matches.get_one::<String>(id)
+        .map(|n_str| n_str.parse::<usize>().with_context(|| format!("OpenBabel substructure search - get argument {} error: ", id)))
+        .transpose()
+}
+
+fn priority(matches: &clap::ArgMatches) -> Result<crate::job::Priority> {
+    match matches.get_one::<String>("priority") {
+        Some(priority_string) => crate::job::Priority::from_str(priority_string).map_err(|e| e.into()),
+        None => Ok(crate::job::Priority::default())
+    }
 }
 
 fn fingerprint(matches: &clap::ArgMatches) -> Result<crate::app::chem::kinds::Fingerprint> {
@@ -86,14 +182,25 @@ fn fingerprint(matches: &clap::ArgMatches) -> Result<crate::app::chem::kinds::Fi
         .map_err(|e| e.into())
 }
 
+fn metric(matches: &clap::ArgMatches) -> Result<crate::app::chem::kinds::Metric> {
+    match matches.get_one::<String>("metric") {
+        Some(metric_string) => crate::app::chem::kinds::Metric::from_str(metric_string).map_err(|e| e.into()),
+        None => Ok(crate::app::chem::kinds::Metric::default())
+    }
+}
+
 fn similarity(matches: &clap::ArgMatches) -> Result<Option<crate::job::Job>> {
     let dsk = dataset(matches)?;
     let smiles = smiles(matches)?.to_string();
     let fpk = fingerprint(matches)?;
     let threshold = threshold(matches)?;
+    let top_k = top_k(matches)?;
+    let metric = metric(matches)?;
+    let priority = priority(matches)?;
 
-    let input = crate::app::chem::openbabel::similarity::Input { smiles, threshold };
-    let req = crate::job::Requirement::new(input.ser_to(), crate::kinds::Operator::OpenBabelSimilaritySearching(fpk), dsk);
+    let input = crate::app::chem::openbabel::similarity::Input { smiles, threshold, top_k, metric };
+    let req = crate::job::Requirement::new(input.ser_to(), crate::kinds::Operator::similarity_searching(fpk), dsk)
+        .with_priority(priority);
 
     Ok(Some(crate::job::Job::new(req)))
 }
@@ -101,9 +208,16 @@ fn similarity(matches: &clap::ArgMatches) -> Result<Option<crate::job::Job>> {
 fn substructure(matches: &clap::ArgMatches) -> Result<Option<crate::job::Job>> {
     let dsk = dataset(matches)?;
     let smarts = smarts(matches)?.to_string();
+    let prescreen = matches.get_flag("prescreen");
+    let has_match_only = matches.get_flag("has_match");
+    let unique = matches.get_flag("unique");
+    let max_mappings = max_count(matches, "max_mappings")?;
+    let max_hits = max_count(matches, "max_hits")?;
+    let priority = priority(matches)?;
 
-    let input = crate::app::chem::openbabel::substructure::Input { smarts };
-    let req = crate::job::Requirement::new(input.ser_to(), crate::kinds::Operator::OpenBabelSSMatching, dsk);
+    let input = crate::app::chem::openbabel::substructure::Input { smarts, prescreen, has_match_only, max_mappings, unique, max_hits };
+    let req = crate::job::Requirement::new(input.ser_to(), crate::kinds::Operator::OpenBabelSSMatching, dsk)
+        .with_priority(priority);
 
     Ok(Some(crate::job::Job::new(req)))
 }
@@ -167,5 +281,72 @@ mod tests {
         let cmd_6 = set();
         let res_6 = cmd_6.try_get_matches_from(vec!["ob", "examples"]);
         assert!(res_6.is_ok());
+        let cmd_7 = set();
+        let res_7 = cmd_7.try_get_matches_from(vec!["ob", "sim", "--dataset", "dummy", "--fingerprint", "ob_ecfp4_512", "--smiles", "c1cccccc1", "--top-k", "5"]);
+        assert!(res_7.is_ok());
+        let job_7 = parse(&res_7.unwrap()).unwrap().unwrap();
+        let input_7 = crate::app::chem::openbabel::similarity::Input::ser_from(job_7.get_input());
+        assert_eq!(input_7.top_k, Some(5));
+        assert_eq!(input_7.threshold, 0.0);
+        let cmd_8 = set();
+        let res_8 = cmd_8.try_get_matches_from(vec!["ob", "sim", "--dataset", "dummy", "--fingerprint", "ob_ecfp4_512", "--smiles", "c1cccccc1", "--top-k", "5", "--threshold", "0.2"]);
+        let job_8 = parse(&res_8.unwrap()).unwrap().unwrap();
+        let input_8 = crate::app::chem::openbabel::similarity::Input::ser_from(job_8.get_input());
+        assert_eq!(input_8.top_k, Some(5));
+        assert_eq!(input_8.threshold, 0.2);
+        assert_eq!(input_8.metric, crate::app::chem::kinds::Metric::Tanimoto);
+        let res_8_zero = set().try_get_matches_from(vec!["ob", "sim", "--dataset", "dummy", "--fingerprint", "ob_ecfp4_512", "--smiles", "c1cccccc1", "--top-k", "0"]);
+        assert!(parse(&res_8_zero.unwrap()).is_err());
+        let cmd_9 = set();
+        let res_9 = cmd_9.try_get_matches_from(vec!["ob", "sim", "--dataset", "dummy", "--fingerprint", "ob_ecfp4_512", "--smiles", "c1cccccc1", "--threshold", "0.2", "--metric", "tversky_0.9_0.1"]);
+        let job_9 = parse(&res_9.unwrap()).unwrap().unwrap();
+        let input_9 = crate::app::chem::openbabel::similarity::Input::ser_from(job_9.get_input());
+        assert_eq!(input_9.metric, crate::app::chem::kinds::Metric::Tversky { alpha: 0.9, beta: 0.1 });
+        let cmd_10 = set();
+        let res_10 = cmd_10.try_get_matches_from(vec!["ob", "sim", "--dataset", "dummy", "--fingerprint", "ob_ecfp4_512", "--smiles", "c1cccccc1", "--threshold", "0.2", "--metric", "jaccard"]);
+        assert!(parse(&res_10.unwrap()).is_err());
+        let cmd_11 = set();
+        let res_11 = cmd_11.try_get_matches_from(vec!["ob", "ss", "--dataset", "dummy", "--smarts", "c1ccccc1", "--prescreen"]);
+        let job_11 = parse(&res_11.unwrap()).unwrap().unwrap();
+        let input_11 = crate::app::chem::openbabel::substructure::Input::ser_from(job_11.get_input());
+        assert!(input_11.prescreen);
+        let cmd_12 = set();
+        let res_12 = cmd_12.try_get_matches_from(vec!["ob", "ss", "--dataset", "dummy", "--smarts", "c1ccccc1"]);
+        let job_12 = parse(&res_12.unwrap()).unwrap().unwrap();
+        let input_12 = crate::app::chem::openbabel::substructure::Input::ser_from(job_12.get_input());
+        assert_eq!(input_12, crate::app::chem::openbabel::substructure::Input::new("c1ccccc1".to_string()));
+        let cmd_13 = set();
+        let res_13 = cmd_13.try_get_matches_from(vec!["ob", "ss", "--dataset", "dummy", "--smarts", "C(=O)O", "--has-match", "--unique", "--max-mappings", "2", "--max-hits", "100"]);
+        let job_13 = parse(&res_13.unwrap()).unwrap().unwrap();
+        let input_13 = crate::app::chem::openbabel::substructure::Input::ser_from(job_13.get_input());
+        assert!(input_13.has_match_only);
+        assert!(input_13.unique);
+        assert_eq!(input_13.max_mappings, Some(2));
+        assert_eq!(input_13.max_hits, Some(100));
+        let cmd_14 = set();
+        let res_14 = cmd_14.try_get_matches_from(vec!["ob", "ss", "--dataset", "dummy", "--smarts", "C(=O)O", "--max-hits", "many"]);
+        assert!(parse(&res_14.unwrap()).is_err());
+        let cmd_15 = set();
+        let res_15 = cmd_15.try_get_matches_from(vec!["ob", "ss", "--dataset", "dummy", "--smarts", "c1ccccc1", "--priority", "high"]);
+        let job_15 = parse(&res_15.unwrap()).unwrap().unwrap();
+        assert_eq!(job_15.get_priority(), &crate::job::Priority::High);
+        assert_eq!(job_12.get_priority(), &crate::job::Priority::Normal);
+        let cmd_16 = set();
+        let res_16 = cmd_16.try_get_matches_from(vec!["ob", "sim", "--dataset", "dummy", "--fingerprint", "ob_ecfp4_512", "--smiles", "c1cccccc1", "--threshold", "0.2", "--priority", "urgent"]);
+        assert!(parse(&res_16.unwrap()).is_err());
+        // custom datasets are resolved in the registry, the only test setting DATASET_CUSTOM_PATH
+        let dir = crate::utils::tests::temp_dir();
+        std::fs::write(dir.join("library.csv"), "id,smiles\nm1,CCO\n").unwrap();
+        let mut registry = crate::data::DatasetRegistry::open(&dir.join(crate::data::DatasetRegistry::FILENAME)).unwrap();
+        registry.register(crate::data::CustomDataset::new("my_library", dir.join("library.csv"), crate::data::Format::Csv, 0, 1)).unwrap();
+        std::env::set_var("DATASET_CUSTOM_PATH", &dir);
+        let cmd_17 = set();
+        let res_17 = cmd_17.try_get_matches_from(vec!["ob", "ss", "--dataset", "my_library", "--smarts", "c1ccccc1"]);
+        let job_17 = parse(&res_17.unwrap()).unwrap().unwrap();
+        assert_eq!(job_17.get_dsk(), &crate::kinds::Dataset::Custom("my_library".to_string()));
+        let res_18 = set().try_get_matches_from(vec!["ob", "ss", "--dataset", "my_libary", "--smarts", "c1ccccc1"]);
+        assert!(parse(&res_18.unwrap()).is_err());
+        std::env::remove_var("DATASET_CUSTOM_PATH");
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
\ No newline at end of file
@@ -7,6 +7,7 @@ pub enum Kind {
     FP2 { nbits: u32 },
     FP3 { nbits: u32 },
     FP4 { nbits: u32 },
+    MACCS { nbits: u32 },
     ECFP0 { nbits: u32 },
     ECFP2 { nbits: u32 },
     ECFP4 { nbits: u32 },
@@ -19,4 +20,48 @@ impl std::default::Default for Kind {
     fn default() -> Self {
         Self::ECFP4 { nbits: 2048 }
     }
-}
\ No newline at end of file
+}
+
+impl Kind {
+    /// ID of the fingerprint plugin in OpenBabel
+    pub fn name(&self) -> &str {
+        match self {
+            Self::FP2 { .. } => "FP2",
+            Self::FP3 { .. } => "FP3",
+            Self::FP4 { .. } => "FP4",
+            Self::MACCS { .. } => "MACCS",
+            Self::ECFP0 { .. } => "ECFP0",
+            Self::ECFP2 { .. } => "ECFP2",
+            Self::ECFP4 { .. } => "ECFP4",
+            Self::ECFP6 { .. } => "ECFP6",
+            Self::ECFP8 { .. } => "ECFP8",
+            Self::ECFP10 { .. } => "ECFP10"
+        }
+    }
+
+    pub fn get_nbits(&self) -> u32 {
+        match self {
+            Self::FP2 { nbits } | Self::FP3 { nbits } | Self::FP4 { nbits } | Self::MACCS { nbits } 
+            | Self::ECFP0 { nbits } | Self::ECFP2 { nbits } | Self::ECFP4 { nbits } | Self::ECFP6 { nbits } | Self::ECFP8 { nbits } | Self::ECFP10 { nbits } => *nbits
+        }
+    }
+
+    /// Whether OpenBabel generates fingerprints of exactly nbits bits
+    ///     FP2 is folded from 1024 bits, ECFP is hashed into nbits bits, 
+    ///     pattern based FP3 (55 patterns), FP4 (307 patterns) and MACCS (166 keys) have the fixed size of the smallest power of two holding their patterns
+    pub fn is_valid_nbits(&self) -> bool {
+        let nbits = self.get_nbits();
+        match self {
+            Self::FP2 { .. } => nbits.is_power_of_two() && (32..=1024).contains(&nbits),
+            Self::FP3 { .. } => nbits == 64,
+            Self::FP4 { .. } => nbits == 512,
+            Self::MACCS { .. } => nbits == 256,
+            Self::ECFP0 { .. } | Self::ECFP2 { .. } | Self::ECFP4 { .. } | Self::ECFP6 { .. } | Self::ECFP8 { .. } | Self::ECFP10 { .. } => nbits > 0 && nbits.is_multiple_of(32)
+        }
+    }
+
+    /// Whether the fingerprint is built from SMARTS patterns read from the OpenBabel data directory
+    pub fn is_pattern_based(&self) -> bool {
+        matches!(self, Self::FP3 { .. } | Self::FP4 { .. } | Self::MACCS { .. })
+    }
+}
@@ -8,14 +8,20 @@ use chiral_derive::*;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Serialization)] 
 pub struct Input {
     pub smiles: crate::app::chem::types::SMILES,
-    pub threshold: f32
+    pub threshold: f32,
+    #[serde(default)]
+    pub top_k: Option<usize>, // keep only the k most similar entries
+    #[serde(default)]
+    pub metric: crate::app::chem::kinds::Metric
 }
 
 impl TraitInput for Input {
     fn default() -> Self {
         Self { 
             smiles: crate::app::chem::types::SMILES::from("c1ccccc1N=O"),
-            threshold: 0.1
+            threshold: 0.1,
+            top_k: None,
+            metric: crate::app::chem::kinds::Metric::default()
         }
     }
 }
@@ -24,11 +30,27 @@ impl TraitInput for Input {
 /// Output
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Serialization)]
 pub struct Output {
-    pub results: Vec<(f32, crate::data::types::EntryID)>
+    pub results: Vec<(f32, crate::data::types::EntryID)>,
+    #[serde(default)]
+    pub top_k: Option<usize>
+}
+
+impl Output {
+    /// descending coefficient, ties broken by entry id
+    fn order((c1, id1): &(f32, crate::data::types::EntryID), (c2, id2): &(f32, crate::data::types::EntryID)) -> std::cmp::Ordering {
+        c2.total_cmp(c1).then_with(|| id1.cmp(id2))
+    }
+
+    /// sort results unless they are already in order
+    fn sort(&mut self) {
+        if !self.results.windows(2).all(|w| Self::order(&w[0], &w[1]).is_le()) {
+            self.results.sort_by(Self::order);
+        }
+    }
 }
 
 impl TraitOutput for Output {
-    fn blank() -> Self { Self { results: vec![] } }
+    fn blank() -> Self { Self { results: vec![], top_k: None } }
 
     fn len(&self) -> usize { self.results.len() }
 
@@ -37,7 +59,25 @@ impl TraitOutput for Output {
     }
 
     fn append(&mut self, other: &mut Self) {
-        self.results.append(&mut other.results)
+        self.top_k = self.top_k.or(other.top_k);
+        self.sort();
+        other.sort();
+        let limit = self.top_k.unwrap_or(usize::MAX);
+        let mut merged = Vec::with_capacity((self.results.len() + other.results.len()).min(limit));
+        let mut lhs = std::mem::take(&mut self.results).into_iter().peekable();
+        let mut rhs = std::mem::take(&mut other.results).into_iter().peekable();
+        while merged.len() < limit {
+            let next = match (lhs.peek(), rhs.peek()) {
+                (Some(l), Some(r)) => if Self::order(l, r).is_le() { lhs.next() } else { rhs.next() },
+                (Some(_), None) => lhs.next(),
+                (None, _) => rhs.next()
+            };
+            match next {
+                Some(hit) => merged.push(hit),
+                None => break
+            }
+        }
+        self.results = merged;
     }
 }
 
@@ -48,10 +88,17 @@ pub struct Report {}
 
 impl crate::traits::TraitReport for Report {
     fn print(&self) {
-        println!("Report of OpenBabel Similarity Search\n");
+        match self.cuk.get_opk() {
+            crate::kinds::Operator::RustSimilaritySearching(_) => println!("Report of Rust Similarity Search\n"),
+            _ => println!("Report of OpenBabel Similarity Search\n")
+        }
         println!(" Input");
         println!("\t smiles: {}", self.input.smiles);
+        println!("\t metric: {}", self.input.metric);
         println!("\t threshold: {:.2}", self.input.threshold);
+        if let Some(k) = self.input.top_k {
+            println!("\t top k: {}", k);
+        }
         println!(" Operator");
         println!("\t fingerprint kind: {}", self.cuk.get_opk().to_string());
         println!(" Dataset");
@@ -62,4 +109,26 @@ impl crate::traits::TraitReport for Report {
         }
         println!("\t Count: {}", self.output.len());
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_append_top_k() {
+        let mut output = Output::blank();
+        let mut output_1 = Output { results: vec![(0.9, "id_1".to_string()), (0.3, "id_2".to_string())], top_k: Some(3) };
+        let mut output_2 = Output { results: vec![(0.5, "id_3".to_string()), (0.7, "id_4".to_string())], top_k: Some(3) };
+        output.append(&mut output_1);
+        output.append(&mut output_2);
+        assert_eq!(output.top_k, Some(3));
+        assert_eq!(output.results, vec![(0.9, "id_1".to_string()), (0.7, "id_4".to_string()), (0.5, "id_3".to_string())]);
+
+        let mut output_all = Output::blank();
+        output_all.append(&mut Output { results: vec![(0.2, "id_1".to_string()), (0.8, "id_2".to_string())], top_k: None });
+        assert_eq!(output_all.results, vec![(0.8, "id_2".to_string()), (0.2, "id_1".to_string())]);
+        output_all.append(&mut Output { results: vec![(0.8, "id_0".to_string()), (0.5, "id_3".to_string())], top_k: None });
+        assert_eq!(output_all.results, vec![(0.8, "id_0".to_string()), (0.8, "id_2".to_string()), (0.5, "id_3".to_string()), (0.2, "id_1".to_string())]);
+    }
+}
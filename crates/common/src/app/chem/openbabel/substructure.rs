@@ -8,12 +8,57 @@ use chiral_derive::*;
 /// Input
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Serialization)] 
 pub struct  Input {
-    pub smarts: crate::app::chem::types::SMARTS
+    pub smarts: crate::app::chem::types::SMARTS,
+    /// rule out molecules with FP2 path fingerprints before the SMARTS match
+    #[serde(default)]
+    pub prescreen: bool,
+    /// keep the matching molecules only, without atom mappings
+    #[serde(default)]
+    pub has_match_only: bool,
+    /// maximum number of atom mappings kept per molecule
+    #[serde(default)]
+    pub max_mappings: Option<usize>,
+    /// keep one atom mapping for each set of matched atoms
+    #[serde(default)]
+    pub unique: bool,
+    /// stop once this number of matching molecules is found
+    #[serde(default)]
+    pub max_hits: Option<usize>
+}
+
+impl Input {
+    pub fn new(smarts: crate::app::chem::types::SMARTS) -> Self {
+        Self { smarts, prescreen: false, has_match_only: false, max_mappings: None, unique: false, max_hits: None }
+    }
+
+    /// Atom mappings of a matching molecule kept in the output
+    pub fn limit_mappings(&self, mr: MatchResult) -> MatchResult {
+        if self.has_match_only {
+            return vec![];
+        }
+
+        let mut mappings = if self.unique {
+            let mut atom_sets: std::collections::HashSet<Vec<i32>> = std::collections::HashSet::new();
+            mr.into_iter()
+                .filter(|mapping| {
+                    let mut atoms = mapping.to_owned();
+                    atoms.sort_unstable();
+                    atom_sets.insert(atoms)
+                })
+                .collect()
+        } else {
+            mr
+        };
+        if let Some(max) = self.max_mappings {
+            mappings.truncate(max);
+        }
+        mappings
+    }
 }
 
 impl TraitInput for Input {
     fn default() -> Self {
-        Self { smarts: crate::app::chem::types::SMILES::from("c1ccccc1N=O") }
+        Self::new(crate::app::chem::types::SMILES::from("c1ccccc1N=O"))
     }
 }
 
@@ -22,20 +67,39 @@ pub type MatchResult = Vec<Vec<i32>>;
 
 #[derive(PartialEq, Debug, Serialize, Deserialize, Serialization, Clone)]
 pub struct Output {
-    pub results: Vec<(MatchResult, crate::data::types::EntryID)>
+    pub results: Vec<(MatchResult, crate::data::types::EntryID)>,
+    /// molecules checked by the prescreen, and those ruled out without the SMARTS match
+    #[serde(default)]
+    pub screened: usize,
+    #[serde(default)]
+    pub screened_out: usize,
+    #[serde(default)]
+    pub max_hits: Option<usize>
 }
 
 impl crate::traits::TraitOutput for Output {
-    fn blank() -> Self { Self { results: vec![] } }
+    fn blank() -> Self { Self { results: vec![], screened: 0, screened_out: 0, max_hits: None } }
      
     fn len(&self) -> usize { self.results.len() }
 
     fn clear(&mut self) {
         self.results.clear();
+        self.screened = 0;
+        self.screened_out = 0;
     }
 
     fn append(&mut self, other: &mut Self) {
+        self.max_hits = self.max_hits.or(other.max_hits);
         self.results.append(&mut other.results);
+        if let Some(max) = self.max_hits {
+            self.results.truncate(max);
+        }
+        self.screened += other.screened;
+        self.screened_out += other.screened_out;
+    }
+
+    fn is_complete(&self) -> bool {
+        self.max_hits.is_some_and(|max| self.results.len() >= max)
     }
 }
 
@@ -49,14 +113,26 @@ impl crate::traits::TraitReport for Report {
         println!(" Report of OpenBabel Substructure Search\n");
         println!(" Input");
         println!("\t smarts: {}", self.input.smarts);
+        println!("\t prescreen: {}", self.input.prescreen);
+        println!("\t has match only: {}", self.input.has_match_only);
+        println!("\t unique: {}", self.input.unique);
+        println!("\t max mappings: {}", self.input.max_mappings.map_or("all".to_string(), |n| n.to_string()));
+        println!("\t max hits: {}", self.input.max_hits.map_or("all".to_string(), |n| n.to_string()));
         println!(" Operator");
         println!(" Dataset");
         println!("\t kind: {}", self.cuk.get_dsk());
         println!(" Output");
         for (matches, id) in self.output.results.iter() {
-            println!("\t {id}\t {matches:?}");
+            if self.input.has_match_only {
+                println!("\t {id}");
+            } else {
+                println!("\t {id}\t {matches:?}");
+            }
         }
         println!("\t Count: {}", self.output.results.len());
+        if self.input.prescreen {
+            println!("\t Screened out: {} of {}", self.output.screened_out, self.output.screened);
+        }
         // let mut ids: Vec<crate::data::types::EntryID> = vec![];
         // for r in self.output.results.iter() {
         //     if !ids.contains(&r.1) {
@@ -67,3 +143,45 @@ impl crate::traits::TraitReport for Report {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_mappings() {
+        let mr: MatchResult = vec![vec![1, 2, 3], vec![3, 2, 1], vec![4, 2, 1], vec![1, 2, 4]];
+        let mut input = Input::new("C(=O)O".to_string());
+        assert_eq!(input.limit_mappings(mr.to_owned()), mr);
+        input.unique = true;
+        assert_eq!(input.limit_mappings(mr.to_owned()), vec![vec![1, 2, 3], vec![4, 2, 1]]);
+        input.max_mappings = Some(1);
+        assert_eq!(input.limit_mappings(mr.to_owned()), vec![vec![1, 2, 3]]);
+        input.unique = false;
+        input.max_mappings = Some(3);
+        assert_eq!(input.limit_mappings(mr.to_owned()).len(), 3);
+        input.has_match_only = true;
+        assert!(input.limit_mappings(mr).is_empty());
+        // inputs serialized before the options were added
+        let input_old = Input::ser_from(&r#"{"smarts":"C(=O)O"}"#.to_string());
+        assert_eq!(input_old, Input::new("C(=O)O".to_string()));
+    }
+
+    #[test]
+    fn test_output_max_hits() {
+        let output_with = |ids: &[&str], max_hits: Option<usize>| Output {
+            results: ids.iter().map(|id| (vec![], id.to_string())).collect(),
+            screened: 0, screened_out: 0, max_hits
+        };
+        let mut merged = Output::blank();
+        assert!(!merged.is_complete());
+        merged.append(&mut output_with(&["id_1", "id_2"], Some(3)));
+        assert!(!merged.is_complete());
+        merged.append(&mut output_with(&["id_3", "id_4"], Some(3)));
+        assert!(merged.is_complete());
+        assert_eq!(merged.results.iter().map(|(_, id)| id.as_str()).collect::<Vec<&str>>(), vec!["id_1", "id_2", "id_3"]);
+        let mut unlimited = output_with(&["id_1", "id_2"], None);
+        unlimited.append(&mut output_with(&["id_3"], None));
+        assert_eq!(unlimited.len(), 3);
+        assert!(!unlimited.is_complete());
+    }
+}
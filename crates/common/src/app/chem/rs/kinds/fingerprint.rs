@@ -0,0 +1,52 @@
+//! Fingerprint Kind for the pure Rust package
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]  
+pub enum Kind {
+    /// circular fingerprint, diameter in bonds: ecfp4 covers the neighbourhood of radius 2
+    ECFP { diameter: u32, nbits: u32 },
+    /// linear paths of up to length bonds
+    Path { length: u32, nbits: u32 }
+}
+
+impl std::default::Default for Kind {
+    fn default() -> Self {
+        Self::ECFP { diameter: 4, nbits: 2048 }
+    }
+}
+
+impl Kind {
+    /// Parse the fingerprint part of the string format, eg. ecfp4 or path7
+    pub fn new(fp_str: &str, nbits: u32) -> Option<Self> {
+        let (name, size) = fp_str.split_at(fp_str.find(|c: char| c.is_ascii_digit())?);
+        let size: u32 = size.parse().ok()?;
+        match name {
+            "ecfp" => Some(Self::ECFP { diameter: size, nbits }),
+            "path" => Some(Self::Path { length: size, nbits }),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::ECFP { diameter, .. } => format!("ecfp{}", diameter),
+            Self::Path { length, .. } => format!("path{}", length)
+        }
+    }
+
+    pub fn get_nbits(&self) -> u32 {
+        match self {
+            Self::ECFP { nbits, .. } | Self::Path { nbits, .. } => *nbits
+        }
+    }
+
+    /// Diameters 0 to 10 in steps of 2, path lengths 1 to 10, nbits a positive multiple of 32
+    pub fn is_valid(&self) -> bool {
+        let nbits_valid = self.get_nbits() > 0 && self.get_nbits().is_multiple_of(32);
+        match self {
+            Self::ECFP { diameter, .. } => nbits_valid && *diameter <= 10 && diameter.is_multiple_of(2),
+            Self::Path { length, .. } => nbits_valid && (1..=10).contains(length)
+        }
+    }
+}
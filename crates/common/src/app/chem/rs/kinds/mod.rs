@@ -0,0 +1,3 @@
+mod fingerprint;
+
+pub use fingerprint::Kind as Fingerprint;
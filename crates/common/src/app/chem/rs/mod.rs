@@ -0,0 +1,5 @@
+//! Application - Pure Rust cheminformatics
+//!     fingerprints built from a Rust molecular graph, for worker nodes without the OpenBabel C++ library
+//!
+
+pub mod kinds;
@@ -8,6 +8,8 @@ pub enum Kind {
     OpenBabelSimilaritySearching(crate::app::chem::kinds::Fingerprint),
     #[strum(serialize = "ob_ss")]
     OpenBabelSSMatching,
+    #[strum(serialize = "rs_sim")]
+    RustSimilaritySearching(crate::app::chem::kinds::Fingerprint),
     #[strum(serialize = "recgen_build")]
     ReCGenBuild
 }
@@ -18,6 +20,14 @@ impl Kind {
         Kind::OpenBabelSimilaritySearching(fpk)
     }
 
+    /// Similarity searching with the package of the fingerprint kind
+    pub fn similarity_searching(fpk: crate::app::chem::kinds::Fingerprint) -> Self {
+        match fpk {
+            crate::app::chem::kinds::Fingerprint::OpenBabel { .. } => Kind::OpenBabelSimilaritySearching(fpk),
+            crate::app::chem::kinds::Fingerprint::Rust { .. } => Kind::RustSimilaritySearching(fpk)
+        }
+    }
+
     pub fn is_openbabel(&self) -> bool {
         match self {
             Self::OpenBabelSSMatching | Self::OpenBabelSimilaritySearching(_) => true, 
@@ -26,20 +36,44 @@ impl Kind {
 
     }
 
+    pub fn cost_class(&self) -> crate::planner::CostClass {
+        match self {
+            Kind::OpenBabelSimilaritySearching(_) | Kind::RustSimilaritySearching(_) => crate::planner::CostClass::Medium,
+            Kind::OpenBabelSSMatching | Kind::ReCGenBuild => crate::planner::CostClass::Heavy
+        }
+    }
+
+    /// check that the serialized input can be read by the operator
+    pub fn validate_input(&self, content: &crate::traits::SerializedFormat) -> Result<(), serde_json::Error> {
+        match self {
+            Kind::OpenBabelSimilaritySearching(_) | Kind::RustSimilaritySearching(_) => serde_json::from_str::<super::chem::openbabel::similarity::Input>(content).map(|_| ()),
+            Kind::OpenBabelSSMatching => serde_json::from_str::<super::chem::openbabel::substructure::Input>(content).map(|_| ()),
+            Kind::ReCGenBuild => serde_json::from_str::<super::chem::recgen::build::Input>(content).map(|_| ())
+        }
+    }
+
+    /// number of results after which the job stops, the max_hits of a substructure search
+    pub fn max_hits(&self, content: &crate::traits::SerializedFormat) -> Option<usize> {
+        match self {
+            Kind::OpenBabelSSMatching => serde_json::from_str::<super::chem::openbabel::substructure::Input>(content).ok().and_then(|input| input.max_hits),
+            _ => None
+        }
+    }
+
     pub fn report_print(&self, content: &crate::traits::SerializedFormat) {
         match self {
-            crate::kinds::Operator::OpenBabelSimilaritySearching(_) => super::chem::openbabel::similarity::Report::ser_from(content).print(),
-            crate::kinds::Operator::OpenBabelSSMatching => super::chem::openbabel::substructure::Report::ser_from(content).print(),
-            crate::kinds::Operator::ReCGenBuild => super::chem::recgen::build::Report::ser_from(content).print()
+            crate::kinds::Operator::OpenBabelSimilaritySearching(_) | crate::kinds::Operator::RustSimilaritySearching(_) => crate::job::JobReport::<super::chem::openbabel::similarity::Report>::ser_from(content).print(),
+            crate::kinds::Operator::OpenBabelSSMatching => crate::job::JobReport::<super::chem::openbabel::substructure::Report>::ser_from(content).print(),
+            crate::kinds::Operator::ReCGenBuild => crate::job::JobReport::<super::chem::recgen::build::Report>::ser_from(content).print()
         }
     }
 
-    pub fn report_save(&self, job_id: crate::job::ID, dsk: crate::kinds::Dataset, input_ser: &crate::traits::SerializedFormat, output_sers: &Vec<crate::traits::SerializedFormat>, filepath: &std::path::PathBuf) -> std::io::Result<u64> {
+    pub fn report_save(&self, job_id: crate::job::ID, dsk: crate::kinds::Dataset, input_ser: &crate::traits::SerializedFormat, output_sers: &Vec<crate::traits::SerializedFormat>, skipped: &[crate::job::JobError], filepath: &std::path::PathBuf) -> std::io::Result<u64> {
         let cuk = crate::kinds::ComputingUnit::new(self.to_owned(), dsk); 
         match self {
-            Kind::OpenBabelSimilaritySearching(_) => super::chem::openbabel::similarity::Report::new((job_id, cuk, input_ser, output_sers)).save(filepath),
-            Kind::OpenBabelSSMatching => super::chem::openbabel::substructure::Report::new((job_id, cuk, input_ser, output_sers)).save(filepath),
-            Kind::ReCGenBuild => super::chem::recgen::build::Report::new((job_id, cuk, input_ser, output_sers)).save(filepath)
+            Kind::OpenBabelSimilaritySearching(_) | Kind::RustSimilaritySearching(_) => crate::job::JobReport::new(super::chem::openbabel::similarity::Report::new((job_id, cuk, input_ser, output_sers)), skipped).save(filepath),
+            Kind::OpenBabelSSMatching => crate::job::JobReport::new(super::chem::openbabel::substructure::Report::new((job_id, cuk, input_ser, output_sers)), skipped).save(filepath),
+            Kind::ReCGenBuild => crate::job::JobReport::new(super::chem::recgen::build::Report::new((job_id, cuk, input_ser, output_sers)), skipped).save(filepath)
         }
     }
 }
@@ -56,5 +90,20 @@ mod tests {
         assert_eq!(Kind::OpenBabelSimilaritySearching(crate::app::chem::kinds::Fingerprint::kind_openbabel_ecfp4(1024)).to_string(), "ob_sim");
         assert_eq!(Kind::from_str("ob_ss").unwrap(), Kind::OpenBabelSSMatching);
         assert_eq!(Kind::OpenBabelSSMatching.to_string(), "ob_ss");
+        assert_eq!(Kind::RustSimilaritySearching(crate::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 1024)).to_string(), "rs_sim");
+        assert_eq!(Kind::similarity_searching(crate::app::chem::kinds::Fingerprint::kind_rust_path(7, 1024)), Kind::RustSimilaritySearching(crate::app::chem::kinds::Fingerprint::kind_rust_path(7, 1024)));
+        assert!(!Kind::similarity_searching(crate::app::chem::kinds::Fingerprint::kind_rust_path(7, 1024)).is_openbabel());
+    }
+
+    #[test]
+    fn test_validate_input() {
+        let input = crate::app::chem::openbabel::substructure::Input::new("CC".to_string()).ser_to();
+        assert!(Kind::OpenBabelSSMatching.validate_input(&input).is_ok());
+        assert!(Kind::default_ob_similarity_searching().validate_input(&input).is_err());
+        assert!(Kind::OpenBabelSSMatching.validate_input(&"c1ccccc1".to_string()).is_err());
+        assert_eq!(Kind::OpenBabelSSMatching.max_hits(&input), None);
+        let input_max_hits = crate::app::chem::openbabel::substructure::Input { max_hits: Some(3), ..crate::app::chem::openbabel::substructure::Input::new("CC".to_string()) }.ser_to();
+        assert_eq!(Kind::OpenBabelSSMatching.max_hits(&input_max_hits), Some(3));
+        assert_eq!(Kind::default_ob_similarity_searching().max_hits(&input_max_hits), None);
     }
 }
\ No newline at end of file
@@ -75,9 +75,54 @@ impl From<crate::data::SourceChembl> for DocSMILES {
     }
 }
 
+impl From<crate::data::SourcePubChem> for DocSMILES {
+    fn from(spc: crate::data::SourcePubChem) -> Self {
+        let (ids, smiles) = spc.get_cid_smiles_pairs();
+        DocSMILES::new(ids, smiles)
+    }
+}
+
+impl From<crate::data::SourceSDF> for DocSMILES {
+    fn from(ss: crate::data::SourceSDF) -> Self {
+        let (ids, smiles) = ss.get_id_smiles_pairs();
+        DocSMILES::new(ids, smiles)
+    }
+}
+
+impl From<crate::data::SourceDelimited> for DocSMILES {
+    fn from(sd: crate::data::SourceDelimited) -> Self {
+        let (ids, smiles) = sd.get_id_smiles_pairs();
+        DocSMILES::new(ids, smiles)
+    }
+}
+
+/// DocSMILES of streamed batches, sorted once all are received
+impl FromIterator<crate::app::chem::types::IdSmilesPairs> for DocSMILES {
+    fn from_iter<I: IntoIterator<Item = crate::app::chem::types::IdSmilesPairs>>(batches: I) -> Self {
+        let (mut ids, mut smiles) = (vec![], vec![]);
+        for (mut batch_ids, mut batch_smiles) in batches {
+            ids.append(&mut batch_ids);
+            smiles.append(&mut batch_smiles);
+        }
+        DocSMILES::new(ids, smiles)
+    }
+}
+
 /// Datastore for DocSMILES
 pub type DocStoreSMILES = std::collections::HashMap<crate::kinds::Dataset, DocSMILES>;
 
+impl crate::traits::TraitDataStore for DocStoreSMILES {
+    fn get_id_smiles_pairs(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex) -> Option<crate::app::chem::types::IdSmilesPairs> {
+        let doc = self.get(dsk)?;
+        let range = div_index.0.min(doc.len())..div_index.1.min(doc.len());
+        Some((doc.extract_ids(&range), doc.extract_smiles_vec(&range)))
+    }
+
+    fn get_size(&self, dsk: &crate::kinds::Dataset) -> Option<usize> {
+        self.get(dsk).map(DocSMILES::len)
+    }
+}
+
 impl crate::data::Info for DocStoreSMILES {
     fn info(&self) -> String {
         format!("{:15} {:>15}\n", "name", "entries")
@@ -132,6 +177,18 @@ mod tests {
         assert!(store.info().contains("100"));
     }
 
+    #[test]
+    fn test_store_dividend() {
+        use crate::traits::TraitDataStore;
+        let mut store = DocStoreSMILES::new();
+        store.insert(crate::kinds::Dataset::Dummy, DocSMILES::dummy());
+        let (ids, smiles) = store.get_id_smiles_pairs(&crate::kinds::Dataset::Dummy, &(1, 3)).unwrap();
+        assert_eq!(ids, vec!["label_2".to_string(), "label_3".to_string()]);
+        assert_eq!(smiles.len(), 2);
+        assert_eq!(store.get_id_smiles_pairs(&crate::kinds::Dataset::Dummy, &(2, 10)).unwrap().0.len(), 2);
+        assert!(store.get_id_smiles_pairs(&crate::kinds::Dataset::TestChembl, &(0, 1)).is_none());
+    }
+
     #[test]
     fn test_permutation() {
         let dsk = crate::kinds::Dataset::TestChembl;
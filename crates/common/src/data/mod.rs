@@ -1,9 +1,18 @@
 mod doc;
+mod registry;
 mod source;
+mod store;
 
 pub use doc::smiles::DocSMILES;
 pub use doc::smiles::DocStoreSMILES;
 pub use source::chembl::SourceChembl;
+pub use source::pubchem::SourcePubChem;
+pub use source::delimited::{SourceDelimited, EntryDelimited, EntriesDelimited, Column, DelimitedError, Format};
+pub use source::sdf::{SourceSDF, IdSource};
+pub use source::molfile::{Molfile, MolfileError};
+pub use source::stream::{Batches, LineIndex, BATCH_SIZE, INDEX_STRIDE, is_seekable};
+pub use registry::{DatasetRegistry, CustomDataset, RegistryError};
+pub use store::DataStore;
 
 pub mod types {
     pub type EntryID = String;
@@ -21,18 +30,53 @@ pub trait Info {
     fn info(&self) -> String;
 }
 
-pub fn load_from_path<T: Empty + Dummy + From<SourceChembl>>(kind: &crate::kinds::Dataset, data_dir: &std::path::PathBuf) -> T {
-    match kind {
-        crate::kinds::Dataset::Empty => T::empty(), 
-        crate::kinds::Dataset::Dummy => T::dummy(),
-        crate::kinds::Dataset::TestChembl | crate::kinds::Dataset::Chembl30 => {
-            let mut sc = SourceChembl::new();
-            sc.set_path(data_dir.join(kind.filename()).as_os_str());
-            sc.load_all();
-            T::from(sc)
+/// collect the batches of a source, empty if the file cannot be read to its end
+fn collect_batches<T, I>(batches: std::io::Result<I>, path: &std::path::Path) -> T
+where
+    T: Empty + FromIterator<crate::app::chem::types::IdSmilesPairs>,
+    I: Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>>
+{
+    match batches.and_then(|batches| batches.collect()) {
+        Ok(data) => data,
+        Err(e) => {
+            crate::logging::error(format!("Error {} on file path: {:?}", e, path).as_str());
+            T::empty()
+        }
+    }
+}
+
+pub fn load_from_path<T: Empty + Dummy + From<SourcePubChem> + FromIterator<crate::app::chem::types::IdSmilesPairs>>(kind: &crate::kinds::Dataset, data_dir: &std::path::PathBuf) -> T {
+    match (kind, kind.filename()) {
+        (crate::kinds::Dataset::Empty, _) => T::empty(), 
+        (crate::kinds::Dataset::Dummy, _) => T::dummy(),
+        (crate::kinds::Dataset::TestChembl | crate::kinds::Dataset::Chembl30, Some(filename)) => {
+            let mut sd = SourceDelimited::chembl();
+            sd.set_path(data_dir.join(filename).as_os_str());
+            collect_batches(sd.batches(BATCH_SIZE), sd.get_path())
+        },
+        (crate::kinds::Dataset::PubChem, Some(filename)) => {
+            let mut spc = SourcePubChem::new();
+            spc.set_path(data_dir.join(filename).as_os_str());
+            spc.load_all();
+            T::from(spc)
+        },
+        (crate::kinds::Dataset::Custom(name), _) => {
+            // the dataset registry is in the data directory
+            match DatasetRegistry::open(&data_dir.join(DatasetRegistry::FILENAME)).and_then(|registry| registry.get(name).cloned()) {
+                Ok(dataset) if dataset.format == Format::Sdf => collect_batches(SourceSDF::from(&dataset).batches(BATCH_SIZE), &dataset.path),
+                Ok(dataset) => {
+                    let sd = SourceDelimited::from(&dataset);
+                    collect_batches(sd.batches(BATCH_SIZE), sd.get_path())
+                },
+                Err(e) => {
+                    crate::logging::error(format!("Error {} on dataset: {}", e, name).as_str());
+                    T::empty()
+                }
+            }
         },
-        crate::kinds::Dataset::PubChem => {
-            unimplemented!()
+        (_, None) => {
+            crate::logging::error(format!("No file of dataset {}", kind).as_str());
+            T::empty()
         }
     }
 }
@@ -48,5 +92,30 @@ mod tests {
         assert_eq!(doc_1.get_ids().len(), 10000);
         assert_eq!(doc_1.get_smiles_vec().len(), 10000);
     }
+
+    #[test]
+    fn test_load_pubchem() {
+        let data_dir = crate::utils::tests::temp_dir();
+        source::pubchem::tests::write_sample(&data_dir);
+        let doc = load_from_path::<doc::smiles::DocSMILES>(&crate::kinds::Dataset::PubChem, &data_dir);
+        assert_eq!(doc.len(), 4);
+        assert_eq!(doc.get_smiles(&"4".to_string()).unwrap(), "CC(=O)Nc1ccc(O)cc1");
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_custom() {
+        let data_dir = crate::utils::tests::temp_dir();
+        std::fs::write(data_dir.join("library.csv"), "id,smiles\nm2,CCN\nm1,CCO\n").unwrap();
+        let dsk = crate::kinds::Dataset::Custom("my_library".to_string());
+        let mut registry = DatasetRegistry::open(&data_dir.join(DatasetRegistry::FILENAME)).unwrap();
+        registry.register(CustomDataset::new("my_library", data_dir.join("library.csv"), Format::Csv, 0, 1).with_header(true)).unwrap();
+        let doc = load_from_path::<doc::smiles::DocSMILES>(&dsk, &data_dir);
+        assert_eq!(doc.len(), 2);
+        assert_eq!(doc.get_smiles(&"m1".to_string()).unwrap(), "CCO");
+        let doc = load_from_path::<doc::smiles::DocSMILES>(&crate::kinds::Dataset::Custom("unknown".to_string()), &data_dir);
+        assert_eq!(doc.len(), 0);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
 }
 
@@ -0,0 +1,206 @@
+//! Dataset Registry
+//!     custom datasets registered by name
+//!
+//! Each custom dataset carries its own file, format and columns. The registry is persisted as a JSON file,
+//! datasets.json in the directory of DATASET_CUSTOM_PATH by default, so the CLI, the server and the workers
+//! resolve the same names.
+//!
+
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("dataset registry io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("dataset registry corrupted: {0}")]
+    Corrupted(#[from] serde_json::Error),
+    #[error("invalid dataset name '{0}': letters, digits, '_' or '-' only, and not a built-in dataset")]
+    InvalidName(String),
+    #[error("dataset '{0}' already registered")]
+    AlreadyRegistered(String),
+    #[error("dataset '{0}' not registered")]
+    NotRegistered(String)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomDataset {
+    pub name: String,
+    pub path: std::path::PathBuf,
+    pub format: crate::data::Format,
+    /// column of the entry ID, by name or zero based index
+    pub id_column: crate::data::Column,
+    /// column of the SMILES, by name or zero based index
+    pub smiles_column: crate::data::Column,
+    /// whether the first line holds the column names, detected if None
+    #[serde(default)]
+    pub header: Option<bool>,
+    /// SD files: where the entry ID of a record comes from
+    #[serde(default)]
+    pub id_source: crate::data::IdSource,
+    /// SD files: data field of the SMILES, written from the connection table if None
+    #[serde(default)]
+    pub smiles_field: Option<String>
+}
+
+impl CustomDataset {
+    pub fn new(name: &str, path: std::path::PathBuf, format: crate::data::Format, id_column: impl Into<crate::data::Column>, smiles_column: impl Into<crate::data::Column>) -> Self {
+        Self { name: name.to_string(), path, format, id_column: id_column.into(), smiles_column: smiles_column.into(), header: None, id_source: crate::data::IdSource::default(), smiles_field: None }
+    }
+
+    /// SD file, the columns are not used
+    pub fn new_sdf(name: &str, path: std::path::PathBuf) -> Self {
+        Self::new(name, path, crate::data::Format::Sdf, 0, 0)
+    }
+
+    pub fn with_id_source(mut self, id_source: crate::data::IdSource) -> Self {
+        self.id_source = id_source;
+        self
+    }
+
+    /// take the SMILES of an SD file from a data field instead of the connection table
+    pub fn with_smiles_field(mut self, name: &str) -> Self {
+        self.smiles_field = Some(name.to_string());
+        self
+    }
+
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn dsk(&self) -> crate::kinds::Dataset {
+        crate::kinds::Dataset::Custom(self.name.to_owned())
+    }
+}
+
+/// whether the name is not a built-in dataset and fits in filenames and comma separated lists
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && <crate::kinds::Dataset as std::str::FromStr>::from_str(name).is_err()
+}
+
+#[derive(Debug, Default)]
+pub struct DatasetRegistry {
+    path: std::path::PathBuf,
+    datasets: std::collections::BTreeMap<String, CustomDataset>
+}
+
+impl DatasetRegistry {
+    /// registry file in the data directory of the custom datasets
+    pub const FILENAME: &'static str = "datasets.json";
+
+    /// read the registry file, empty if the file does not exist yet
+    pub fn open(path: &std::path::Path) -> Result<Self, RegistryError> {
+        let datasets = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str::<Vec<CustomDataset>>(&content)?
+                .into_iter()
+                .map(|dataset| (dataset.name.to_owned(), dataset))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => std::collections::BTreeMap::new(),
+            Err(e) => return Err(e.into())
+        };
+        Ok(Self { path: path.to_path_buf(), datasets })
+    }
+
+    /// write the registry file, replacing the previous one at once
+    fn save(&self) -> Result<(), RegistryError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string_pretty(&self.datasets.values().collect::<Vec<&CustomDataset>>())?;
+        let path_tmp = self.path.with_extension(format!("json.{}", crate::utils::generate_id(8)));
+        std::fs::write(&path_tmp, content)?;
+        std::fs::rename(&path_tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// add the dataset with the absolute path of its file and persist the registry
+    pub fn register(&mut self, mut dataset: CustomDataset) -> Result<(), RegistryError> {
+        if !is_valid_name(&dataset.name) {
+            return Err(RegistryError::InvalidName(dataset.name));
+        }
+        if self.datasets.contains_key(&dataset.name) {
+            return Err(RegistryError::AlreadyRegistered(dataset.name));
+        }
+        dataset.path = std::fs::canonicalize(&dataset.path)?;
+        self.datasets.insert(dataset.name.to_owned(), dataset);
+        self.save()
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Result<CustomDataset, RegistryError> {
+        let dataset = self.datasets.remove(name).ok_or_else(|| RegistryError::NotRegistered(name.to_string()))?;
+        self.save()?;
+        Ok(dataset)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&CustomDataset, RegistryError> {
+        self.datasets.get(name).ok_or_else(|| RegistryError::NotRegistered(name.to_string()))
+    }
+
+    /// the built-in dataset of the name, or the registered custom dataset
+    pub fn resolve(&self, name: &str) -> Result<crate::kinds::Dataset, RegistryError> {
+        match std::str::FromStr::from_str(name) {
+            Ok(dsk) => Ok(dsk),
+            Err(_) => self.get(name).map(CustomDataset::dsk)
+        }
+    }
+
+    /// registered datasets sorted by name
+    pub fn datasets(&self) -> impl Iterator<Item = &CustomDataset> {
+        self.datasets.values()
+    }
+
+    pub fn len(&self) -> usize { self.datasets.len() }
+    pub fn is_empty(&self) -> bool { self.datasets.is_empty() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Format;
+
+    #[test]
+    fn test_registry() {
+        let dir = crate::utils::tests::temp_dir();
+        let data_path = dir.join("library.csv");
+        std::fs::write(&data_path, "id,smiles\nm1,CCO\n").unwrap();
+        let registry_path = dir.join("registry").join(DatasetRegistry::FILENAME);
+
+        let mut registry = DatasetRegistry::open(&registry_path).unwrap();
+        assert!(registry.is_empty());
+        let dataset = CustomDataset::new("my_library", data_path.to_owned(), Format::Csv, 0, 1).with_header(true);
+        registry.register(dataset.to_owned()).unwrap();
+        assert!(matches!(registry.register(dataset.to_owned()), Err(RegistryError::AlreadyRegistered(_))));
+        for name in ["test_chembl", "my library", "a,b", ""] {
+            let invalid = CustomDataset { name: name.to_string(), ..dataset.to_owned() };
+            assert!(matches!(registry.register(invalid), Err(RegistryError::InvalidName(_))));
+        }
+        let missing = CustomDataset::new("missing", dir.join("missing.csv"), Format::Csv, "id", "smiles");
+        assert!(matches!(registry.register(missing), Err(RegistryError::Io(_))));
+
+        // persisted
+        let registry = DatasetRegistry::open(&registry_path).unwrap();
+        assert_eq!(registry.len(), 1);
+        let registered = registry.get("my_library").unwrap();
+        assert_eq!(registered.path, std::fs::canonicalize(&data_path).unwrap());
+        assert_eq!(registered.format, Format::Csv);
+        assert_eq!(registered.header, Some(true));
+        assert_eq!(registered.dsk().to_string(), "my_library");
+        assert_eq!(registry.resolve("my_library").unwrap(), crate::kinds::Dataset::Custom("my_library".to_string()));
+        assert_eq!(registry.resolve("test_chembl").unwrap(), crate::kinds::Dataset::TestChembl);
+        assert!(matches!(registry.resolve("my_libary"), Err(RegistryError::NotRegistered(_))));
+
+        let mut registry = DatasetRegistry::open(&registry_path).unwrap();
+        assert_eq!(registry.unregister("my_library").unwrap().name, "my_library");
+        assert!(matches!(registry.unregister("my_library"), Err(RegistryError::NotRegistered(_))));
+        assert!(DatasetRegistry::open(&registry_path).unwrap().is_empty());
+
+        // registry files with the columns as indices and the header as a flag
+        std::fs::write(&registry_path, format!(r#"[{{"name":"old","path":{:?},"format":"tsv","id_column":0,"smiles_column":1,"header":true}}]"#, data_path)).unwrap();
+        let registered = DatasetRegistry::open(&registry_path).unwrap().get("old").unwrap().to_owned();
+        assert_eq!((registered.id_column, registered.smiles_column, registered.header), (crate::data::Column::Index(0), crate::data::Column::Index(1), Some(true)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,9 @@
 //! Database ChEMBL
 //! https://ftp.ebi.ac.uk/pub/databases/chembl/ChEMBLdb/latest/
 //! 
+//! The chemreps file is read as a delimited source, see SourceDelimited::chembl.
+//! 
 
-use std::io::prelude::*;
 use rand::prelude::*;
 
 type ChemblID = String;
@@ -18,17 +19,10 @@ pub struct EntryChembl {
     pub inchi_key: StandardInchiKey
 }
 
-impl EntryChembl {
-    pub fn new(v: Vec<&str>) -> Self {
-        let (chembl_id, smiles, inchi, inchi_key) = (String::from(v[0]), String::from(v[1]), String::from(v[2]), String::from(v[3]));
-        Self { chembl_id, smiles, inchi, inchi_key }
-    }
-}
-
 type DataChembl = std::collections::HashMap<ChemblID, EntryChembl>;
 
 pub struct SourceChembl {
-    path: std::path::PathBuf,
+    source: crate::data::SourceDelimited,
     data: DataChembl
 }
 
@@ -46,50 +40,49 @@ impl std::default::Default for SourceChembl {
 impl SourceChembl {
     pub fn new() -> Self {
         Self {
-            path: std::path::PathBuf::new(),
+            source: crate::data::SourceDelimited::chembl(),
             data: DataChembl::new(),
         }
     }
 
     pub fn set_path(&mut self, path_str: &std::ffi::OsStr) {
-        self.path = std::path::PathBuf::from(path_str)
+        self.source.set_path(path_str)
     }
 
-
-    fn sanitize(&mut self) {
-        self.data.remove("chembl_id");
+    fn convert_entries(&mut self) {
+        self.data = self.source.get_all().iter()
+            .map(|entry| {
+                let property = |name: &str| entry.properties.get(name).cloned().unwrap_or_default();
+                let ec = EntryChembl {
+                    chembl_id: entry.id.to_owned(),
+                    smiles: entry.smiles.to_owned(),
+                    inchi: property("standard_inchi"),
+                    inchi_key: property("standard_inchi_key")
+                };
+                (entry.id.to_owned(), ec)
+            })
+            .collect();
     }
 
-    fn convert_lines(&mut self, lines: impl std::iter::Iterator<Item = std::io::Result<String>>) {
-        self.data.clear();
-        self.data = lines.map(|l| {
-                let line = l.unwrap();
-                // let v = line.as_str().split_whitespace().collect::<Vec<&str>>();
-                let v = line.as_str().split("\t").collect::<Vec<&str>>(); // whitespace is not working for some entries like 1077164, inchi is blank
-                (String::from(v[0]), EntryChembl::new(v))
-            }
-        )
-        .collect::<Vec<(ChemblID, EntryChembl)>>()
-        .into_iter()
-        .collect();
+    pub fn load_all(&mut self) {
+        self.source.load_all();
+        self.convert_entries();
+    }
 
-        self.sanitize();
+    /// load the entries of the range of file lines, the header line 0 included
+    pub fn load_partial(&mut self, range: &std::ops::Range<usize>) {
+        self.source.load_partial(&(range.start.saturating_sub(1)..range.end.saturating_sub(1)));
+        self.convert_entries();
     }
 
-    pub fn load_all(&mut self) {
-        match std::fs::File::open(&self.path) {
-            Ok(file) => {
-                let lines = std::io::BufReader::new(file).lines();
-                self.convert_lines(lines);
-            },
-            Err(e) => crate::logging::error(format!("Error {} on file path: {:?}", e, self.path).as_str())
-        }
+    /// index the lines of the file, so partial loads seek to their first entry
+    pub fn build_index(&mut self, stride: usize) -> std::io::Result<usize> {
+        self.source.build_index(stride).map(crate::data::LineIndex::len)
     }
 
-    pub fn load_partial(&mut self, range: &std::ops::Range<usize>) {
-        let file = std::fs::File::open(&self.path).unwrap();
-        let reader = std::io::BufReader::new(file);
-        self.convert_lines(reader.lines().skip(range.start).take(range.len()));
+    /// (ChEMBL ID, SMILES) pairs in batches, without the InChIs nor loading all entries
+    pub fn batches(&self, batch_size: usize) -> std::io::Result<impl Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send + 'static> {
+        self.source.batches(batch_size)
     }
 
     pub fn get(&self, id: &ChemblID) -> Option<&EntryChembl> { self.data.get(id) }
@@ -149,5 +142,16 @@ mod test_chembl {
         assert_eq!(ec.smiles, "CC(C)=CCC/C(C)=C/Cc1c2c(c3oc4c(c(=O)c3c1O)CC1c3c(c(O)cc(O)c3-4)OC1(C)C)C=CC(C)(C)O2");    
         assert_eq!(ec.inchi, "InChI=1S/C35H38O7/c1-17(2)9-8-10-18(3)11-12-19-28(38)27-29(39)21-15-22-25-26(23(36)16-24(37)33(25)42-35(22,6)7)32(21)40-31(27)20-13-14-34(4,5)41-30(19)20/h9,11,13-14,16,22,36-38H,8,10,12,15H2,1-7H3/b18-11+");
         assert_eq!(ec.inchi_key, "UJHMTIUPFDVYQA-WOJGMQOQSA-N");
+        // partial load after a seek
+        assert_eq!(sc.build_index(16).unwrap(), 100);
+        sc.load_partial(&(30..40));
+        assert_eq!(sc.len(), 10);
+        assert!(sc.get(&String::from("CHEMBL501923")).is_some());
+        // the header line is not an entry
+        sc.load_partial(&(0..10));
+        assert_eq!(sc.len(), 9);
+        assert_eq!(sc.get(&String::from("chembl_id")), None);
+        let (ids, _) = sc.batches(30).unwrap().next().unwrap().unwrap();
+        assert_eq!(ids.len(), 30);
     }
 }
\ No newline at end of file
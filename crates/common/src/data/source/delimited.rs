@@ -0,0 +1,580 @@
+//! Delimited text
+//!     SMILES, CSV or TSV files with the entry ID and the SMILES in columns mapped by name or index
+//!
+//! Lines are streamed from the file, gzip compressed or not. CSV and TSV fields may be quoted with double quotes,
+//! a quote inside being doubled, but a quoted field does not span lines. Without a header setting, the first line
+//! is a header if a column is mapped by name or if it holds no SMILES in the SMILES column.
+//! Malformed lines are reported with their line numbers and skipped.
+//!
+
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use strum_macros::{EnumString, Display};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DelimitedError {
+    #[error("line {0}: unclosed quote")]
+    UnclosedQuote(usize),
+    #[error("line {0}: no column {1}")]
+    MissingColumn(usize, Column),
+    #[error("line {0}: empty column {1}")]
+    EmptyField(usize, Column),
+    #[error("column {0} not in the header")]
+    UnknownColumn(Column),
+    #[error("column {0} mapped by name without header")]
+    HeaderRequired(Column)
+}
+
+/// Format of a custom dataset file, delimited text or SD file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Format {
+    /// columns separated by whitespaces, e.g. SMILES and name
+    Smi,
+    Csv,
+    Tsv,
+    /// SD file without columns, read by SourceSDF
+    Sdf
+}
+
+impl Format {
+    /// None for columns separated by any whitespace, or for SD files without columns
+    pub fn delimiter(&self) -> Option<char> {
+        match self {
+            Format::Smi | Format::Sdf => None,
+            Format::Csv => Some(','),
+            Format::Tsv => Some('\t')
+        }
+    }
+}
+
+/// Column of a delimited file, by its name in the header or its zero based index
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String)
+}
+
+impl std::str::FromStr for Column {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse::<usize>() {
+            Ok(index) => Column::Index(index),
+            Err(_) => Column::Name(s.to_string())
+        })
+    }
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+impl std::fmt::Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Column::Index(index) => write!(f, "{}", index),
+            Column::Name(name) => write!(f, "'{}'", name)
+        }
+    }
+}
+
+/// fields of a line, None for a quote left open
+fn split(line: &str, delimiter: Option<char>) -> Option<Vec<String>> {
+    let Some(delimiter) = delimiter else {
+        return Some(line.split_ascii_whitespace().map(str::to_string).collect());
+    };
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (false, c) if c == delimiter => fields.push(std::mem::take(&mut field).trim().to_string()),
+            (false, c) => field.push(c)
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field.trim().to_string());
+    Some(fields)
+}
+
+/// whether the text can be a SMILES, a header field like 'smiles' or 'canonical_smiles' cannot
+fn looks_like_smiles(text: &str) -> bool {
+    let mut chars = text.chars().peekable();
+    let mut atoms = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                if !chars.by_ref().any(|c| c == ']') {
+                    return false;
+                }
+                atoms += 1;
+            }
+            'B' | 'C' => {
+                chars.next_if(|next| (c, *next) == ('B', 'r') || (c, *next) == ('C', 'l'));
+                atoms += 1;
+            }
+            'N' | 'O' | 'P' | 'S' | 'F' | 'I' | 'b' | 'c' | 'n' | 'o' | 'p' | 's' | '*' => atoms += 1,
+            '0'..='9' | '%' | '(' | ')' | '=' | '#' | '$' | ':' | '/' | '\\' | '.' | '-' | '+' | '@' => {}
+            _ => return false
+        }
+    }
+    atoms > 0
+}
+
+#[derive(PartialEq, Debug)]
+pub struct EntryDelimited {
+    pub id: crate::data::types::EntryID,
+    pub smiles: crate::app::chem::types::SMILES,
+    /// values of the property columns, by their names in the header or their indices
+    pub properties: std::collections::BTreeMap<String, String>
+}
+
+/// Columns resolved to indices once the header is known
+struct Mapping {
+    id: (Column, usize),
+    smiles: (Column, usize),
+    properties: Vec<(String, usize)>
+}
+
+/// Format and columns of a file
+#[derive(Clone)]
+struct Layout {
+    format: Format,
+    id_column: Column,
+    smiles_column: Column,
+    property_columns: Vec<Column>,
+    /// None to detect the header from the first line
+    header: Option<bool>
+}
+
+/// Entries of the lines of a file, or the errors of the malformed lines
+pub struct EntriesDelimited<I: Iterator<Item = std::io::Result<String>>> {
+    layout: Layout,
+    lines: I,
+    /// number of the last line read, starting from 1
+    line: usize,
+    mapping: Option<Mapping>,
+    done: bool
+}
+
+impl<I: Iterator<Item = std::io::Result<String>>> EntriesDelimited<I> {
+    fn new(layout: Layout, lines: I) -> Self {
+        Self { layout, lines, line: 0, mapping: None, done: false }
+    }
+
+    fn resolve(&self, column: &Column, header: Option<&[String]>) -> Result<usize, DelimitedError> {
+        match (column, header) {
+            (Column::Index(index), _) => Ok(*index),
+            (Column::Name(name), Some(header)) => header.iter().position(|field| field == name).ok_or_else(|| DelimitedError::UnknownColumn(column.to_owned())),
+            (Column::Name(_), None) => Err(DelimitedError::HeaderRequired(column.to_owned()))
+        }
+    }
+
+    /// the mapping from the first line, true if it is a header
+    fn map_columns(&self, fields: &[String]) -> Result<(Mapping, bool), DelimitedError> {
+        let layout = &self.layout;
+        let by_name = std::iter::once(&layout.id_column).chain(std::iter::once(&layout.smiles_column)).chain(layout.property_columns.iter())
+            .any(|column| matches!(column, Column::Name(_)));
+        let is_header = layout.header.unwrap_or_else(|| by_name || match layout.smiles_column {
+            Column::Index(index) => !fields.get(index).is_some_and(|field| looks_like_smiles(field)),
+            Column::Name(_) => true
+        });
+        let header = is_header.then_some(fields);
+        let mut properties = vec![];
+        for column in layout.property_columns.iter() {
+            let index = self.resolve(column, header)?;
+            let name = match (column, header) {
+                (Column::Name(name), _) => name.to_owned(),
+                (Column::Index(index), Some(header)) => header.get(*index).cloned().unwrap_or_else(|| index.to_string()),
+                (Column::Index(index), None) => index.to_string()
+            };
+            properties.push((name, index));
+        }
+        let mapping = Mapping {
+            id: (layout.id_column.to_owned(), self.resolve(&layout.id_column, header)?),
+            smiles: (layout.smiles_column.to_owned(), self.resolve(&layout.smiles_column, header)?),
+            properties
+        };
+        Ok((mapping, is_header))
+    }
+
+    fn entry(mapping: &Mapping, n: usize, fields: &[String]) -> Result<EntryDelimited, DelimitedError> {
+        let field = |(column, index): &(Column, usize)| match fields.get(*index) {
+            Some(field) if field.is_empty() => Err(DelimitedError::EmptyField(n, column.to_owned())),
+            Some(field) => Ok(field.to_owned()),
+            None => Err(DelimitedError::MissingColumn(n, column.to_owned()))
+        };
+        Ok(EntryDelimited {
+            id: field(&mapping.id)?,
+            smiles: field(&mapping.smiles)?,
+            properties: mapping.properties.iter()
+                .map(|(name, index)| (name.to_owned(), fields.get(*index).cloned().unwrap_or_default()))
+                .collect()
+        })
+    }
+
+    /// the entry of the next line, None for a blank line or the header
+    fn feed(&mut self, line: &str) -> Option<Result<EntryDelimited, DelimitedError>> {
+        self.line += 1;
+        if line.trim().is_empty() {
+            return None;
+        }
+        let Some(fields) = split(line, self.layout.format.delimiter()) else {
+            return Some(Err(DelimitedError::UnclosedQuote(self.line)));
+        };
+        let mapping = match self.mapping.take() {
+            Some(mapping) => mapping,
+            None => match self.map_columns(&fields) {
+                Ok((mapping, true)) => {
+                    self.mapping = Some(mapping);
+                    return None;
+                }
+                Ok((mapping, false)) => mapping,
+                Err(e) => {
+                    // no entry can be read with the columns
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        };
+        let entry = Self::entry(&mapping, self.line, &fields);
+        self.mapping = Some(mapping);
+        Some(entry)
+    }
+}
+
+impl<I: Iterator<Item = std::io::Result<String>>> Iterator for EntriesDelimited<I> {
+    type Item = Result<EntryDelimited, DelimitedError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.lines.next()? {
+                Ok(line) => {
+                    if let Some(entry) = self.feed(&line) {
+                        return Some(entry);
+                    }
+                },
+                Err(e) => {
+                    crate::logging::error(format!("read error at line {}: {}", self.line + 1, e).as_str());
+                    self.done = true;
+                }
+            }
+        }
+        None
+    }
+}
+
+type DataDelimited = Vec<EntryDelimited>;
+/// lines of a file, which can be replaced by the lines after a seek
+type LinesDelimited = std::io::Lines<Box<dyn std::io::BufRead + Send>>;
+
+pub struct SourceDelimited {
+    path: std::path::PathBuf,
+    layout: Layout,
+    /// index of the entry lines, see build_index
+    index: Option<crate::data::LineIndex>,
+    data: DataDelimited,
+    errors: Vec<DelimitedError>
+}
+
+impl SourceDelimited {
+    pub fn new(format: Format, id_column: Column, smiles_column: Column) -> Self {
+        let layout = Layout { format, id_column, smiles_column, property_columns: vec![], header: None };
+        Self { path: std::path::PathBuf::new(), layout, index: None, data: DataDelimited::new(), errors: vec![] }
+    }
+
+    /// chemreps of ChEMBL, chembl_30_chemreps.txt
+    pub fn chembl() -> Self {
+        Self::new(Format::Tsv, Column::from("chembl_id"), Column::from("canonical_smiles"))
+            .with_property_columns(vec![Column::from("standard_inchi"), Column::from("standard_inchi_key")])
+    }
+
+    pub fn set_path(&mut self, path_str: &std::ffi::OsStr) {
+        self.path = std::path::PathBuf::from(path_str);
+        self.index = None;
+    }
+
+    pub fn get_path(&self) -> &std::path::Path { &self.path }
+
+    /// whether the first line is a header, detected if None
+    pub fn with_header(mut self, header: Option<bool>) -> Self {
+        self.layout.header = header;
+        self
+    }
+
+    /// columns kept as properties of the entries
+    pub fn with_property_columns(mut self, columns: Vec<Column>) -> Self {
+        self.layout.property_columns = columns;
+        self
+    }
+
+    pub fn entries(&self) -> std::io::Result<EntriesDelimited<LinesDelimited>> {
+        Ok(EntriesDelimited::new(self.layout.to_owned(), crate::data::source::read_lines(&self.path)?))
+    }
+
+    /// index the entry lines of the file, so partial loads and batches seek to their first entry
+    ///     the index is dropped with a new path but not when the file changes
+    pub fn build_index(&mut self, stride: usize) -> std::io::Result<&crate::data::LineIndex> {
+        if !crate::data::is_seekable(&self.path) {
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, format!("{:?} cannot seek", self.path)));
+        }
+        let mut entries = EntriesDelimited::new(self.layout.to_owned(), std::iter::empty());
+        let mut index = crate::data::LineIndex::new(stride);
+        let file = std::io::BufReader::new(std::fs::File::open(&self.path)?);
+        for result in crate::data::source::stream::OffsetLines::new(file) {
+            let (offset, line) = result?;
+            match entries.feed(&line) {
+                Some(Ok(_)) => index.push(offset, entries.line),
+                Some(Err(e)) if entries.done => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+                _ => {}
+            }
+        }
+        Ok(self.index.insert(index))
+    }
+
+    pub fn get_index(&self) -> Option<&crate::data::LineIndex> { self.index.as_ref() }
+
+    /// entries from a mark of the index at or before the start, with the number of its first entry
+    fn entries_from(&self, start: usize) -> std::io::Result<(EntriesDelimited<LinesDelimited>, usize)> {
+        use std::io::{BufRead, Seek};
+        let Some(index) = self.index.as_ref() else {
+            return Ok((self.entries()?, 0));
+        };
+        let (pos, line, first) = match index.seek(start) {
+            Some((offset, line, skip)) => (std::io::SeekFrom::Start(offset), line, start - skip),
+            None => (std::io::SeekFrom::End(0), 0, index.len())
+        };
+        // the columns are mapped from the header or the first entry
+        let mut entries = self.entries()?;
+        while entries.mapping.is_none() && entries.next().is_some() {}
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(pos)?;
+        let reader: Box<dyn BufRead + Send> = Box::new(std::io::BufReader::new(file));
+        entries.lines = reader.lines();
+        entries.line = line.saturating_sub(1);
+        Ok((entries, first))
+    }
+
+    /// (entry ID, SMILES) pairs from the start, the errors of the malformed lines are logged
+    fn pairs_from(&self, start: usize) -> std::io::Result<impl Iterator<Item = (crate::data::types::EntryID, crate::app::chem::types::SMILES)> + Send + 'static> {
+        let (entries, first) = self.entries_from(start)?;
+        let path = self.path.to_owned();
+        Ok(entries
+            .filter_map(move |result| match result {
+                Ok(entry) => Some((entry.id, entry.smiles)),
+                Err(e) => {
+                    crate::logging::error(format!("{:?} {}", path, e).as_str());
+                    None
+                }
+            })
+            .skip(start - first))
+    }
+
+    /// entries of the range in batches of (entry ID, SMILES) pairs, without loading them
+    pub fn batches_of(&self, range: &std::ops::Range<usize>, batch_size: usize) -> std::io::Result<impl Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send + 'static> {
+        Ok(crate::data::Batches::new(self.pairs_from(range.start)?.take(range.len()).map(Ok), batch_size))
+    }
+
+    pub fn batches(&self, batch_size: usize) -> std::io::Result<impl Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send + 'static> {
+        self.batches_of(&(0..usize::MAX), batch_size)
+    }
+
+    /// load the entries of the range, the errors of the malformed lines are logged and kept
+    fn load(&mut self, range: &std::ops::Range<usize>) {
+        let (mut data, mut errors) = (DataDelimited::new(), vec![]);
+        match self.entries_from(range.start) {
+            Ok((entries, first)) => {
+                let mut count = first;
+                for result in entries {
+                    match result {
+                        Ok(_) if count >= range.end => break,
+                        Ok(entry) => {
+                            if count >= range.start {
+                                data.push(entry);
+                            }
+                            count += 1;
+                        },
+                        Err(e) => {
+                            crate::logging::error(format!("{:?} {}", self.path, e).as_str());
+                            errors.push(e);
+                        }
+                    }
+                }
+            },
+            Err(e) => crate::logging::error(format!("Error {} on file path: {:?}", e, self.path).as_str())
+        }
+        self.data = data;
+        self.errors = errors;
+    }
+
+    pub fn load_all(&mut self) {
+        self.load(&(0..usize::MAX));
+    }
+
+    /// load the entries of the range only, e.g. a dividend, after a seek if the file is indexed
+    pub fn load_partial(&mut self, range: &std::ops::Range<usize>) {
+        self.load(range);
+    }
+
+    pub fn get_all(&self) -> &DataDelimited { &self.data }
+    pub fn get_errors(&self) -> &[DelimitedError] { &self.errors }
+    pub fn len(&self) -> usize { self.data.len() }
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    pub fn get_id_smiles_pairs(&self) -> (Vec<crate::data::types::EntryID>, Vec<crate::app::chem::types::SMILES>) {
+        self.data.iter()
+            .map(|entry| (entry.id.to_owned(), entry.smiles.to_owned()))
+            .unzip()
+    }
+}
+
+impl From<&crate::data::CustomDataset> for SourceDelimited {
+    fn from(dataset: &crate::data::CustomDataset) -> Self {
+        let mut sd = Self::new(dataset.format, dataset.id_column.to_owned(), dataset.smiles_column.to_owned())
+            .with_header(dataset.header);
+        sd.set_path(dataset.path.as_os_str());
+        sd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(dir: &std::path::Path, filename: &str, content: &str, sd: SourceDelimited) -> SourceDelimited {
+        let path = dir.join(filename);
+        std::fs::write(&path, content).unwrap();
+        let mut sd = sd;
+        sd.set_path(path.as_os_str());
+        sd.load_all();
+        sd
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("a, \"b,c\" ,\"d \"\"e\"\"\"", Some(',')).unwrap(), vec!["a", "b,c", "d \"e\""]);
+        assert_eq!(split("a\t\t c", Some('\t')).unwrap(), vec!["a", "", "c"]);
+        assert_eq!(split("  CCO   ethanol ", None).unwrap(), vec!["CCO", "ethanol"]);
+        assert_eq!(split("a,\"b", Some(',')), None);
+        assert!(looks_like_smiles("CC(=O)Nc1ccc(O)cc1"));
+        assert!(looks_like_smiles("[NH4+].[Cl-]"));
+        assert!(looks_like_smiles("C[C@@H](Br)Cl"));
+        for header in ["smiles", "SMILES", "canonical_smiles", "Structure", ""] {
+            assert!(!looks_like_smiles(header));
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = crate::utils::tests::temp_dir();
+
+        // header detected from the SMILES column
+        let sd = load(&dir, "library.smi", "smiles name\nCCO ethanol\n\nCC(=O)Nc1ccc(O)cc1   paracetamol\nC1CC1\n", SourceDelimited::new(Format::Smi, Column::from(1), Column::from(0)));
+        assert_eq!(sd.get_id_smiles_pairs(), (vec!["ethanol".to_string(), "paracetamol".to_string()], vec!["CCO".to_string(), "CC(=O)Nc1ccc(O)cc1".to_string()]));
+        assert_eq!(sd.get_errors(), &[DelimitedError::MissingColumn(5, Column::Index(1))]);
+        let sd = load(&dir, "library.smi", "CCO ethanol\nCCN ethylamine\n", SourceDelimited::new(Format::Smi, Column::from(1), Column::from(0)));
+        assert_eq!(sd.len(), 2);
+
+        // columns by name, quoted fields and malformed lines
+        let content = "\"name\",weight,smiles\nm1,46.07,CCO\n\"m2, salt\",58.44,\"[Na+].[Cl-]\"\nm3,0,\"CC\nm4,1,\n,2,C\nm5,3,N\n";
+        let sd = load(&dir, "library.csv", content, SourceDelimited::new(Format::Csv, Column::from("name"), Column::from("smiles")).with_property_columns(vec![Column::from(1)]));
+        assert_eq!(sd.get_id_smiles_pairs(), (vec!["m1".to_string(), "m2, salt".to_string(), "m5".to_string()], vec!["CCO".to_string(), "[Na+].[Cl-]".to_string(), "N".to_string()]));
+        assert_eq!(sd.get_all()[1].properties.get("weight").unwrap(), "58.44");
+        assert_eq!(sd.get_errors(), &[
+            DelimitedError::UnclosedQuote(4),
+            DelimitedError::EmptyField(5, Column::from("smiles")),
+            DelimitedError::EmptyField(6, Column::from("name"))
+        ]);
+
+        // the range counts the entries, not the lines
+        let mut sd = SourceDelimited::new(Format::Csv, Column::from("name"), Column::from("smiles"));
+        sd.set_path(dir.join("library.csv").as_os_str());
+        sd.load_partial(&(1..3));
+        assert_eq!(sd.get_id_smiles_pairs().0, vec!["m2, salt".to_string(), "m5".to_string()]);
+
+        // unknown columns stop the loading
+        let sd = load(&dir, "library.csv", content, SourceDelimited::new(Format::Csv, Column::from("id"), Column::from("smiles")));
+        assert!(sd.is_empty());
+        assert_eq!(sd.get_errors(), &[DelimitedError::UnknownColumn(Column::from("id"))]);
+        let sd = load(&dir, "library.csv", content, SourceDelimited::new(Format::Csv, Column::from("name"), Column::from("smiles")).with_header(Some(false)));
+        assert_eq!(sd.get_errors(), &[DelimitedError::HeaderRequired(Column::from("name"))]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_index() {
+        let dir = crate::utils::tests::temp_dir();
+        let content = "id,smiles\nm0,C\n\nm1,CC\nm2,\"CCC\nm3,CCCC\r\nm4,N\nm5,O\n";
+        let mut sd = load(&dir, "library.csv", content, SourceDelimited::new(Format::Csv, Column::from("id"), Column::from("smiles")));
+        assert_eq!(sd.len(), 5);
+        assert_eq!(sd.build_index(2).unwrap().len(), 5);
+
+        // seek to the mark of m3, the line numbers of the errors follow
+        sd.load_partial(&(3..10));
+        assert_eq!(sd.get_id_smiles_pairs().0, vec!["m4".to_string(), "m5".to_string()]);
+        assert!(sd.get_errors().is_empty());
+        sd.load_partial(&(1..3));
+        assert_eq!(sd.get_id_smiles_pairs(), (vec!["m1".to_string(), "m3".to_string()], vec!["CC".to_string(), "CCCC".to_string()]));
+        assert_eq!(sd.get_errors(), &[DelimitedError::UnclosedQuote(5)]);
+        sd.load_partial(&(5..6));
+        assert!(sd.is_empty());
+
+        let batches: Vec<crate::app::chem::types::IdSmilesPairs> = sd.batches_of(&(1..5), 3).unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(batches[0].0, vec!["m1".to_string(), "m3".to_string(), "m4".to_string()]);
+        assert_eq!(batches[1].0, vec!["m5".to_string()]);
+        let doc: crate::data::DocSMILES = sd.batches(2).unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(doc.len(), 5);
+        assert_eq!(doc.get_smiles(&"m3".to_string()).unwrap(), "CCCC");
+
+        // the first line is an entry
+        let mut sd = load(&dir, "library.smi", "C m0\nCC m1\nCCC m2\n", SourceDelimited::new(Format::Smi, Column::from(1), Column::from(0)));
+        sd.build_index(2).unwrap();
+        sd.load_partial(&(2..3));
+        assert_eq!(sd.get_id_smiles_pairs().0, vec!["m2".to_string()]);
+
+        let mut sd = load(&dir, "library.csv", content, SourceDelimited::new(Format::Csv, Column::from("name"), Column::from("smiles")));
+        assert_eq!(sd.build_index(2).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_chembl_gz() {
+        use std::io::Write;
+        let dir = crate::utils::tests::temp_dir();
+        let path = dir.join("chembl_30_chemreps.txt.gz");
+        let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::default());
+        encoder.write_all(b"chembl_id\tcanonical_smiles\tstandard_inchi\tstandard_inchi_key\nCHEMBL545\tCCO\tInChI=1S/C2H6O/c1-2-3/h3H,2H2,1H3\tLFQSCWFLJHTTHZ-UHFFFAOYSA-N\nCHEMBL1\tC\t\t\n").unwrap();
+        encoder.finish().unwrap();
+        let mut sd = SourceDelimited::chembl();
+        sd.set_path(path.as_os_str());
+        sd.load_all();
+        assert_eq!(sd.len(), 2);
+        assert_eq!(sd.get_all()[0].properties.get("standard_inchi_key").unwrap(), "LFQSCWFLJHTTHZ-UHFFFAOYSA-N");
+        assert_eq!(sd.get_all()[1].properties.get("standard_inchi").unwrap(), "");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
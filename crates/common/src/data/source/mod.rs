@@ -1,2 +1,18 @@
 pub mod chembl;
-// pub mod pubchem;
\ No newline at end of file
+pub mod delimited;
+pub mod molfile;
+pub mod pubchem;
+pub mod sdf;
+pub mod stream;
+
+use std::io::BufRead;
+
+/// lines of a source file, decompressed on the fly for a .gz file
+pub(crate) fn read_lines(path: &std::path::Path) -> std::io::Result<std::io::Lines<Box<dyn BufRead + Send>>> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let reader: Box<dyn BufRead + Send> = match path.extension() {
+        Some(ext) if ext == "gz" => Box::new(std::io::BufReader::new(flate2::bufread::MultiGzDecoder::new(file))),
+        _ => Box::new(file)
+    };
+    Ok(reader.lines())
+}
@@ -0,0 +1,466 @@
+//! MOL file connection tables, V2000 and V3000
+//!
+//! Atoms, bonds, charges and isotopes are read from the connection table and written as SMILES, without
+//! stereochemistry. Bonds keep their Kekulé orders, aromatic bonds (type 4) make their atoms aromatic.
+//!
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MolfileError {
+    #[error("molfile truncated, missing {0}")]
+    Truncated(&'static str),
+    #[error("invalid counts line: '{0}'")]
+    InvalidCounts(String),
+    #[error("invalid atom: '{0}'")]
+    InvalidAtom(String),
+    #[error("invalid bond: '{0}'")]
+    InvalidBond(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Atom {
+    symbol: String,
+    charge: i8,
+    isotope: u16,
+    aromatic: bool
+}
+
+impl Atom {
+    fn new(symbol: &str) -> Self {
+        Self { symbol: symbol.to_string(), charge: 0, isotope: 0, aromatic: false }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Bond {
+    atoms: (usize, usize),
+    /// 1, 2 or 3, 4 for aromatic
+    order: u8
+}
+
+impl Bond {
+    fn valence(&self) -> u8 {
+        match self.order {
+            4 => 1,
+            order => order
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Molfile {
+    title: String,
+    atoms: Vec<Atom>,
+    bonds: Vec<Bond>
+}
+
+/// trimmed columns of a fixed width line, empty past its end
+fn column(line: &str, range: std::ops::Range<usize>) -> &str {
+    line.get(range.start.min(line.len())..range.end.min(line.len())).unwrap_or("").trim()
+}
+
+/// V2000 charge code of the atom block
+fn charge_v2000(code: &str) -> i8 {
+    match code {
+        "1" => 3,
+        "2" => 2,
+        "3" => 1,
+        "5" => -1,
+        "6" => -2,
+        "7" => -3,
+        _ => 0
+    }
+}
+
+impl Molfile {
+    pub fn parse(block: &str) -> Result<Self, MolfileError> {
+        let lines: Vec<&str> = block.lines().collect();
+        let counts = lines.get(3).ok_or(MolfileError::Truncated("counts line"))?;
+        let mut molfile = Self { title: lines[0].trim().to_string(), ..Self::default() };
+        if counts.contains("V3000") {
+            molfile.parse_v3000(&lines[4..])?;
+        } else {
+            molfile.parse_v2000(counts, &lines[4..])?;
+        }
+        molfile.mark_aromatic();
+        Ok(molfile)
+    }
+
+    fn parse_v2000(&mut self, counts: &str, lines: &[&str]) -> Result<(), MolfileError> {
+        let count = |range| column(counts, range).parse::<usize>().map_err(|_| MolfileError::InvalidCounts(counts.to_string()));
+        let (atom_count, bond_count) = (count(0..3)?, count(3..6)?);
+        if lines.len() < atom_count + bond_count {
+            return Err(MolfileError::Truncated("atoms or bonds"));
+        }
+        for line in lines[..atom_count].iter() {
+            let symbol = column(line, 31..34);
+            if symbol.is_empty() {
+                return Err(MolfileError::InvalidAtom(line.to_string()));
+            }
+            let mut atom = Atom::new(symbol);
+            atom.charge = charge_v2000(column(line, 36..39));
+            self.atoms.push(atom);
+        }
+        for line in lines[atom_count..atom_count + bond_count].iter() {
+            let index = |range| column(line, range).parse::<usize>().ok().filter(|i| (1..=atom_count).contains(i));
+            let order = column(line, 6..9).parse::<u8>().ok().filter(|order| (1..=4).contains(order));
+            match (index(0..3), index(3..6), order) {
+                (Some(a1), Some(a2), Some(order)) => self.bonds.push(Bond { atoms: (a1 - 1, a2 - 1), order }),
+                _ => return Err(MolfileError::InvalidBond(line.to_string()))
+            }
+        }
+
+        // properties, a CHG or ISO line replaces the values of the atom block
+        let (mut charges_reset, mut isotopes_reset) = (false, false);
+        for line in lines[atom_count + bond_count..].iter() {
+            let property = column(line, 0..6);
+            if property == "M  END" {
+                break;
+            }
+            if property != "M  CHG" && property != "M  ISO" {
+                continue;
+            }
+            if property == "M  CHG" && !charges_reset {
+                self.atoms.iter_mut().for_each(|atom| atom.charge = 0);
+                charges_reset = true;
+            }
+            if property == "M  ISO" && !isotopes_reset {
+                self.atoms.iter_mut().for_each(|atom| atom.isotope = 0);
+                isotopes_reset = true;
+            }
+            let values: Vec<&str> = line[6..].split_ascii_whitespace().skip(1).collect();
+            for pair in values.chunks(2) {
+                let atom = pair[0].parse::<usize>().ok()
+                    .and_then(|i| i.checked_sub(1))
+                    .and_then(|i| self.atoms.get_mut(i))
+                    .ok_or_else(|| MolfileError::InvalidAtom(line.to_string()))?;
+                let value = pair.get(1).ok_or_else(|| MolfileError::InvalidAtom(line.to_string()))?;
+                if property == "M  CHG" {
+                    atom.charge = value.parse().map_err(|_| MolfileError::InvalidAtom(line.to_string()))?;
+                } else {
+                    atom.isotope = value.parse().map_err(|_| MolfileError::InvalidAtom(line.to_string()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_v3000(&mut self, lines: &[&str]) -> Result<(), MolfileError> {
+        // lines ending with '-' continue on the next one
+        let mut v30_lines: Vec<String> = vec![];
+        let mut continued = false;
+        for line in lines.iter() {
+            if column(line, 0..6) == "M  END" {
+                break;
+            }
+            let Some(content) = line.strip_prefix("M  V30 ") else { continue };
+            let content = content.trim_end();
+            match (continued, v30_lines.last_mut()) {
+                (true, Some(last)) => last.push_str(content.trim_end_matches('-')),
+                _ => v30_lines.push(content.trim_end_matches('-').to_string())
+            }
+            continued = content.ends_with('-');
+        }
+
+        let mut indices: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+        let mut block = "";
+        for line in v30_lines.iter() {
+            let mut tokens = line.split_ascii_whitespace();
+            match (tokens.next(), block) {
+                (Some("BEGIN"), _) => block = tokens.next().unwrap_or(""),
+                (Some("END"), _) => block = "",
+                (Some(index), "ATOM") => {
+                    let symbol = tokens.next().filter(|s| !s.starts_with('[') && !s.starts_with("NOT"))
+                        .ok_or_else(|| MolfileError::InvalidAtom(line.to_string()))?;
+                    let mut atom = Atom::new(symbol);
+                    for (key, value) in tokens.skip(4).filter_map(|t| t.split_once('=')) {
+                        match key {
+                            "CHG" => atom.charge = value.parse().map_err(|_| MolfileError::InvalidAtom(line.to_string()))?,
+                            "MASS" => atom.isotope = value.parse().map_err(|_| MolfileError::InvalidAtom(line.to_string()))?,
+                            _ => {}
+                        }
+                    }
+                    indices.insert(index, self.atoms.len());
+                    self.atoms.push(atom);
+                }
+                (Some(_), "BOND") => {
+                    let order = tokens.next().and_then(|t| t.parse::<u8>().ok()).filter(|order| (1..=4).contains(order));
+                    let a1 = tokens.next().and_then(|t| indices.get(t));
+                    let a2 = tokens.next().and_then(|t| indices.get(t));
+                    match (a1, a2, order) {
+                        (Some(a1), Some(a2), Some(order)) => self.bonds.push(Bond { atoms: (*a1, *a2), order }),
+                        _ => return Err(MolfileError::InvalidBond(line.to_string()))
+                    }
+                }
+                _ => {}
+            }
+        }
+        if indices.is_empty() && !v30_lines.iter().any(|l| l.starts_with("COUNTS")) {
+            return Err(MolfileError::Truncated("V3000 connection table"));
+        }
+        Ok(())
+    }
+
+    fn mark_aromatic(&mut self) {
+        for bond in self.bonds.iter().filter(|bond| bond.order == 4) {
+            self.atoms[bond.atoms.0].aromatic = true;
+            self.atoms[bond.atoms.1].aromatic = true;
+        }
+    }
+
+    pub fn title(&self) -> &str { &self.title }
+    pub fn len(&self) -> usize { self.atoms.len() }
+    pub fn is_empty(&self) -> bool { self.atoms.is_empty() }
+}
+
+/// default valences of the elements whose hydrogens are implicit
+fn default_valences(symbol: &str) -> &'static [u8] {
+    match symbol {
+        "H" | "F" | "Cl" | "Br" | "I" => &[1],
+        "B" => &[3],
+        "C" | "Si" => &[4],
+        "N" | "As" => &[3, 5],
+        "O" => &[2],
+        "P" => &[3, 5],
+        "S" | "Se" => &[2, 4, 6],
+        _ => &[]
+    }
+}
+
+/// elements written without brackets when neutral, the organic subset of SMILES
+fn is_organic(symbol: &str) -> bool {
+    matches!(symbol, "B" | "C" | "N" | "O" | "P" | "S" | "F" | "Cl" | "Br" | "I")
+}
+
+/// elements written in lower case when aromatic
+fn is_aromatic_symbol(symbol: &str) -> bool {
+    matches!(symbol, "B" | "C" | "N" | "O" | "P" | "S" | "Se" | "As")
+}
+
+impl Molfile {
+    /// hydrogens of an atom from its default valences shifted by its charge
+    fn implicit_hydrogens(&self, atom: usize, bonds: &[(usize, usize)]) -> u8 {
+        let symbol = self.atoms[atom].symbol.as_str();
+        let charge = self.atoms[atom].charge as i16;
+        let bond_valence: i16 = bonds.iter().map(|(_, b)| self.bonds[*b].valence() as i16).sum::<i16>() + i16::from(self.atoms[atom].aromatic);
+        default_valences(symbol).iter()
+            .map(|v| match symbol {
+                "C" | "Si" => *v as i16 - charge.abs(),
+                "B" => *v as i16 - charge,
+                _ => *v as i16 + charge
+            })
+            .find(|v| *v >= bond_valence)
+            .map_or(0, |v| (v - bond_valence) as u8)
+    }
+
+    fn write_atom(&self, atom: usize, bonds: &[(usize, usize)], smiles: &mut String) {
+        let a = &self.atoms[atom];
+        let symbol = match a.aromatic && is_aromatic_symbol(&a.symbol) {
+            true => a.symbol.to_lowercase(),
+            false => a.symbol.to_owned()
+        };
+        if is_organic(&a.symbol) && a.charge == 0 && a.isotope == 0 {
+            smiles.push_str(&symbol);
+            return;
+        }
+        smiles.push('[');
+        if a.isotope > 0 {
+            smiles.push_str(&a.isotope.to_string());
+        }
+        smiles.push_str(&symbol);
+        match self.implicit_hydrogens(atom, bonds) {
+            0 => {}
+            1 => smiles.push('H'),
+            n => smiles.push_str(&format!("H{}", n))
+        }
+        match a.charge {
+            0 => {}
+            1 => smiles.push('+'),
+            -1 => smiles.push('-'),
+            c if c > 0 => smiles.push_str(&format!("+{}", c)),
+            c => smiles.push_str(&format!("-{}", -c))
+        }
+        smiles.push(']');
+    }
+
+    fn write_bond(&self, bond: usize, smiles: &mut String) {
+        let b = &self.bonds[bond];
+        match b.order {
+            2 => smiles.push('='),
+            3 => smiles.push('#'),
+            // a single bond between aromatic atoms is explicit
+            1 if self.atoms[b.atoms.0].aromatic && self.atoms[b.atoms.1].aromatic => smiles.push('-'),
+            _ => {}
+        }
+    }
+
+    /// SMILES of the connection table, disconnected components separated by '.'
+    pub fn to_smiles(&self) -> String {
+        let mut neighbours: Vec<Vec<(usize, usize)>> = vec![vec![]; self.atoms.len()];
+        for (b, bond) in self.bonds.iter().enumerate() {
+            neighbours[bond.atoms.0].push((bond.atoms.1, b));
+            neighbours[bond.atoms.1].push((bond.atoms.0, b));
+        }
+
+        // depth first spanning forest, the other bonds close rings
+        let mut visited = vec![false; self.atoms.len()];
+        let mut children: Vec<Vec<(usize, usize)>> = vec![vec![]; self.atoms.len()];
+        let mut tree_bonds = vec![false; self.bonds.len()];
+        let mut roots = vec![];
+        for root in 0..self.atoms.len() {
+            if visited[root] {
+                continue;
+            }
+            roots.push(root);
+            visited[root] = true;
+            let mut stack = vec![(root, 0)];
+            while let Some((atom, next)) = stack.pop() {
+                if let Some((neighbour, b)) = neighbours[atom].get(next).copied() {
+                    stack.push((atom, next + 1));
+                    if !visited[neighbour] {
+                        visited[neighbour] = true;
+                        tree_bonds[b] = true;
+                        children[atom].push((neighbour, b));
+                        stack.push((neighbour, 0));
+                    }
+                }
+            }
+        }
+
+        let mut ring_numbers: std::collections::HashMap<usize, usize> = std::collections::HashMap::new();
+        let mut written = vec![false; self.atoms.len()];
+        let mut smiles = String::new();
+        for (n, root) in roots.iter().enumerate() {
+            if n > 0 {
+                smiles.push('.');
+            }
+            // atoms to write, or a closing parenthesis
+            let mut stack: Vec<Option<(usize, Option<usize>, bool)>> = vec![Some((*root, None, false))];
+            while let Some(item) = stack.pop() {
+                let Some((atom, bond, branch)) = item else {
+                    smiles.push(')');
+                    continue;
+                };
+                if branch {
+                    smiles.push('(');
+                }
+                if let Some(b) = bond {
+                    self.write_bond(b, &mut smiles);
+                }
+                self.write_atom(atom, &neighbours[atom], &mut smiles);
+                written[atom] = true;
+                for (neighbour, b) in neighbours[atom].iter().filter(|(_, b)| !tree_bonds[*b]) {
+                    let number = match ring_numbers.remove(b) {
+                        Some(number) => number,
+                        None if !written[*neighbour] => {
+                            let number = (1..).find(|i| !ring_numbers.values().any(|n| n == i)).unwrap();
+                            ring_numbers.insert(*b, number);
+                            self.write_bond(*b, &mut smiles);
+                            number
+                        },
+                        None => continue
+                    };
+                    match number {
+                        1..=9 => smiles.push_str(&number.to_string()),
+                        _ => smiles.push_str(&format!("%{}", number))
+                    }
+                }
+                // the last child continues the chain, the others are branches
+                for (i, (child, b)) in children[atom].iter().enumerate().rev() {
+                    let branch = i + 1 < children[atom].len();
+                    if branch {
+                        stack.push(None);
+                    }
+                    stack.push(Some((*child, Some(*b), branch)));
+                }
+            }
+        }
+        smiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2000(atoms: &[(&str, &str)], bonds: &[(usize, usize, u8)], properties: &[&str]) -> String {
+        let mut block = format!("title\n  test\n\n{:3}{:3}  0  0  0  0  0  0  0  0999 V2000\n", atoms.len(), bonds.len());
+        for (symbol, charge) in atoms.iter() {
+            block.push_str(&format!("    0.0000    0.0000    0.0000 {:<3} 0{:>3}  0  0  0  0  0  0  0  0  0  0\n", symbol, charge));
+        }
+        for (a1, a2, order) in bonds.iter() {
+            block.push_str(&format!("{:3}{:3}{:3}  0\n", a1, a2, order));
+        }
+        for property in properties.iter() {
+            block.push_str(property);
+            block.push('\n');
+        }
+        block.push_str("M  END\n");
+        block
+    }
+
+    #[test]
+    fn test_v2000() {
+        let ethanol = Molfile::parse(&v2000(&[("C", "0"), ("C", "0"), ("O", "0")], &[(1, 2, 1), (2, 3, 1)], &[])).unwrap();
+        assert_eq!(ethanol.title(), "title");
+        assert_eq!(ethanol.to_smiles(), "CCO");
+
+        let benzene = v2000(&[("C", "0"); 6], &[(1, 2, 2), (2, 3, 1), (3, 4, 2), (4, 5, 1), (5, 6, 2), (6, 1, 1)], &[]);
+        assert_eq!(Molfile::parse(&benzene).unwrap().to_smiles(), "C1=CC=CC=C1");
+        let benzene_aromatic = v2000(&[("C", "0"); 6], &[(1, 2, 4), (2, 3, 4), (3, 4, 4), (4, 5, 4), (5, 6, 4), (6, 1, 4)], &[]);
+        assert_eq!(Molfile::parse(&benzene_aromatic).unwrap().to_smiles(), "c1ccccc1");
+
+        // acetate from the atom block, ammonium and chloride from the CHG line replacing it
+        let acetate = v2000(&[("C", "0"), ("C", "0"), ("O", "0"), ("O", "5")], &[(1, 2, 1), (2, 3, 2), (2, 4, 1)], &[]);
+        assert_eq!(Molfile::parse(&acetate).unwrap().to_smiles(), "CC(=O)[O-]");
+        let salt = v2000(&[("N", "3"), ("Cl", "0"), ("C", "0")], &[], &["M  CHG  2   1   1   2  -1", "M  ISO  1   3  13"]);
+        assert_eq!(Molfile::parse(&salt).unwrap().to_smiles(), "[NH4+].[Cl-].[13CH4]");
+
+        // branches and two rings
+        let methylcyclohexanol = v2000(&[("C", "0"), ("C", "0"), ("C", "0"), ("C", "0"), ("C", "0"), ("C", "0"), ("C", "0"), ("O", "0")],
+            &[(1, 2, 1), (2, 3, 1), (3, 4, 1), (4, 5, 1), (5, 6, 1), (6, 1, 1), (1, 7, 1), (1, 8, 1)], &[]);
+        assert_eq!(Molfile::parse(&methylcyclohexanol).unwrap().to_smiles(), "C1(CCCCC1)(C)O");
+        let bicycle = v2000(&[("C", "0"); 4], &[(1, 2, 1), (2, 3, 1), (3, 1, 1), (3, 4, 1), (4, 1, 1)], &[]);
+        assert_eq!(Molfile::parse(&bicycle).unwrap().to_smiles(), "C12CC1C2");
+        let sodium = v2000(&[("Na", "3")], &[], &[]);
+        assert_eq!(Molfile::parse(&sodium).unwrap().to_smiles(), "[Na+]");
+
+        assert_eq!(Molfile::parse("title\n\n"), Err(MolfileError::Truncated("counts line")));
+        assert!(matches!(Molfile::parse(&v2000(&[("C", "0")], &[(1, 2, 1)], &[])), Err(MolfileError::InvalidBond(_))));
+        assert!(matches!(Molfile::parse("t\n\n\nxx\n"), Err(MolfileError::InvalidCounts(_))));
+    }
+
+    #[test]
+    fn test_v3000() {
+        let block = "\
+glycine zwitterion
+  test
+
+  0  0  0     0  0            999 V3000
+M  V30 BEGIN CTAB
+M  V30 COUNTS 5 4 0 0 0
+M  V30 BEGIN ATOM
+M  V30 1 N 0 0 0 0 CHG=1
+M  V30 2 C 1.2 0 0 0
+M  V30 3 C 2.4 0 0 0
+M  V30 4 O 3.6 0 0 0 -
+M  V30 CHG=-1
+M  V30 7 O 2.4 1.2 0 0 MASS=18
+M  V30 END ATOM
+M  V30 BEGIN BOND
+M  V30 1 1 1 2
+M  V30 2 1 2 3
+M  V30 3 1 3 4
+M  V30 4 2 3 7
+M  V30 END BOND
+M  V30 END CTAB
+M  END
+";
+        let molfile = Molfile::parse(block).unwrap();
+        assert_eq!(molfile.title(), "glycine zwitterion");
+        assert_eq!(molfile.len(), 5);
+        assert_eq!(molfile.to_smiles(), "[NH3+]CC([O-])=[18O]");
+        assert!(matches!(Molfile::parse("t\n\n\n  0  0  0     0  0            999 V3000\nM  V30 BEGIN CTAB\nM  V30 BEGIN BOND\nM  V30 1 1 1 2\n"), Err(MolfileError::InvalidBond(_))));
+    }
+}
@@ -1,13 +1,27 @@
-use std::io::Read;
+//! Database PubChem
+//! https://ftp.ncbi.nlm.nih.gov/pubchem/Compound/Extras/CID-SMILES.gz
+//!
+//! Lines of CID and SMILES separated by a tab, read from the gzip file as a stream so only the loaded
+//! entries are kept in memory. Gzip files cannot seek, so the file is decompressed once before indexing
+//! its lines, and the dividends are read from the plain file after a seek.
+//!
 
-#[derive(Debug)]
+pub type CompoundID = crate::data::types::EntryID;
+
+#[derive(PartialEq, Debug)]
 pub struct EntryPubChem {
+    pub cid: CompoundID,
     pub smiles: crate::app::chem::types::SMILES
 }
 
-impl From<&str> for EntryPubChem {
-    fn from(l: &str) -> Self {
-        Self { smiles: l.to_string() }
+impl EntryPubChem {
+    /// None for a line without CID and SMILES
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_ascii_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(cid), Some(smiles), None) => Some(Self { cid: cid.to_string(), smiles: smiles.to_string() }),
+            _ => None
+        }
     }
 }
 
@@ -15,78 +29,274 @@ type DataPubChem = Vec<EntryPubChem>;
 
 pub struct SourcePubChem {
     path: std::path::PathBuf,
+    /// plain file with the index of its entry lines, see build_index
+    index: Option<(std::path::PathBuf, crate::data::LineIndex)>,
     data: DataPubChem
 }
 
+impl std::default::Default for SourcePubChem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SourcePubChem {
     pub fn new() -> Self {
-        Self { path: std::path::PathBuf::new(), data: DataPubChem::new() }
+        Self { path: std::path::PathBuf::new(), index: None, data: DataPubChem::new() }
     }
 
     pub fn set_path(&mut self, path_str: &std::ffi::OsStr) {
         self.path = std::path::PathBuf::from(path_str);
+        self.index = None;
     }
 
-    fn convert_lines(&mut self, lines: impl std::iter::Iterator<Item = std::io::Result<String>>) {
-        self.data.clear();
-        self.data = lines.map(|l| {
-            let line = l.unwrap();
-            EntryPubChem::from(line.as_str())
-        })
-        .collect();
+    pub fn get_path(&self) -> &std::path::Path { &self.path }
+
+    /// index the entry lines of the file, so partial loads and batches seek to their first entry
+    ///     a gzip file is decompressed into dir first, which is required, and reused while the gzip file is unchanged
+    ///     the index is dropped with a new path but not when the file changes
+    pub fn build_index(&mut self, dir: Option<&std::path::Path>, stride: usize) -> std::io::Result<&crate::data::LineIndex> {
+        let plain = match (crate::data::is_seekable(&self.path), dir) {
+            (true, _) => self.path.to_owned(),
+            (false, None) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{:?} is decompressed into an index directory, none given", self.path))),
+            (false, Some(dir)) => {
+                let plain = dir.join(self.path.file_stem().unwrap_or_default());
+                let stamp_path = plain.with_extension("stamp");
+                let stamp = crate::app::chem::fingerprint_index::SourceStamp::new(&self.path)?.to_string();
+                if !plain.is_file() || std::fs::read_to_string(&stamp_path).ok().as_deref() != Some(stamp.as_str()) {
+                    // written under another name first, so a concurrent reader never sees a partial file
+                    let partial = plain.with_extension(crate::utils::generate_id(8));
+                    std::fs::create_dir_all(dir)?;
+                    let decompressed = std::fs::File::open(&self.path).and_then(|file| {
+                        let mut decoder = flate2::read::MultiGzDecoder::new(file);
+                        std::io::copy(&mut decoder, &mut std::fs::File::create(&partial)?)?;
+                        std::fs::rename(&partial, &plain)
+                    });
+                    if let Err(e) = decompressed {
+                        let _ = std::fs::remove_file(&partial);
+                        return Err(e);
+                    }
+                    std::fs::write(&stamp_path, stamp)?;
+                }
+                plain
+            }
+        };
+        let mut index = crate::data::LineIndex::new(stride);
+        let file = std::io::BufReader::new(std::fs::File::open(&plain)?);
+        for (n, result) in crate::data::source::stream::OffsetLines::new(file).enumerate() {
+            let (offset, line) = result?;
+            if EntryPubChem::parse(&line).is_some() {
+                index.push(offset, n + 1);
+            }
+        }
+        Ok(&self.index.insert((plain, index)).1)
     }
 
-    pub fn load(&mut self, count: usize) {
-        let pb = indicatif::ProgressBar::new(count as u64);
+    pub fn get_index(&self) -> Option<&crate::data::LineIndex> { self.index.as_ref().map(|(_, index)| index) }
+
+    /// entries of the range in order, malformed lines are logged and skipped, read errors are returned
+    fn entries_of(&self, range: &std::ops::Range<usize>) -> std::io::Result<impl Iterator<Item = std::io::Result<EntryPubChem>> + Send + 'static> {
+        let (entries, first) = self.entries_from(range.start)?;
+        let mut skip = range.start - first;
+        Ok(entries
+            .filter(move |entry| match entry {
+                Ok(_) if skip > 0 => {
+                    skip -= 1;
+                    false
+                },
+                _ => true
+            })
+            .take(range.len()))
+    }
+
+    /// entries from a mark of the index at or before the start, with the number of its first entry
+    fn entries_from(&self, start: usize) -> std::io::Result<(impl Iterator<Item = std::io::Result<EntryPubChem>> + Send + 'static, usize)> {
+        use std::io::{BufRead, Seek};
+        let (lines, first) = match self.index.as_ref() {
+            Some((plain, index)) => {
+                let (pos, first) = match index.seek(start) {
+                    Some((offset, _, skip)) => (std::io::SeekFrom::Start(offset), start - skip),
+                    None => (std::io::SeekFrom::End(0), index.len())
+                };
+                let mut file = std::fs::File::open(plain)?;
+                file.seek(pos)?;
+                let reader: Box<dyn BufRead + Send> = Box::new(std::io::BufReader::new(file));
+                (reader.lines(), first)
+            },
+            None => (crate::data::source::read_lines(&self.path)?, 0)
+        };
+        let entries = lines
+            .filter_map(|line| match line {
+                Ok(line) => {
+                    let entry = EntryPubChem::parse(&line);
+                    if entry.is_none() {
+                        crate::logging::error(format!("PubChem read line error: {}", line).as_str());
+                    }
+                    entry.map(Ok)
+                },
+                Err(e) => Some(Err(e))
+            });
+        Ok((entries, first))
+    }
+
+    pub fn load_all(&mut self) {
+        let pb = indicatif::ProgressBar::new(crate::kinds::Dataset::PubChem.size() as u64);
         pb.set_style(indicatif::ProgressStyle::default_bar()
             .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {human_pos}/{human_len} ({per_sec}, {eta})")
             .unwrap()
             .progress_chars("#>-"));
         pb.set_message("Loading PubChem smiles");
 
-        let file = std::fs::File::open(&self.path).unwrap();
-        let input = std::io::BufReader::new(file);
-        let mut bytes = flate2::bufread::GzDecoder::new(input).bytes();
-        let mut i: usize = 0;
-        let mut buf = Vec::<u8>::new();
-        while let Some(b_o) = bytes.next() {
-            let b = b_o.unwrap();
-            if b == b'\n' {
-                let line = std::str::from_utf8(&buf).unwrap();
-                let parts: Vec<&str> = line.split_ascii_whitespace().collect();
-                if parts.len() == 2 {
-                    self.data.push(EntryPubChem::from(parts[1]));
-                    i += 1;
-                    if i == count {
-                        break;
-                    }
-                    if i % 1000 == 0 {
-                        pb.set_position(i as u64);
-                    }
-                } else {
-                    crate::logging::error(format!("PubChem read line error: {}", line).as_str());
-                }
-                buf.clear();
-            } else {
-                if b != b'\r' {
-                    buf.push(b);
+        self.data.clear();
+        let loaded = self.entries_of(&(0..usize::MAX)).and_then(|entries| {
+            for entry in entries {
+                self.data.push(entry?);
+                if self.data.len().is_multiple_of(1000) {
+                    pb.set_position(self.data.len() as u64);
                 }
             }
+            Ok(())
+        });
+        pb.finish_and_clear();
+        if let Err(e) = loaded {
+            // no truncated dataset
+            self.data.clear();
+            crate::logging::error(format!("Error {} on file path: {:?}", e, self.path).as_str());
+        }
+    }
+
+    /// load the entries of the range only, e.g. a dividend, after a seek if the file is indexed
+    pub fn load_partial(&mut self, range: &std::ops::Range<usize>) {
+        self.data.clear();
+        match self.entries_of(range).and_then(|entries| entries.collect()) {
+            Ok(data) => self.data = data,
+            Err(e) => crate::logging::error(format!("Error {} on file path: {:?}", e, self.path).as_str())
         }
     }
+
+    /// entries of the range in batches of (CID, SMILES) pairs, without loading them, ending with a read error
+    pub fn batches_of(&self, range: &std::ops::Range<usize>, batch_size: usize) -> std::io::Result<impl Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send + 'static> {
+        let entries = self.entries_of(range)?.map(|entry| entry.map(|entry| (entry.cid, entry.smiles)));
+        Ok(crate::data::Batches::new(entries, batch_size))
+    }
+
+    pub fn get_all(&self) -> &DataPubChem { &self.data }
+    pub fn len(&self) -> usize { self.data.len() }
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    pub fn get_cid_smiles_pairs(&self) -> (Vec<CompoundID>, Vec<crate::app::chem::types::SMILES>) {
+        self.data.iter()
+            .map(|entry| (entry.cid.to_owned(), entry.smiles.to_owned()))
+            .unzip()
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// a small CID-SMILES.gz in a temporary directory
+    pub(crate) fn write_sample(dir: &std::path::Path) -> std::path::PathBuf {
+        use std::io::Write;
+        let path = dir.join(crate::kinds::Dataset::PubChem.filename().unwrap());
+        let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::default());
+        write!(encoder, "1\tCC(=O)OC(CC(=O)[O-])C[N+](C)(C)C\n2\tCC(=O)OC(CC(=O)O)C[N+](C)(C)C\ncorrupted line with spaces\n3\tC1=CC(C(C(=C1)C(=O)O)O)O\r\n4\tCC(=O)Nc1ccc(O)cc1\n").unwrap();
+        encoder.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(EntryPubChem::parse("2244\tCC(=O)OC1=CC=CC=C1C(=O)O"), Some(EntryPubChem { cid: "2244".to_string(), smiles: "CC(=O)OC1=CC=CC=C1C(=O)O".to_string() }));
+        assert_eq!(EntryPubChem::parse("2244"), None);
+        assert_eq!(EntryPubChem::parse("2244 C C"), None);
+    }
+
     #[test]
+    fn test_load_gz() {
+        let dir = crate::utils::tests::temp_dir();
+        let mut spc = SourcePubChem::new();
+        spc.set_path(write_sample(&dir).as_os_str());
+        spc.load_all();
+        assert_eq!(spc.len(), 4);
+        assert_eq!(spc.get_all()[2], EntryPubChem { cid: "3".to_string(), smiles: "C1=CC(C(C(=C1)C(=O)O)O)O".to_string() });
+        spc.load_partial(&(1..3));
+        let (cids, smiles) = spc.get_cid_smiles_pairs();
+        assert_eq!(cids, vec!["2".to_string(), "3".to_string()]);
+        assert_eq!(smiles[0], "CC(=O)OC(CC(=O)O)C[N+](C)(C)C");
+        spc.load_partial(&(3..10));
+        assert_eq!(spc.len(), 1);
+        let batches: Vec<crate::app::chem::types::IdSmilesPairs> = spc.batches_of(&(1..4), 2).unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(batches.iter().map(|(cids, _)| cids.len()).collect::<Vec<usize>>(), vec![2, 1]);
+        assert_eq!(batches[1].0, vec!["4".to_string()]);
+
+        // decompressed once, dividends across the marks of the index
+        assert_eq!(spc.build_index(None, 2).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        let index = spc.build_index(Some(&dir.join("plain")), 2).unwrap();
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.seek(3), Some((94, 4, 1)));
+        assert!(dir.join("plain").join("CID-SMILES").is_file());
+        let batches: Vec<crate::app::chem::types::IdSmilesPairs> = spc.batches_of(&(1..4), 2).unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(batches[0].0, vec!["2".to_string(), "3".to_string()]);
+        assert_eq!(batches[1], (vec!["4".to_string()], vec!["CC(=O)Nc1ccc(O)cc1".to_string()]));
+        spc.load_partial(&(3..10));
+        assert_eq!(spc.get_cid_smiles_pairs().0, vec!["4".to_string()]);
+        assert!(spc.batches_of(&(10..20), 2).unwrap().next().is_none());
+        // the plain file is read instead of the gzip file
+        std::fs::remove_file(spc.get_path()).unwrap();
+        assert_eq!(spc.batches_of(&(0..4), 10).unwrap().next().unwrap().unwrap().0.len(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_decompress() {
+        let dir = crate::utils::tests::temp_dir();
+        let plain_dir = dir.join("plain");
+        let mut spc = SourcePubChem::new();
+        spc.set_path(write_sample(&dir).as_os_str());
+        spc.build_index(Some(&plain_dir), 2).unwrap();
+        // the decompressed file is reused while the gzip file is unchanged
+        let mut plain = std::fs::OpenOptions::new().append(true).open(plain_dir.join("CID-SMILES")).unwrap();
+        std::io::Write::write_all(&mut plain, b"5\tCCO\n").unwrap();
+        assert_eq!(spc.build_index(Some(&plain_dir), 2).unwrap().len(), 5);
+        std::fs::write(spc.get_path(), std::fs::read(spc.get_path()).unwrap().repeat(2)).unwrap();
+        assert_eq!(spc.build_index(Some(&plain_dir), 2).unwrap().len(), 8);
+        // no partial file is left by a failed decompression
+        std::fs::write(spc.get_path(), b"not gzip").unwrap();
+        assert!(spc.build_index(Some(&plain_dir), 2).is_err());
+        let mut files: Vec<String> = std::fs::read_dir(&plain_dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+        files.sort();
+        assert_eq!(files, vec!["CID-SMILES".to_string(), "CID-SMILES.stamp".to_string()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_error() {
+        let dir = crate::utils::tests::temp_dir();
+        let path = dir.join("CID-SMILES");
+        std::fs::write(&path, b"1\tCCO\n2\tCCN\n\xff\xfe\n3\tCCC\n").unwrap();
+        let mut spc = SourcePubChem::new();
+        spc.set_path(path.as_os_str());
+        // the entries before the error are in a batch, then the error ends the batches
+        let batches: Vec<std::io::Result<crate::app::chem::types::IdSmilesPairs>> = spc.batches_of(&(0..4), 1).unwrap().collect();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[2].as_ref().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert!(spc.batches_of(&(0..2), 10).unwrap().all(|batch| batch.is_ok()));
+        // no truncated dataset is loaded
+        spc.load_all();
+        assert!(spc.is_empty());
+        spc.load_partial(&(1..3));
+        assert!(spc.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs CID-SMILES.gz from PubChem"]
     fn test_load() {
         let mut spc = SourcePubChem::new();
         let path = std::ffi::OsString::from("../../../../../.chiral/CID-SMILES.gz");
         spc.set_path(&path);
-        spc.load(1000 * 100);
+        spc.load_partial(&(0..1000 * 100));
         assert_eq!(100 * 100  * 10, spc.data.len());
     }
 }
-
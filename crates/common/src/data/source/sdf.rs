@@ -0,0 +1,396 @@
+//! SD files
+//!     records of a MOL V2000 or V3000 connection table followed by data fields, ending with $$$$
+//!
+//! Records are streamed from the file, gzip compressed or not. The entry ID is the title line or a data field,
+//! the SMILES a data field or written from the connection table, the other data fields are kept as properties.
+//!
+
+use serde::{Serialize, Deserialize};
+
+type Properties = std::collections::BTreeMap<String, String>;
+
+/// One record of the file, the connection table with its data fields in order
+#[derive(PartialEq, Debug, Default)]
+pub struct RecordSDF {
+    pub molblock: String,
+    pub fields: Vec<(String, String)>
+}
+
+impl RecordSDF {
+    /// the name of a data header line, e.g. 'ID' of '>  <ID>  (1)'
+    fn field_name(line: &str) -> Option<&str> {
+        let rest = line.strip_prefix('>')?;
+        let start = rest.find('<')?;
+        let end = rest[start..].find('>')?;
+        Some(&rest[start + 1..start + end])
+    }
+
+    fn parse(lines: Vec<String>) -> Self {
+        let mut record = Self::default();
+        let mut lines = lines.into_iter();
+        for line in lines.by_ref() {
+            let end = line.starts_with("M  END");
+            record.molblock.push_str(&line);
+            record.molblock.push('\n');
+            if end {
+                break;
+            }
+        }
+        let mut field: Option<(String, Vec<String>)> = None;
+        for line in lines {
+            match (Self::field_name(&line), field.as_mut()) {
+                (Some(name), _) => {
+                    record.fields.extend(field.take().map(|(name, values)| (name, values.join("\n"))));
+                    field = Some((name.to_string(), vec![]));
+                }
+                // a blank line ends the value
+                (None, Some((name, values))) if line.trim().is_empty() => {
+                    record.fields.push((name.to_owned(), values.join("\n")));
+                    field = None;
+                }
+                (None, Some((_, values))) => values.push(line),
+                (None, None) => {}
+            }
+        }
+        record.fields.extend(field.map(|(name, values)| (name, values.join("\n"))));
+        record
+    }
+
+    pub fn title(&self) -> &str {
+        self.molblock.lines().next().unwrap_or("").trim()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Records of the lines of a file, with their numbers starting from 1
+pub struct RecordsSDF<I: Iterator<Item = std::io::Result<String>>> {
+    lines: I,
+    count: usize
+}
+
+impl<I: Iterator<Item = std::io::Result<String>>> Iterator for RecordsSDF<I> {
+    type Item = std::io::Result<(usize, RecordSDF)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut lines = vec![];
+        for line in self.lines.by_ref() {
+            match line {
+                Ok(line) if line.starts_with("$$$$") => {
+                    self.count += 1;
+                    return Some(Ok((self.count, RecordSDF::parse(lines))));
+                }
+                Ok(line) => lines.push(line),
+                Err(e) => return Some(Err(e))
+            }
+        }
+        // the last record may miss its delimiter
+        if lines.iter().all(|line| line.trim().is_empty()) {
+            return None;
+        }
+        self.count += 1;
+        Some(Ok((self.count, RecordSDF::parse(lines))))
+    }
+}
+
+/// Where the entry ID of a record comes from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdSource {
+    /// the first line of the connection table
+    #[default]
+    Title,
+    Field(String)
+}
+
+#[derive(PartialEq, Debug)]
+pub struct EntrySDF {
+    pub id: crate::data::types::EntryID,
+    pub smiles: crate::app::chem::types::SMILES,
+    pub properties: Properties
+}
+
+type DataSDF = Vec<EntrySDF>;
+
+pub struct SourceSDF {
+    path: std::path::PathBuf,
+    id_source: IdSource,
+    smiles_field: Option<String>,
+    data: DataSDF
+}
+
+impl std::default::Default for SourceSDF {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SourceSDF {
+    pub fn new() -> Self {
+        Self { path: std::path::PathBuf::new(), id_source: IdSource::default(), smiles_field: None, data: DataSDF::new() }
+    }
+
+    pub fn set_path(&mut self, path_str: &std::ffi::OsStr) {
+        self.path = std::path::PathBuf::from(path_str);
+    }
+
+    pub fn with_id_source(mut self, id_source: IdSource) -> Self {
+        self.id_source = id_source;
+        self
+    }
+
+    /// take the SMILES from a data field instead of the connection table
+    pub fn with_smiles_field(mut self, name: &str) -> Self {
+        self.smiles_field = Some(name.to_string());
+        self
+    }
+
+    pub fn records(&self) -> std::io::Result<RecordsSDF<std::io::Lines<Box<dyn std::io::BufRead + Send>>>> {
+        Ok(RecordsSDF { lines: crate::data::source::read_lines(&self.path)?, count: 0 })
+    }
+
+    fn entry(&self, record: RecordSDF) -> Result<EntrySDF, String> {
+        let id = match &self.id_source {
+            IdSource::Title => record.title(),
+            IdSource::Field(name) => record.field(name).unwrap_or("")
+        };
+        if id.trim().is_empty() {
+            return Err("no entry ID".to_string());
+        }
+        let id = id.trim().to_string();
+        let smiles = match &self.smiles_field {
+            Some(name) => record.field(name).map(|smiles| smiles.trim().to_string()).filter(|smiles| !smiles.is_empty()).ok_or_else(|| format!("no field {}", name))?,
+            None => crate::data::source::molfile::Molfile::parse(&record.molblock).map_err(|e| e.to_string())?.to_smiles()
+        };
+        let properties = record.fields.into_iter()
+            .filter(|(name, _)| !matches!(&self.id_source, IdSource::Field(id_name) if id_name == name))
+            .collect();
+        Ok(EntrySDF { id, smiles, properties })
+    }
+
+    /// entries of the file in order, malformed records are logged and skipped, read errors are returned
+    pub fn entries(&self) -> std::io::Result<impl Iterator<Item = std::io::Result<EntrySDF>> + '_> {
+        Ok(self.records()?
+            .filter_map(|r| match r {
+                Ok((n, record)) => self.entry(record)
+                    .map_err(|e| crate::logging::error(format!("SDF read record {} error: {}", n, e).as_str()))
+                    .ok()
+                    .map(Ok),
+                Err(e) => Some(Err(e))
+            }))
+    }
+
+    /// batches of the (entry ID, SMILES) pairs of the file, without the properties, ending with a read error
+    pub fn batches(&self, size: usize) -> std::io::Result<impl Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + '_> {
+        Ok(crate::data::Batches::new(self.entries()?.map(|entry| entry.map(|entry| (entry.id, entry.smiles))), size))
+    }
+
+    /// load all entries, none if the file cannot be read to its end
+    pub fn load_all(&mut self) {
+        let data = match self.entries().and_then(|entries| entries.collect()) {
+            Ok(data) => data,
+            Err(e) => {
+                crate::logging::error(format!("Error {} on file path: {:?}", e, self.path).as_str());
+                DataSDF::new()
+            }
+        };
+        self.data = data;
+    }
+
+    /// load the entries of the range only, e.g. a dividend, none if the records before its end cannot be read
+    pub fn load_partial(&mut self, range: &std::ops::Range<usize>) {
+        let data = self.entries().and_then(|entries| {
+            let mut data = DataSDF::new();
+            for (i, entry) in entries.take(range.end).enumerate() {
+                let entry = entry?;
+                if i >= range.start {
+                    data.push(entry);
+                }
+            }
+            Ok(data)
+        });
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                crate::logging::error(format!("Error {} on file path: {:?}", e, self.path).as_str());
+                DataSDF::new()
+            }
+        };
+        self.data = data;
+    }
+
+    pub fn get_all(&self) -> &DataSDF { &self.data }
+    pub fn len(&self) -> usize { self.data.len() }
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    pub fn get_id_smiles_pairs(&self) -> (Vec<crate::data::types::EntryID>, Vec<crate::app::chem::types::SMILES>) {
+        self.data.iter()
+            .map(|entry| (entry.id.to_owned(), entry.smiles.to_owned()))
+            .unzip()
+    }
+}
+
+impl From<&crate::data::CustomDataset> for SourceSDF {
+    fn from(dataset: &crate::data::CustomDataset) -> Self {
+        let mut ss = Self::new().with_id_source(dataset.id_source.to_owned());
+        ss.smiles_field = dataset.smiles_field.to_owned();
+        ss.set_path(dataset.path.as_os_str());
+        ss
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+ethanol
+  test
+
+  3  2  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    1.2000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    2.4000    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  2  3  1  0
+M  END
+>  <CATALOG_ID>  (1)
+V-001
+
+> <SMILES>
+CCO
+
+> <NOTE>
+first line
+second line
+
+$$$$
+broken
+  test
+
+  2  1  0  0  0  0  0  0  0  0999 V2000
+M  END
+> <CATALOG_ID>
+V-002
+
+$$$$
+acetate
+  test
+
+  0  0  0     0  0            999 V3000
+M  V30 BEGIN CTAB
+M  V30 COUNTS 4 3 0 0 0
+M  V30 BEGIN ATOM
+M  V30 1 C 0 0 0 0
+M  V30 2 C 0 0 0 0
+M  V30 3 O 0 0 0 0
+M  V30 4 O 0 0 0 0 CHG=-1
+M  V30 END ATOM
+M  V30 BEGIN BOND
+M  V30 1 1 1 2
+M  V30 2 2 2 3
+M  V30 3 1 2 4
+M  V30 END BOND
+M  V30 END CTAB
+M  END
+> <CATALOG_ID>
+V-003
+";
+
+    fn write_sample(dir: &std::path::Path, gzip: bool) -> std::path::PathBuf {
+        use std::io::Write;
+        match gzip {
+            true => {
+                let path = dir.join("catalogue.sdf.gz");
+                let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::default());
+                encoder.write_all(SAMPLE.as_bytes()).unwrap();
+                encoder.finish().unwrap();
+                path
+            },
+            false => {
+                let path = dir.join("catalogue.sdf");
+                std::fs::write(&path, SAMPLE).unwrap();
+                path
+            }
+        }
+    }
+
+    #[test]
+    fn test_records() {
+        let dir = crate::utils::tests::temp_dir();
+        let mut ss = SourceSDF::new();
+        ss.set_path(write_sample(&dir, false).as_os_str());
+        let records: Vec<(usize, RecordSDF)> = ss.records().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 3);
+        let (n, record) = &records[0];
+        assert_eq!(*n, 1);
+        assert_eq!(record.title(), "ethanol");
+        assert_eq!(record.fields, vec![
+            ("CATALOG_ID".to_string(), "V-001".to_string()),
+            ("SMILES".to_string(), "CCO".to_string()),
+            ("NOTE".to_string(), "first line\nsecond line".to_string())
+        ]);
+        // the last record without delimiter nor blank line
+        assert_eq!(records[2].1.field("CATALOG_ID"), Some("V-003"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = crate::utils::tests::temp_dir();
+        let mut ss = SourceSDF::new();
+        ss.set_path(write_sample(&dir, true).as_os_str());
+        ss.load_all();
+        // the broken connection table is skipped
+        assert_eq!(ss.len(), 2);
+        assert_eq!(ss.get_all()[0].id, "ethanol");
+        assert_eq!(ss.get_all()[0].smiles, "CCO");
+        assert_eq!(ss.get_all()[0].properties.get("CATALOG_ID").unwrap(), "V-001");
+        assert_eq!(ss.get_id_smiles_pairs(), (vec!["ethanol".to_string(), "acetate".to_string()], vec!["CCO".to_string(), "CC(=O)[O-]".to_string()]));
+
+        let mut ss = SourceSDF::new().with_id_source(IdSource::Field("CATALOG_ID".to_string())).with_smiles_field("SMILES");
+        ss.set_path(write_sample(&dir, false).as_os_str());
+        ss.load_all();
+        // only the first record has a SMILES field
+        assert_eq!(ss.len(), 1);
+        assert_eq!(ss.get_all()[0].id, "V-001");
+        assert!(!ss.get_all()[0].properties.contains_key("CATALOG_ID"));
+        assert_eq!(ss.get_all()[0].properties.get("NOTE").unwrap(), "first line\nsecond line");
+
+        let mut ss = SourceSDF::new().with_id_source(IdSource::Field("CATALOG_ID".to_string()));
+        ss.set_path(write_sample(&dir, false).as_os_str());
+        ss.load_partial(&(1..2));
+        assert_eq!(ss.get_id_smiles_pairs(), (vec!["V-003".to_string()], vec!["CC(=O)[O-]".to_string()]));
+
+        let doc = crate::data::DocSMILES::from(ss);
+        assert_eq!(doc.get_smiles(&"V-003".to_string()).unwrap(), "CC(=O)[O-]");
+
+        // a registered SD file
+        let dataset = crate::data::CustomDataset::new_sdf("catalogue", write_sample(&dir, false)).with_id_source(IdSource::Field("CATALOG_ID".to_string()));
+        let batches: Vec<crate::app::chem::types::IdSmilesPairs> = SourceSDF::from(&dataset).batches(1).unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(batches, vec![(vec!["V-001".to_string()], vec!["CCO".to_string()]), (vec!["V-003".to_string()], vec!["CC(=O)[O-]".to_string()])]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_error() {
+        let dir = crate::utils::tests::temp_dir();
+        let path = write_sample(&dir, false);
+        let mut content = std::fs::read(&path).unwrap();
+        content.extend_from_slice(b"\n$$$$\n\xff\xfe\n");
+        std::fs::write(&path, content).unwrap();
+        let mut ss = SourceSDF::new().with_id_source(IdSource::Field("CATALOG_ID".to_string()));
+        ss.set_path(path.as_os_str());
+        let entries: Vec<std::io::Result<EntrySDF>> = ss.entries().unwrap().collect();
+        assert_eq!(entries.last().unwrap().as_ref().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert!(ss.batches(10).unwrap().any(|batch| batch.is_err()));
+        // no truncated dataset is loaded, the entries before the error are
+        ss.load_all();
+        assert!(ss.is_empty());
+        ss.load_partial(&(0..2));
+        assert_eq!(ss.len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+//! Streaming
+//!     batches of entry IDs and SMILES read from a source file, and a byte-offset index of its entry lines
+//!
+//! Batches bound the memory of loading to what the consumer keeps, e.g. a DocSMILES or the fingerprints of an
+//! operator's data. The line index records the byte offset and the line number of every stride-th entry, so the
+//! entries of a dividend are read after a seek instead of from the start of the file. Gzip files cannot seek
+//! and are not indexed.
+//!
+
+use serde::{Serialize, Deserialize};
+
+/// entries of a batch
+pub const BATCH_SIZE: usize = 10000;
+/// entries between two marks of a line index
+pub const INDEX_STRIDE: usize = 1024;
+
+/// Batches of the (entry ID, SMILES) pairs of an iterator, ending with the first read error
+pub struct Batches<I: Iterator<Item = std::io::Result<(crate::data::types::EntryID, crate::app::chem::types::SMILES)>>> {
+    entries: I,
+    size: usize,
+    failed: bool
+}
+
+impl<I: Iterator<Item = std::io::Result<(crate::data::types::EntryID, crate::app::chem::types::SMILES)>>> Batches<I> {
+    pub fn new(entries: I, size: usize) -> Self {
+        Self { entries, size: size.max(1), failed: false }
+    }
+}
+
+impl<I: Iterator<Item = std::io::Result<(crate::data::types::EntryID, crate::app::chem::types::SMILES)>>> Iterator for Batches<I> {
+    type Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut batch: crate::app::chem::types::IdSmilesPairs = (vec![], vec![]);
+        for entry in self.entries.by_ref().take(self.size) {
+            match entry {
+                Ok((id, smiles)) => {
+                    batch.0.push(id);
+                    batch.1.push(smiles);
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        (!batch.0.is_empty()).then_some(Ok(batch))
+    }
+}
+
+/// Lines of a reader with the byte offsets of their starts, line endings removed
+pub struct OffsetLines<R: std::io::BufRead> {
+    reader: R,
+    offset: u64
+}
+
+impl<R: std::io::BufRead> OffsetLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, offset: 0 }
+    }
+}
+
+impl<R: std::io::BufRead> Iterator for OffsetLines<R> {
+    type Item = std::io::Result<(u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = vec![];
+        match self.reader.read_until(b'\n', &mut buf) {
+            Ok(0) => None,
+            Ok(n) => {
+                let offset = self.offset;
+                self.offset += n as u64;
+                for end in [b'\n', b'\r'] {
+                    if buf.last() == Some(&end) {
+                        buf.pop();
+                    }
+                }
+                Some(String::from_utf8(buf)
+                    .map(|line| (offset, line))
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+            },
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+/// Byte offsets and line numbers of every stride-th entry of a file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LineIndex {
+    stride: usize,
+    len: usize,
+    marks: Vec<(u64, usize)>
+}
+
+impl LineIndex {
+    pub fn new(stride: usize) -> Self {
+        Self { stride: stride.max(1), len: 0, marks: vec![] }
+    }
+
+    /// add the next entry, at the byte offset of its line and the line number starting from 1
+    pub fn push(&mut self, offset: u64, line: usize) {
+        if self.len.is_multiple_of(self.stride) {
+            self.marks.push((offset, line));
+        }
+        self.len += 1;
+    }
+
+    /// byte offset and line number of the closest mark up to the entry, with the entries to skip from there
+    pub fn seek(&self, entry: usize) -> Option<(u64, usize, usize)> {
+        if entry >= self.len {
+            return None;
+        }
+        let (offset, line) = self.marks[entry / self.stride];
+        Some((offset, line, entry % self.stride))
+    }
+
+    /// entries of the file
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+/// whether the file can be indexed, gzip files cannot seek
+pub fn is_seekable(path: &std::path::Path) -> bool {
+    path.extension().is_none_or(|ext| ext != "gz")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches() {
+        let entries = (0..5).map(|i| Ok((i.to_string(), "C".repeat(i + 1))));
+        let batches: Vec<crate::app::chem::types::IdSmilesPairs> = Batches::new(entries, 2).collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[1], (vec!["2".to_string(), "3".to_string()], vec!["CCC".to_string(), "CCCC".to_string()]));
+        assert_eq!(batches[2].0, vec!["4".to_string()]);
+
+        // a read error ends the batches
+        let entries = (0..5).map(|i| match i {
+            3 => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "not UTF-8")),
+            _ => Ok((i.to_string(), "C".to_string()))
+        });
+        let batches: Vec<std::io::Result<crate::app::chem::types::IdSmilesPairs>> = Batches::new(entries, 2).collect();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].as_ref().unwrap().0.len(), 2);
+        assert_eq!(batches[1].as_ref().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_line_index() {
+        let lines: Vec<(u64, String)> = OffsetLines::new("a\r\n\nbc\nd".as_bytes()).map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec![(0, "a".to_string()), (3, "".to_string()), (4, "bc".to_string()), (7, "d".to_string())]);
+
+        let mut index = LineIndex::new(2);
+        for (n, (offset, _)) in lines.iter().enumerate().filter(|(_, (_, line))| !line.is_empty()) {
+            index.push(*offset, n + 1);
+        }
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.seek(0), Some((0, 1, 0)));
+        assert_eq!(index.seek(1), Some((0, 1, 1)));
+        assert_eq!(index.seek(2), Some((7, 4, 0)));
+        assert_eq!(index.seek(3), None);
+        assert!(is_seekable(std::path::Path::new("library.csv")));
+        assert!(!is_seekable(std::path::Path::new("CID-SMILES.gz")));
+    }
+}
@@ -0,0 +1,182 @@
+//! Data store combining documents loaded in memory with datasets streamed from their files
+//!
+//! The dividends of a streamed dataset are read from its file when requested, so a dataset like PubChem
+//! is never held in memory as a whole. The files are indexed by line first, so the dividends seek to
+//! their entries instead of reading the file from its start.
+//!
+
+pub struct DataStore {
+    docs: crate::data::DocStoreSMILES,
+    /// PubChem files with their line index
+    files: std::collections::HashMap<crate::kinds::Dataset, crate::data::SourcePubChem>,
+    /// source files of the loaded documents
+    sources: std::collections::HashMap<crate::kinds::Dataset, std::path::PathBuf>,
+    /// delimited files with their line index
+    indexed: std::collections::HashMap<crate::kinds::Dataset, crate::data::SourceDelimited>,
+    index_dir: Option<std::path::PathBuf>
+}
+
+impl std::default::Default for DataStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataStore {
+    pub fn new() -> Self {
+        Self { docs: crate::data::DocStoreSMILES::new(), files: std::collections::HashMap::new(), sources: std::collections::HashMap::new(), indexed: std::collections::HashMap::new(), index_dir: None }
+    }
+
+    /// directory of the persistent fingerprint indices of the dividends
+    pub fn with_index_dir(mut self, dir: std::path::PathBuf) -> Self {
+        self.index_dir = Some(dir);
+        self
+    }
+
+    pub fn insert_doc(&mut self, dsk: crate::kinds::Dataset, doc: crate::data::DocSMILES) {
+        self.docs.insert(dsk, doc);
+    }
+
+    /// a document loaded from the file, whose changes invalidate the persistent indices
+    pub fn insert_doc_from(&mut self, dsk: crate::kinds::Dataset, doc: crate::data::DocSMILES, path: std::path::PathBuf) {
+        self.sources.insert(dsk.to_owned(), path);
+        self.insert_doc(dsk, doc);
+    }
+
+    /// stream the dividends of the dataset from the file after indexing its lines, only PubChem is supported
+    ///     a gzip file is decompressed into the index directory, which it requires
+    pub fn insert_file(&mut self, dsk: crate::kinds::Dataset, path: std::path::PathBuf) -> std::io::Result<bool> {
+        match dsk {
+            crate::kinds::Dataset::PubChem => {
+                let mut spc = crate::data::SourcePubChem::new();
+                spc.set_path(path.as_os_str());
+                spc.build_index(self.index_dir.as_deref(), crate::data::INDEX_STRIDE)?;
+                self.files.insert(dsk, spc);
+                Ok(true)
+            }
+            _ => Ok(false)
+        }
+    }
+
+    /// stream the dividends of the dataset from the delimited file after indexing its lines, returns the entries
+    pub fn insert_indexed(&mut self, dsk: crate::kinds::Dataset, mut source: crate::data::SourceDelimited) -> std::io::Result<usize> {
+        let size = source.build_index(crate::data::INDEX_STRIDE)?.len();
+        self.indexed.insert(dsk, source);
+        Ok(size)
+    }
+
+    pub fn contains(&self, dsk: &crate::kinds::Dataset) -> bool {
+        self.docs.contains_key(dsk) || self.files.contains_key(dsk) || self.indexed.contains_key(dsk)
+    }
+}
+
+impl crate::traits::TraitDataStore for DataStore {
+    fn get_id_smiles_pairs(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex) -> Option<crate::app::chem::types::IdSmilesPairs> {
+        if self.docs.contains_key(dsk) {
+            return self.docs.get_id_smiles_pairs(dsk, div_index);
+        }
+        let batches = self.get_id_smiles_batches(dsk, div_index, crate::data::BATCH_SIZE)?;
+        let batches: std::io::Result<Vec<crate::app::chem::types::IdSmilesPairs>> = batches.collect();
+        batches.map(|batches| batches.into_iter().flat_map(|(ids, smiles)| ids.into_iter().zip(smiles)).unzip())
+            .map_err(|e| crate::logging::error(format!("Error {} on dataset: {}", e, dsk).as_str()))
+            .ok()
+    }
+
+    fn get_id_smiles_batches(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex, batch_size: usize) -> Option<Box<dyn Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send>> {
+        if self.docs.contains_key(dsk) {
+            return self.docs.get_id_smiles_batches(dsk, div_index, batch_size);
+        }
+        let range = div_index.0..div_index.1;
+        let batches = match (self.files.get(dsk), self.indexed.get(dsk)) {
+            (Some(spc), _) => spc.batches_of(&range, batch_size).map(|batches| Box::new(batches) as Box<dyn Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send>),
+            (None, Some(sd)) => sd.batches_of(&range, batch_size).map(|batches| Box::new(batches) as Box<dyn Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send>),
+            (None, None) => return None
+        };
+        // a file which cannot be read has no entries, the dividend fails in the data preparation
+        batches.map_err(|e| crate::logging::error(format!("Error {} on dataset: {}", e, dsk).as_str())).ok()
+    }
+
+    fn get_size(&self, dsk: &crate::kinds::Dataset) -> Option<usize> {
+        self.docs.get_size(dsk)
+            .or_else(|| self.indexed.get(dsk).and_then(crate::data::SourceDelimited::get_index).map(crate::data::LineIndex::len))
+            .or_else(|| self.files.get(dsk).and_then(crate::data::SourcePubChem::get_index).map(crate::data::LineIndex::len))
+    }
+
+    fn get_source_path(&self, dsk: &crate::kinds::Dataset) -> Option<std::path::PathBuf> {
+        self.files.get(dsk).map(|spc| spc.get_path().to_path_buf())
+            .or_else(|| self.sources.get(dsk).cloned())
+            .or_else(|| self.indexed.get(dsk).map(|sd| sd.get_path().to_path_buf()))
+    }
+
+    fn get_index_dir(&self) -> Option<std::path::PathBuf> {
+        self.index_dir.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::TraitDataStore;
+    use crate::data::Dummy;
+
+    #[test]
+    fn test_data_store() {
+        let dir = crate::utils::tests::temp_dir();
+        let path = crate::data::source::pubchem::tests::write_sample(&dir);
+        let mut store = DataStore::new();
+        assert_eq!(store.insert_file(crate::kinds::Dataset::PubChem, path.to_owned()).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        let mut store = store.with_index_dir(dir.join("index"));
+        store.insert_doc(crate::kinds::Dataset::Dummy, crate::data::DocSMILES::dummy());
+        assert_eq!(store.insert_file(crate::kinds::Dataset::PubChem, dir.join("missing.gz")).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert!(!store.contains(&crate::kinds::Dataset::PubChem));
+        assert!(store.insert_file(crate::kinds::Dataset::PubChem, path.to_owned()).unwrap());
+        assert!(!store.insert_file(crate::kinds::Dataset::TestChembl, path.to_owned()).unwrap());
+        assert!(store.contains(&crate::kinds::Dataset::PubChem));
+        assert!(!store.contains(&crate::kinds::Dataset::TestChembl));
+
+        assert_eq!(store.get_id_smiles_pairs(&crate::kinds::Dataset::Dummy, &(0, 4)).unwrap().0.len(), 4);
+        let (cids, smiles) = store.get_id_smiles_pairs(&crate::kinds::Dataset::PubChem, &(2, 4)).unwrap();
+        assert_eq!(cids, vec!["3".to_string(), "4".to_string()]);
+        assert_eq!(smiles[1], "CC(=O)Nc1ccc(O)cc1");
+        // dividends past the end of the file are empty
+        assert!(store.get_id_smiles_pairs(&crate::kinds::Dataset::PubChem, &(100, 200)).unwrap().0.is_empty());
+        assert!(store.get_id_smiles_pairs(&crate::kinds::Dataset::TestChembl, &(0, 1)).is_none());
+        assert_eq!(store.get_source_path(&crate::kinds::Dataset::PubChem), Some(path.to_owned()));
+        assert_eq!(store.get_size(&crate::kinds::Dataset::Dummy), Some(4));
+        assert_eq!(store.get_size(&crate::kinds::Dataset::PubChem), Some(4));
+        // the gzip file is decompressed once, the dividends are read from the plain file
+        assert!(dir.join("index").join("CID-SMILES").is_file());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(store.get_id_smiles_pairs(&crate::kinds::Dataset::PubChem, &(3, 4)).unwrap().0, vec!["4".to_string()]);
+        crate::data::source::pubchem::tests::write_sample(&dir);
+
+        let dsk = crate::kinds::Dataset::Custom("my_library".to_string());
+        store.insert_doc_from(dsk.to_owned(), crate::data::DocSMILES::new(vec!["m1".to_string()], vec!["CCO".to_string()]), path.to_owned());
+        assert_eq!(store.get_size(&dsk), Some(1));
+        assert_eq!(store.get_source_path(&dsk), Some(path));
+
+        // delimited file indexed by line, dividends across the marks of the index
+        let path = dir.join("library.smi");
+        let content: String = (0..3000).map(|i| format!("{} m{}\n", "C".repeat(i % 7 + 1), i)).collect();
+        std::fs::write(&path, format!("smiles id\n{}", content)).unwrap();
+        let mut sd = crate::data::SourceDelimited::new(crate::data::Format::Smi, crate::data::Column::from(1), crate::data::Column::from(0));
+        sd.set_path(path.as_os_str());
+        let dsk = crate::kinds::Dataset::Custom("indexed".to_string());
+        assert_eq!(store.insert_indexed(dsk.to_owned(), sd).unwrap(), 3000);
+        assert!(store.contains(&dsk));
+        assert_eq!(store.get_size(&dsk), Some(3000));
+        assert_eq!(store.get_source_path(&dsk), Some(path));
+        let (ids, smiles) = store.get_id_smiles_pairs(&dsk, &(1020, 2050)).unwrap();
+        assert_eq!(ids.len(), 1030);
+        assert_eq!((ids[0].as_str(), smiles[0].as_str()), ("m1020", "CCCCCC"));
+        assert_eq!(ids[1029], "m2049");
+        let batches: Vec<crate::app::chem::types::IdSmilesPairs> = store.get_id_smiles_batches(&dsk, &(2990, 4000), 4).unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(batches.iter().map(|(ids, _)| ids.len()).collect::<Vec<usize>>(), vec![4, 4, 2]);
+        assert!(store.get_id_smiles_pairs(&dsk, &(3000, 4000)).unwrap().0.is_empty());
+
+        let mut sd = crate::data::SourceDelimited::new(crate::data::Format::Smi, crate::data::Column::from(1), crate::data::Column::from(0));
+        sd.set_path(crate::data::source::pubchem::tests::write_sample(&dir).as_os_str());
+        assert_eq!(store.insert_indexed(dsk, sd).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
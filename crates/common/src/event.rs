@@ -0,0 +1,149 @@
+//! Progress events of jobs
+//!
+//! Events are derived from the changes between two states of a job, so any component storing jobs can
+//! publish them: status transitions, completed dividends with the running count of hits, partial outputs of
+//! stopped dividends and the availability of the report.
+//!
+
+use serde::{Serialize, Deserialize};
+use crate::traits::{Serialization, SerializedFormat};
+use chiral_derive::Serialization;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    Status { status: crate::job::Status },
+    DividendDone {
+        div_index: crate::job::DividendIndex,
+        output_len: usize,
+        /// dividends done so far, out of planned
+        done: usize,
+        planned: usize,
+        /// results of the job so far
+        hits: usize
+    },
+    /// output of a dividend stopped by cancellation or deadline
+    DividendPartial {
+        div_index: crate::job::DividendIndex,
+        output_len: usize,
+        hits: usize
+    },
+    ReportReady
+}
+
+impl EventKind {
+    /// name of the event, e.g. for the event field of server-sent events
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Status { .. } => "status",
+            Self::DividendDone { .. } => "dividend_done",
+            Self::DividendPartial { .. } => "dividend_partial",
+            Self::ReportReady => "report_ready"
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq)]
+pub struct Event {
+    /// increasing number of the events of a stream
+    pub seq: u64,
+    pub job_id: crate::job::ID,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub kind: EventKind
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "job {}: ", self.job_id)?;
+        match &self.kind {
+            EventKind::Status { status } => write!(f, "{}", status),
+            EventKind::DividendDone { div_index, done, planned, hits, .. } => write!(f, "dividend {:?} done, {}/{} dividends, {} hits", div_index, done, planned, hits),
+            EventKind::DividendPartial { div_index, hits, .. } => write!(f, "dividend {:?} stopped, {} hits", div_index, hits),
+            EventKind::ReportReady => write!(f, "report ready")
+        }
+    }
+}
+
+/// Events from the previous state of a job to the current one, the whole history without previous state
+pub fn changes(previous: Option<&crate::job::Job>, job: &crate::job::Job) -> Vec<EventKind> {
+    let history_before = previous.map_or(0, |p| p.get_history().len());
+    let mut statuses: Vec<crate::job::Status> = job.get_history().iter()
+        .skip(history_before)
+        .map(|change| change.status.to_owned())
+        .collect();
+    // jobs stored without history
+    if statuses.is_empty() && previous.is_none_or(|p| p.get_status() != job.get_status()) {
+        statuses.push(job.get_status().to_owned());
+    }
+    // a final status comes after the dividends leading to it
+    let (statuses_final, statuses): (Vec<crate::job::Status>, Vec<crate::job::Status>) = statuses.into_iter().partition(|status| status.is_final());
+    let mut events: Vec<EventKind> = statuses.into_iter().map(|status| EventKind::Status { status }).collect();
+
+    let progress = job.get_progress();
+    let (done_before, partial_before) = previous.map_or((0, 0), |p| (p.get_progress().get_done().len(), p.get_progress().get_partial().len()));
+    let planned = progress.get_planned().len();
+    let mut hits: usize = progress.get_done()[..done_before.min(progress.get_done().len())].iter()
+        .chain(progress.get_partial()[..partial_before.min(progress.get_partial().len())].iter())
+        .map(|record| record.output_len)
+        .sum();
+    for (n, record) in progress.get_done().iter().enumerate().skip(done_before) {
+        hits += record.output_len;
+        events.push(EventKind::DividendDone { div_index: record.div_index, output_len: record.output_len, done: n + 1, planned, hits });
+    }
+    for record in progress.get_partial().iter().skip(partial_before) {
+        hits += record.output_len;
+        events.push(EventKind::DividendPartial { div_index: record.div_index, output_len: record.output_len, hits });
+    }
+    events.extend(statuses_final.into_iter().map(|status| EventKind::Status { status }));
+
+    if job.is_report_ready() && !previous.is_some_and(|p| p.is_report_ready()) {
+        events.push(EventKind::ReportReady);
+    }
+    events
+}
+
+/// whether a job will not change anymore: final, with its report saved unless failed
+pub fn is_settled(job: &crate::job::Job) -> bool {
+    job.get_status().is_final() && (job.is_report_ready() || job.is_failed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let mut job = crate::job::Job::new(crate::job::Requirement::default());
+        job.plan(vec![(0, 5), (5, 10)]);
+        assert_eq!(changes(None, &job), vec![EventKind::Status { status: crate::job::Status::Created }]);
+        let created = job.to_owned();
+        assert!(changes(Some(&created), &job).is_empty());
+
+        job.start().unwrap();
+        job.dividend_done(&(0, 5), 3, std::time::Duration::from_secs(1));
+        let processing = job.to_owned();
+        assert_eq!(changes(Some(&created), &job), vec![
+            EventKind::Status { status: crate::job::Status::Processing },
+            EventKind::DividendDone { div_index: (0, 5), output_len: 3, done: 1, planned: 2, hits: 3 }
+        ]);
+
+        job.dividend_partial(&(5, 10), 2, std::time::Duration::from_secs(1));
+        job.cancel().unwrap();
+        job.report_done();
+        let events = changes(Some(&processing), &job);
+        assert_eq!(events, vec![
+            EventKind::DividendPartial { div_index: (5, 10), output_len: 2, hits: 5 },
+            EventKind::Status { status: crate::job::Status::Cancelled },
+            EventKind::ReportReady
+        ]);
+        assert!(is_settled(&job));
+        assert!(!is_settled(&processing));
+        assert_eq!(changes(None, &job).len(), 6);
+
+        let event = Event { seq: 1, job_id: job.get_id().to_owned(), time: chrono::Utc::now(), kind: events[0].to_owned() };
+        assert_eq!(Event::ser_from(&event.ser_to()).kind, event.kind);
+        assert!(event.ser_to().contains(r#""type":"dividend_partial""#));
+        assert_eq!(event.to_string(), format!("job {}: dividend (5, 10) stopped, 5 hits", job.get_id()));
+    }
+}
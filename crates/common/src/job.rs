@@ -2,9 +2,10 @@
 //! 
 
 use serde::{Serialize, Deserialize};
-use crate::traits::{Serialization, SerializedFormat};
+use crate::traits::{Serialization, SerializedFormat, TraitReport};
 use chiral_derive::Serialization;
 use chrono::serde::ts_milliseconds;
+use strum_macros::{EnumString, Display};
 
 pub type ID = String;
 pub type DividendSize = usize;
@@ -14,21 +15,62 @@ fn generate_id() -> ID {
     crate::utils::generate_id(32)
 }
 
+/// Priority of a job in the scheduler queue
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, EnumString, Display)]  
+#[strum(serialize_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High
+}
+
+impl Priority {
+    pub fn level(&self) -> u64 {
+        *self as u64
+    }
+}
+
 #[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq, Eq, Hash)]  
 pub struct Requirement {
     ji: SerializedFormat, 
     opk: crate::kinds::Operator,
     dsk: crate::kinds::Dataset,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    retry: RetryPolicy,
+    /// wall-clock time after which the job stops with the output gathered so far
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    deadline: Option<chrono::DateTime<chrono::Utc>>
 }
 
 impl Requirement {
     pub fn new(ji: SerializedFormat, opk: crate::kinds::Operator, dsk: crate::kinds::Dataset) -> Self {
-        Self { ji, opk, dsk }
+        Self { ji, opk, dsk, priority: Priority::default(), retry: RetryPolicy::default(), deadline: None }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: chrono::DateTime<chrono::Utc>) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
     }
 
     pub fn get_ji(&self) -> &SerializedFormat { &self.ji }
     pub fn get_opk(&self) -> &crate::kinds::Operator { &self.opk }
     pub fn get_dsk(&self) -> &crate::kinds::Dataset { &self.dsk }
+    pub fn get_priority(&self) -> &Priority { &self.priority }
+    pub fn get_retry(&self) -> &RetryPolicy { &self.retry }
+    pub fn get_deadline(&self) -> &Option<chrono::DateTime<chrono::Utc>> { &self.deadline }
     pub fn generate_cuk(&self) -> crate::kinds::ComputingUnit { crate::kinds::ComputingUnit::new(self.opk.to_owned(), self.dsk.to_owned()) }
 }
 
@@ -38,17 +80,217 @@ impl std::default::Default for Requirement {
         Self {
             ji: "c1cccc1N=O".to_string(),
             opk: crate::kinds::Operator::OpenBabelSSMatching,
-            dsk: crate::kinds::Dataset::TestChembl
+            dsk: crate::kinds::Dataset::TestChembl,
+            priority: Priority::default(),
+            retry: RetryPolicy::default(),
+            deadline: None
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]  
+pub enum ErrorKind {
+    InvalidInput,
+    DataPreparation,
+    Computation,
+    Interrupted
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidInput => write!(f, "invalid input"),
+            Self::DataPreparation => write!(f, "data preparation"),
+            Self::Computation => write!(f, "computation"),
+            Self::Interrupted => write!(f, "interrupted")
+        }
+    }
+}
+
+/// Error of a failed job, with the dividend where it happened
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]  
+pub struct JobError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub div_index: Option<DividendIndex>
+}
+
+impl JobError {
+    pub fn new(kind: ErrorKind, message: &str) -> Self {
+        Self { kind, message: message.to_string(), div_index: None }
+    }
+
+    pub fn at(mut self, div_index: &DividendIndex) -> Self {
+        self.div_index = Some(div_index.to_owned());
+        self
+    }
+
+    /// Error from the payload of an operator panic caught with std::panic::catch_unwind
+    pub fn from_panic(payload: Box<dyn std::any::Any + Send>, div_index: &DividendIndex) -> Self {
+        let message = payload.downcast_ref::<&str>().map(|m| m.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "operator panicked".to_string());
+        Self::new(ErrorKind::Computation, &message).at(div_index)
+    }
+}
+
+impl std::error::Error for JobError {}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.div_index {
+            Some((start, end)) => write!(f, "{} error at dividend {}..{}: {}", self.kind, start, end, self.message),
+            None => write!(f, "{} error: {}", self.kind, self.message)
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq, Eq, Copy)]  
+/// Report of a job, with the dividends given up after failed attempts and missing in its output
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct JobReport<R> {
+    #[serde(flatten)]
+    pub report: R,
+    #[serde(default)]
+    pub skipped: Vec<JobError>
+}
+
+impl<R> JobReport<R> {
+    pub fn new(report: R, skipped: &[JobError]) -> Self {
+        Self { report, skipped: skipped.to_vec() }
+    }
+}
+
+impl<R: TraitReport + Serialize + serde::de::DeserializeOwned> TraitReport for JobReport<R> {
+    fn print(&self) {
+        self.report.print();
+        if !self.skipped.is_empty() {
+            println!(" Skipped dividends");
+            for error in self.skipped.iter() {
+                println!("\t {error}");
+            }
+        }
+    }
+}
+
+impl<R: Serialize + serde::de::DeserializeOwned> Serialization for JobReport<R> {
+    fn ser_to(&self) -> SerializedFormat { serde_json::to_string(self).unwrap() }
+    fn ser_from(content: &SerializedFormat) -> Self { serde_json::from_str(content).unwrap() }
+}
+
+/// Retries of failed dividends
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]  
+pub struct RetryPolicy {
+    /// attempts per dividend, including the first one
+    pub max_attempts: u32,
+    /// delay before the second attempt, doubled for each further attempt
+    pub backoff: std::time::Duration,
+    pub max_backoff: std::time::Duration,
+    /// error kinds worth another attempt
+    pub retryable: Vec<ErrorKind>,
+    /// skip a dividend out of attempts and complete the job with failures, instead of failing the job
+    pub skip_failed: bool
+}
+
+impl RetryPolicy {
+    /// a single attempt, the job fails with the first error
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    pub fn is_retryable(&self, error: &JobError) -> bool {
+        self.retryable.contains(&error.kind)
+    }
+
+    /// delay before the next attempt after a number of failed ones
+    pub fn backoff(&self, attempts: u32) -> std::time::Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl std::default::Default for RetryPolicy {
+    /// 3 attempts from 1 second backoff, invalid input is not retried
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: std::time::Duration::from_secs(1),
+            max_backoff: std::time::Duration::from_secs(60),
+            retryable: vec![ErrorKind::DataPreparation, ErrorKind::Computation, ErrorKind::Interrupted],
+            skip_failed: false
+        }
+    }
+}
+
+/// What to do after a failed dividend
+#[derive(Debug, Clone, PartialEq, Eq)]  
+pub enum FailureAction {
+    /// compute the dividend again after the delay
+    Retry(std::time::Duration),
+    /// give up the dividend, the job goes on
+    Skip,
+    /// the job has failed
+    Fail
+}
+
+/// Why a computation stopped before the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]  
+pub enum StopReason {
+    Cancelled,
+    TimedOut
+}
+
+/// Cooperative cancellation of a running job, shared by the job owner and the operators
+#[derive(Debug, Clone, Default)]  
+pub struct CancelToken {
+    cancelled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    deadline: Option<std::time::Instant>
+}
+
+impl CancelToken {
+    /// loop iterations between two looks at the token
+    pub const CHECK_INTERVAL: usize = 1024;
+
+    pub fn new(deadline: Option<chrono::DateTime<chrono::Utc>>) -> Self {
+        // a deadline already passed stops at once
+        let deadline = deadline.map(|d| std::time::Instant::now() + (d - chrono::Utc::now()).to_std().unwrap_or_default());
+        Self { cancelled: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)), deadline }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        if self.cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+            Some(StopReason::Cancelled)
+        } else if self.deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            Some(StopReason::TimedOut)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop_reason().is_some()
+    }
+
+    /// for the i-th iteration of a loop, looks at the token every CHECK_INTERVAL iterations only
+    pub fn should_stop(&self, i: usize) -> bool {
+        i.is_multiple_of(Self::CHECK_INTERVAL) && self.is_stopped()
+    }
+}
+
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq, Eq)]  
 pub enum Status {
     Created,
     Processing,
     Completed,
+    /// completed with skipped dividends
+    CompletedWithFailures,
     Cancelled,
+    /// stopped at the deadline of the requirement
+    TimedOut,
+    Failed(JobError),
     ErrorJobIDNotFound
 }
 
@@ -58,13 +300,153 @@ impl std::fmt::Display for Status {
             Self::Created => write!(f, "CREATED"),
             Self::Processing => write!(f, "PROCESSING"),
             Self::Completed => write!(f, "COMPLETED"),
+            Self::CompletedWithFailures => write!(f, "COMPLETED WITH FAILURES"),
             Self::Cancelled => write!(f, "CANCELLED"),
+            Self::TimedOut => write!(f, "TIMED OUT"),
+            Self::Failed(_) => write!(f, "FAILED"),
             Self::ErrorJobIDNotFound => write!(f, "ERROR_ID_NOT_FOUND")
         }
     }
 }
 
 
+impl Status {
+    /// No transition leaves a final status
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Completed | Self::CompletedWithFailures | Self::Cancelled | Self::TimedOut | Self::Failed(_) | Self::ErrorJobIDNotFound)
+    }
+
+    fn can_transition_to(&self, to: &Status) -> bool {
+        matches!((self, to),
+            (Self::Created, Self::Processing) | (Self::Created, Self::Cancelled) | (Self::Created, Self::TimedOut) | (Self::Created, Self::Failed(_)) |
+            (Self::Processing, Self::Completed) | (Self::Processing, Self::CompletedWithFailures) | (Self::Processing, Self::Cancelled) | (Self::Processing, Self::TimedOut) | (Self::Processing, Self::Failed(_)) | (Self::Processing, Self::Created)
+        )
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("job {id}: illegal transition from {from} to {to}")]
+pub struct TransitionError {
+    pub id: ID,
+    pub from: String,
+    pub to: String
+}
+
+/// Status change in the history of a job
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]  
+pub struct StatusChange {
+    pub status: Status,
+    #[serde(with = "ts_milliseconds")]
+    pub time: chrono::DateTime<chrono::Utc>
+}
+
+/// Completed dividend of a job, its output is saved in the output directory of the job
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]  
+pub struct DividendRecord {
+    pub div_index: DividendIndex,
+    pub output_len: usize,
+    pub duration: std::time::Duration
+}
+
+/// file of the serialized output of a dividend in the output directory of its job
+pub fn dividend_output_path(dir: &std::path::Path, div_index: &DividendIndex) -> std::path::PathBuf {
+    dir.join(format!("{}_{}.json", div_index.0, div_index.1))
+}
+
+/// save the serialized output of a dividend before recording it as done or partial
+pub fn save_dividend_output(dir: &std::path::Path, div_index: &DividendIndex, output: &SerializedFormat) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dividend_output_path(dir, div_index), output)
+}
+
+/// Planned dividends of a job and those completed so far
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]  
+pub struct Progress {
+    planned: Vec<DividendIndex>,
+    done: Vec<DividendRecord>,
+    #[serde(default)]
+    attempts: Vec<(DividendIndex, u32)>,
+    /// last errors of the dividends given up
+    #[serde(default)]
+    skipped: Vec<JobError>,
+    /// outputs of the dividends stopped by a cancellation or the deadline
+    #[serde(default)]
+    partial: Vec<DividendRecord>
+}
+
+impl Progress {
+    fn entries(div_index: &DividendIndex) -> usize {
+        div_index.1 - div_index.0
+    }
+
+    pub fn get_planned(&self) -> &[DividendIndex] { &self.planned }
+    pub fn get_done(&self) -> &[DividendRecord] { &self.done }
+    pub fn get_skipped(&self) -> &[JobError] { &self.skipped }
+    pub fn get_partial(&self) -> &[DividendRecord] { &self.partial }
+
+    pub fn is_done(&self, div_index: &DividendIndex) -> bool {
+        self.done.iter().any(|record| &record.div_index == div_index)
+    }
+
+    pub fn is_skipped(&self, div_index: &DividendIndex) -> bool {
+        self.skipped.iter().any(|error| error.div_index.as_ref() == Some(div_index))
+    }
+
+    /// failed attempts of a dividend
+    pub fn attempts(&self, div_index: &DividendIndex) -> u32 {
+        self.attempts.iter()
+            .find(|(di, _)| di == div_index)
+            .map_or(0, |(_, n)| *n)
+    }
+
+    fn add_attempt(&mut self, div_index: &DividendIndex) -> u32 {
+        match self.attempts.iter_mut().find(|(di, _)| di == div_index) {
+            Some((_, n)) => {
+                *n += 1;
+                *n
+            }
+            None => {
+                self.attempts.push((div_index.to_owned(), 1));
+                1
+            }
+        }
+    }
+
+    /// planned dividends neither completed nor skipped
+    pub fn pending(&self) -> Vec<DividendIndex> {
+        self.planned.iter()
+            .filter(|div_index| !self.is_done(div_index) && !self.is_skipped(div_index))
+            .cloned()
+            .collect()
+    }
+
+    /// completed or skipped part in percent, weighted by dividend sizes
+    pub fn percentage(&self) -> f32 {
+        let total: usize = self.planned.iter().map(Self::entries).sum();
+        let processed = self.done.iter().map(|record| record.div_index)
+            .chain(self.skipped.iter().filter_map(|error| error.div_index));
+        let done: usize = processed.clone().map(|div_index| Self::entries(&div_index)).sum();
+        match (total, self.planned.len()) {
+            (_, 0) => 0.0,
+            (0, count) => processed.count() as f32 / count as f32 * 100.0,
+            (total, _) => done as f32 / total as f32 * 100.0
+        }
+    }
+
+    /// remaining time extrapolated from the time elapsed for the completed part
+    pub fn eta(&self, elapsed: std::time::Duration) -> Option<std::time::Duration> {
+        let percentage = self.percentage();
+        if percentage <= 0.0 {
+            return None;
+        }
+        Some(elapsed.mul_f32((100.0 - percentage) / percentage))
+    }
+
+    pub fn output_len(&self) -> usize {
+        self.done.iter().chain(self.partial.iter()).map(|record| record.output_len).sum()
+    }
+}
+
 #[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq, Eq)]  
 pub struct Job {
     id: ID,
@@ -74,35 +456,89 @@ pub struct Job {
     #[serde(with = "ts_milliseconds")]
     time_start: chrono::DateTime<chrono::Utc>,
     duration_prep: Option<std::time::Duration>, // time for data preparation
-    duration: Option<std::time::Duration>
+    duration: Option<std::time::Duration>,
+    #[serde(default)]
+    history: Vec<StatusChange>,
+    #[serde(default)]
+    progress: Progress
 }
 
 impl Job {
     pub fn new(req: Requirement) -> Self {
         let id = generate_id();
-        Self { id, req, status: Status::Created, report_ready: false, time_start: chrono::Utc::now(), duration_prep: None, duration: None }
+        let now = chrono::Utc::now();
+        let history = vec![StatusChange { status: Status::Created, time: now }];
+        Self { id, req, status: Status::Created, report_ready: false, time_start: now, duration_prep: None, duration: None, history, progress: Progress::default() }
     }
 
     pub fn set_id(&mut self, id: ID) {
         self.id = id;
     }
 
-    pub fn start(&mut self) {
-        self.status = Status::Processing;
-        self.time_start = chrono::Utc::now()
+    fn transition_at(&mut self, to: Status, time: chrono::DateTime<chrono::Utc>) -> Result<(), TransitionError> {
+        if !self.status.can_transition_to(&to) {
+            return Err(TransitionError { id: self.id.to_owned(), from: self.status.to_string(), to: to.to_string() });
+        }
+        match to {
+            Status::Processing => self.time_start = time,
+            Status::Created => self.duration_prep = None,
+            // a clock going backwards gives a zero duration
+            Status::Completed | Status::CompletedWithFailures => self.duration = Some((time - self.time_start).to_std().unwrap_or_default()),
+            _ => ()
+        }
+        self.history.push(StatusChange { status: to.to_owned(), time });
+        self.status = to;
+        Ok(())
+    }
+
+    fn transition(&mut self, to: Status) -> Result<(), TransitionError> {
+        self.transition_at(to, chrono::Utc::now())
     }
 
-    pub fn complete(&mut self) {
-        self.status = Status::Completed;
-        self.duration = Some((chrono::Utc::now() - self.time_start).to_std().unwrap());
+    pub fn start(&mut self) -> Result<(), TransitionError> {
+        self.transition(Status::Processing)
+    }
+
+    /// Completed, or CompletedWithFailures if dividends were skipped
+    pub fn complete(&mut self) -> Result<(), TransitionError> {
+        if self.progress.skipped.is_empty() {
+            self.transition(Status::Completed)
+        } else {
+            self.transition(Status::CompletedWithFailures)
+        }
     }
 
     pub fn report_done(&mut self) {
         self.report_ready = true;
     }
 
-    pub fn cancel(&mut self) {
-        self.status = Status::Cancelled;
+    pub fn cancel(&mut self) -> Result<(), TransitionError> {
+        self.transition(Status::Cancelled)
+    }
+
+    pub fn time_out(&mut self) -> Result<(), TransitionError> {
+        self.transition(Status::TimedOut)
+    }
+
+    pub fn stop(&mut self, reason: StopReason) -> Result<(), TransitionError> {
+        match reason {
+            StopReason::Cancelled => self.cancel(),
+            StopReason::TimedOut => self.time_out()
+        }
+    }
+
+    /// token stopping the operators at the deadline of the requirement, or when cancelled
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken::new(self.req.deadline)
+    }
+
+    pub fn fail(&mut self, error: JobError) -> Result<(), TransitionError> {
+        self.transition(Status::Failed(error))
+    }
+
+    /// back to Created after an interrupted processing, the preparation time spent is discarded
+    pub fn requeue(&mut self) -> Result<(), TransitionError> {
+        self.transition(Status::Created)
     }
 
     pub fn add_duration_prep(&mut self, d: &std::time::Duration) {
@@ -112,35 +548,370 @@ impl Job {
         };
     }
 
+    /// dividends to be computed, the completed ones are discarded
+    pub fn plan(&mut self, dividends: Vec<DividendIndex>) {
+        self.progress = Progress { planned: dividends, ..Progress::default() };
+    }
+
+    /// record a completed dividend whose output is saved, a dividend already recorded is ignored
+    pub fn dividend_done(&mut self, div_index: &DividendIndex, output_len: usize, duration: std::time::Duration) {
+        if self.progress.is_done(div_index) {
+            return;
+        }
+        self.progress.done.push(DividendRecord { div_index: div_index.to_owned(), output_len, duration });
+    }
+
+    /// record the saved output gathered by a dividend stopped before its end
+    pub fn dividend_partial(&mut self, div_index: &DividendIndex, output_len: usize, duration: std::time::Duration) {
+        self.progress.partial.push(DividendRecord { div_index: div_index.to_owned(), output_len, duration });
+    }
+
+    /// the results gathered so far reach the max_hits of the input, no further dividend is needed
+    pub fn is_output_complete(&self) -> bool {
+        self.req.get_opk().max_hits(self.req.get_ji()).is_some_and(|max| self.progress.output_len() >= max)
+    }
+
+    /// count a failed attempt of a dividend and decide by the retry policy, a job level error fails the job
+    pub fn dividend_failed(&mut self, error: JobError) -> Result<FailureAction, TransitionError> {
+        let policy = self.req.get_retry();
+        if let Some(div_index) = error.div_index {
+            let attempts = self.progress.add_attempt(&div_index);
+            if policy.is_retryable(&error) && attempts < policy.max_attempts {
+                return Ok(FailureAction::Retry(policy.backoff(attempts)));
+            }
+            if policy.skip_failed {
+                crate::logging::warn(format!("job {}: dividend skipped after {} attempts, {}", self.id, attempts, error).as_str());
+                self.progress.skipped.push(error);
+                return Ok(FailureAction::Skip);
+            }
+        }
+        self.fail(error)?;
+        Ok(FailureAction::Fail)
+    }
+
+    /// save a report built from the outputs gathered so far in the output directory, listing the skipped dividends
+    pub fn report_save_partial(&self, dir: &std::path::Path, filepath: &std::path::PathBuf) -> std::io::Result<u64> {
+        let output_sers: Vec<SerializedFormat> = self.progress.done.iter()
+            .chain(self.progress.partial.iter())
+            .map(|record| std::fs::read_to_string(dividend_output_path(dir, &record.div_index)))
+            .collect::<std::io::Result<_>>()?;
+        self.req.get_opk().report_save(self.id.to_owned(), self.req.get_dsk().to_owned(), self.req.get_ji(), &output_sers, &self.progress.skipped, filepath)
+    }
+
     pub fn get_id(&self) -> &ID { &self.id }
     pub fn get_req(&self) -> &Requirement { &self.req }
     pub fn get_input(&self) -> &SerializedFormat { self.req.get_ji() }
     pub fn get_opk(&self) -> &crate::kinds::Operator { self.req.get_opk() }
     pub fn get_dsk(&self) -> &crate::kinds::Dataset { self.req.get_dsk() }
+    pub fn get_priority(&self) -> &Priority { self.req.get_priority() }
+    pub fn get_status(&self) -> &Status { &self.status }
+    pub fn get_time_start(&self) -> &chrono::DateTime<chrono::Utc> { &self.time_start }
+    pub fn get_history(&self) -> &[StatusChange] { &self.history }
+    pub fn get_progress(&self) -> &Progress { &self.progress }
 
     pub fn is_status(&self, comp_status: Status) -> bool {
         self.status == comp_status
     }
 
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, Status::Failed(_))
+    }
+
     pub fn is_report_ready(&self) -> bool { 
         self.report_ready
     }
 
-    pub fn set_status(&mut self, new_status: Status) {
-        self.status = new_status;
+    pub fn set_status(&mut self, new_status: Status) -> Result<(), TransitionError> {
+        self.transition(new_status)
+    }
+
+    /// Time spent in each status of the history, up to now for the current one unless final
+    pub fn phase_durations(&self) -> Vec<(&Status, std::time::Duration)> {
+        let now = chrono::Utc::now();
+        self.history.iter()
+            .enumerate()
+            .filter(|(_, change)| !change.status.is_final())
+            .map(|(i, change)| {
+                let end = self.history.get(i + 1).map_or(now, |next| next.time);
+                (&change.status, (end - change.time).to_std().unwrap_or_default())
+            })
+            .collect()
     }
 
     fn print_time_properties(&self) -> String {
         format!("{}\t{:.2}\t{:.2}", self.time_start.format("%Y-%m-%d %H:%M:%S").to_string(), self.duration_prep.map_or(0.0, |d| d.as_secs_f32()), self.duration.map_or(0.0, |d| d.as_secs_f32()))
     }
+
+    fn print_progress_at(&self, now: chrono::DateTime<chrono::Utc>) -> String {
+        if self.progress.planned.is_empty() {
+            return String::new();
+        }
+        let percentage = format!("{:.1}% of {} dividends", self.progress.percentage(), self.progress.planned.len());
+        let eta = match self.status {
+            Status::Processing => self.progress.eta((now - self.time_start).to_std().unwrap_or_default()),
+            _ => None
+        };
+        match eta {
+            Some(d) => format!("{}, ETA {:.0}s", percentage, d.as_secs_f32()),
+            None => percentage
+        }
+    }
+
+    fn print_phases(&self) -> String {
+        self.phase_durations().iter()
+            .map(|(status, d)| format!("{} {:.2}s", status, d.as_secs_f32()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
 }
 
 impl std::fmt::Display for Job {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        format!("{} {}\t\t{:20}\t{:15}\t{}", self.id, self.status, self.req.get_opk().to_string(), self.req.get_dsk().to_string(), self.print_time_properties()).fmt(f)
+        format!("{} {}\t\t{:20}\t{:15}\t{}\t{}", self.id, self.status, self.req.get_opk().to_string(), self.req.get_dsk().to_string(), self.print_time_properties(), self.print_phases()).fmt(f)?;
+        let progress = self.print_progress_at(chrono::Utc::now());
+        if !progress.is_empty() {
+            write!(f, "\t{}", progress)?;
+        }
+        match &self.status {
+            Status::Failed(error) => write!(f, "\n\t{}", error),
+            _ => Ok(())
+        }
     }
 }
 
 pub type Jobs = std::collections::HashMap<ID, Job>;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_fail() {
+        let mut job = Job::new(Requirement::default());
+        job.start().unwrap();
+        job.fail(JobError::new(ErrorKind::InvalidInput, "SMARTS C(( cannot be parsed").at(&(0, 100))).unwrap();
+        assert!(job.is_failed());
+        assert_eq!(job.get_status(), &Status::Failed(JobError { kind: ErrorKind::InvalidInput, message: "SMARTS C(( cannot be parsed".to_string(), div_index: Some((0, 100)) }));
+        assert_eq!(Job::ser_from(&job.ser_to()).get_status(), job.get_status());
+        let display = job.to_string();
+        assert!(display.contains("FAILED"));
+        assert!(display.ends_with("\n\tinvalid input error at dividend 0..100: SMARTS C(( cannot be parsed"));
 
+        let payload = std::panic::catch_unwind(|| panic!("fingerprint not available")).unwrap_err();
+        let error = JobError::from_panic(payload, &(5, 10));
+        assert_eq!(error.to_string(), "computation error at dividend 5..10: fingerprint not available");
+        let error = JobError::new(ErrorKind::DataPreparation, "no entries");
+        assert_eq!(error.to_string(), "data preparation error: no entries");
+    }
+
+    #[test]
+    fn test_priority() {
+        let req = Requirement::default();
+        assert_eq!(req.get_priority(), &Priority::Normal);
+        // requirements serialized before priorities existed
+        let req_old = Requirement::ser_from(&r#"{"ji":"c1ccccc1","opk":"OpenBabelSSMatching","dsk":"Dummy"}"#.to_string());
+        assert_eq!(req_old.get_priority(), &Priority::Normal);
+        let req_high = req.with_priority(Priority::High);
+        assert_eq!(Requirement::ser_from(&req_high.ser_to()), req_high);
+        assert!(Priority::High > Priority::Normal && Priority::Normal > Priority::Low);
+        assert_eq!(Priority::from_str("low").unwrap(), Priority::Low);
+        assert_eq!(Priority::High.to_string(), "high");
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut job = Job::new(Requirement::default());
+        assert_eq!(job.complete(), Err(TransitionError { id: job.get_id().to_owned(), from: "CREATED".to_string(), to: "COMPLETED".to_string() }));
+        assert!(job.requeue().is_err());
+        job.start().unwrap();
+        assert!(job.start().is_err());
+        job.requeue().unwrap();
+        job.start().unwrap();
+        job.complete().unwrap();
+        // final statuses stay
+        assert!(job.cancel().is_err());
+        assert!(job.fail(JobError::new(ErrorKind::Computation, "late")).is_err());
+        assert!(job.set_status(Status::Processing).is_err());
+        assert!(job.is_status(Status::Completed));
+        assert_eq!(job.get_history().iter().map(|c| c.status.to_string()).collect::<Vec<String>>(), vec!["CREATED", "PROCESSING", "CREATED", "PROCESSING", "COMPLETED"]);
+        assert_eq!(Job::ser_from(&job.ser_to()).get_history().len(), 5);
+
+        let mut job_cancelled = Job::new(Requirement::default());
+        job_cancelled.cancel().unwrap();
+        assert!(job_cancelled.start().is_err());
+        assert_eq!(TransitionError { id: "id".to_string(), from: "CANCELLED".to_string(), to: "PROCESSING".to_string() }.to_string(), "job id: illegal transition from CANCELLED to PROCESSING");
+    }
+
+    #[test]
+    fn test_phase_durations() {
+        let mut job = Job::new(Requirement::default());
+        let t0 = chrono::Utc::now();
+        job.history = vec![StatusChange { status: Status::Created, time: t0 }];
+        job.transition_at(Status::Processing, t0 + chrono::Duration::seconds(2)).unwrap();
+        job.transition_at(Status::Completed, t0 + chrono::Duration::seconds(5)).unwrap();
+        let phases = job.phase_durations();
+        assert_eq!(phases, vec![(&Status::Created, std::time::Duration::from_secs(2)), (&Status::Processing, std::time::Duration::from_secs(3))]);
+        assert_eq!(job.duration, Some(std::time::Duration::from_secs(3)));
+        assert!(job.to_string().contains("CREATED 2.00s, PROCESSING 3.00s"));
+
+        // clock skew gives zero durations instead of a panic
+        let mut job_skewed = Job::new(Requirement::default());
+        let t1 = chrono::Utc::now();
+        job_skewed.transition_at(Status::Processing, t1).unwrap();
+        job_skewed.transition_at(Status::Completed, t1 - chrono::Duration::seconds(1)).unwrap();
+        assert_eq!(job_skewed.duration, Some(std::time::Duration::ZERO));
+        assert_eq!(job_skewed.phase_durations()[1], (&Status::Processing, std::time::Duration::ZERO));
+    }
+
+    #[test]
+    fn test_progress() {
+        let req = Requirement::new(crate::app::chem::openbabel::substructure::Input::new("CC".to_string()).ser_to(), crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::Dummy);
+        let mut job = Job::new(req);
+        assert!(!job.to_string().contains('%'));
+        job.plan(vec![(0, 1), (1, 2), (2, 4)]);
+        job.start().unwrap();
+        assert_eq!(job.get_progress().percentage(), 0.0);
+        assert_eq!(job.get_progress().eta(std::time::Duration::from_secs(10)), None);
+
+        let output = |id: &str| crate::app::chem::openbabel::substructure::Output { results: vec![(vec![vec![1, 2]], id.to_string())], screened: 0, screened_out: 0, max_hits: None };
+        let dir = crate::utils::tests::temp_dir();
+        save_dividend_output(&dir, &(2, 4), &output("mol_3").ser_to()).unwrap();
+        save_dividend_output(&dir, &(0, 1), &output("mol_0").ser_to()).unwrap();
+        job.dividend_done(&(2, 4), 1, std::time::Duration::from_secs(2));
+        job.dividend_done(&(2, 4), 1, std::time::Duration::from_secs(2));
+        job.dividend_done(&(0, 1), 1, std::time::Duration::from_secs(1));
+        let progress = job.get_progress();
+        assert_eq!(progress.percentage(), 75.0);
+        assert_eq!(progress.pending(), vec![(1, 2)]);
+        assert_eq!(progress.output_len(), 2);
+        assert_eq!(progress.eta(std::time::Duration::from_secs(30)), Some(std::time::Duration::from_secs(10)));
+        let now = *job.get_time_start() + chrono::Duration::seconds(6);
+        assert_eq!(job.print_progress_at(now), "75.0% of 3 dividends, ETA 2s");
+        assert_eq!(Job::ser_from(&job.ser_to()).get_progress(), job.get_progress());
+
+        let filepath = dir.join("report.json");
+        job.report_save_partial(&dir, &filepath).unwrap();
+        let report = crate::app::chem::openbabel::substructure::Report::ser_from(&std::fs::read_to_string(&filepath).unwrap());
+        assert!(!job.ser_to().contains("mol_3"));
+        std::fs::remove_file(dividend_output_path(&dir, &(0, 1))).unwrap();
+        assert!(job.report_save_partial(&dir, &filepath).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(&report.job_id, job.get_id());
+        assert_eq!(report.output.results.iter().map(|(_, id)| id.as_str()).collect::<Vec<&str>>(), vec!["mol_3", "mol_0"]);
+
+        job.complete().unwrap();
+        assert_eq!(job.print_progress_at(now), "75.0% of 3 dividends");
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), std::time::Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), std::time::Duration::from_secs(4));
+        assert_eq!(policy.backoff(40), std::time::Duration::from_secs(60));
+        assert!(!policy.is_retryable(&JobError::new(ErrorKind::InvalidInput, "bad SMARTS")));
+        assert!(policy.is_retryable(&JobError::new(ErrorKind::Interrupted, "worker lost")));
+        let req_old = Requirement::ser_from(&r#"{"ji":"c1ccccc1","opk":"OpenBabelSSMatching","dsk":"Dummy"}"#.to_string());
+        assert_eq!(req_old.get_retry(), &policy);
+
+        // the job fails once attempts are used up
+        let mut job = Job::new(Requirement::default().with_retry(RetryPolicy { max_attempts: 2, ..RetryPolicy::default() }));
+        job.plan(vec![(0, 2), (2, 4)]);
+        job.start().unwrap();
+        let crash = JobError::new(ErrorKind::Computation, "OpenBabel crashed").at(&(0, 2));
+        assert_eq!(job.dividend_failed(crash.clone()).unwrap(), FailureAction::Retry(std::time::Duration::from_secs(1)));
+        assert_eq!(job.get_progress().attempts(&(0, 2)), 1);
+        assert_eq!(job.dividend_failed(crash.clone()).unwrap(), FailureAction::Fail);
+        assert_eq!(job.get_status(), &Status::Failed(crash));
+
+        // invalid input is not retried
+        let mut job_invalid = Job::new(Requirement::default());
+        job_invalid.start().unwrap();
+        assert_eq!(job_invalid.dividend_failed(JobError::new(ErrorKind::InvalidInput, "bad SMARTS").at(&(0, 100))).unwrap(), FailureAction::Fail);
+        assert!(job_invalid.is_failed());
+    }
+
+    #[test]
+    fn test_completed_with_failures() {
+        let req = Requirement::new(crate::app::chem::openbabel::substructure::Input::new("CC".to_string()).ser_to(), crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::Dummy)
+            .with_retry(RetryPolicy { skip_failed: true, ..RetryPolicy::none() });
+        let mut job = Job::new(req);
+        job.plan(vec![(0, 2), (2, 4)]);
+        job.start().unwrap();
+        let output = crate::app::chem::openbabel::substructure::Output { results: vec![(vec![vec![1, 2]], "mol_0".to_string())], screened: 0, screened_out: 0, max_hits: None };
+        let dir = crate::utils::tests::temp_dir();
+        save_dividend_output(&dir, &(0, 2), &output.ser_to()).unwrap();
+        job.dividend_done(&(0, 2), 1, std::time::Duration::from_secs(1));
+        let lost = JobError::new(ErrorKind::Interrupted, "worker lost").at(&(2, 4));
+        assert_eq!(job.dividend_failed(lost.clone()).unwrap(), FailureAction::Skip);
+        assert!(job.get_progress().pending().is_empty());
+        assert_eq!(job.get_progress().percentage(), 100.0);
+        job.complete().unwrap();
+        assert!(job.is_status(Status::CompletedWithFailures));
+        assert!(job.to_string().contains("COMPLETED WITH FAILURES"));
+        assert!(job.start().is_err());
+
+        let filepath = dir.join("report.json");
+        job.report_save_partial(&dir, &filepath).unwrap();
+        let report = JobReport::<crate::app::chem::openbabel::substructure::Report>::ser_from(&std::fs::read_to_string(&filepath).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(report.report.output.results.len(), 1);
+        assert_eq!(report.skipped, vec![lost]);
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        let token = CancelToken::default();
+        let token_shared = token.clone();
+        assert_eq!(token.stop_reason(), None);
+        assert!(!token.should_stop(0));
+        token_shared.cancel();
+        assert_eq!(token.stop_reason(), Some(StopReason::Cancelled));
+        assert!(token.should_stop(CancelToken::CHECK_INTERVAL));
+        assert!(!token.should_stop(1));
+
+        let token_passed = CancelToken::new(Some(chrono::Utc::now() - chrono::Duration::seconds(1)));
+        assert_eq!(token_passed.stop_reason(), Some(StopReason::TimedOut));
+        let token_future = CancelToken::new(Some(chrono::Utc::now() + chrono::Duration::hours(1)));
+        assert_eq!(token_future.stop_reason(), None);
+    }
+
+    #[test]
+    fn test_deadline() {
+        let deadline = chrono::Utc::now() - chrono::Duration::seconds(1);
+        let req = Requirement::new(crate::app::chem::openbabel::substructure::Input::new("CC".to_string()).ser_to(), crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::Dummy)
+            .with_deadline(deadline);
+        assert_eq!(Requirement::ser_from(&req.ser_to()).get_deadline().map(|d| d.timestamp_millis()), Some(deadline.timestamp_millis()));
+        let mut job = Job::new(req);
+        job.plan(vec![(0, 2), (2, 4)]);
+        job.start().unwrap();
+        let token = job.cancel_token();
+        let reason = token.stop_reason().unwrap();
+        assert_eq!(reason, StopReason::TimedOut);
+
+        let output = |id: &str| crate::app::chem::openbabel::substructure::Output { results: vec![(vec![vec![1, 2]], id.to_string())], screened: 0, screened_out: 0, max_hits: None };
+        let dir = crate::utils::tests::temp_dir();
+        save_dividend_output(&dir, &(0, 2), &output("mol_0").ser_to()).unwrap();
+        save_dividend_output(&dir, &(2, 4), &output("mol_2").ser_to()).unwrap();
+        job.dividend_done(&(0, 2), 1, std::time::Duration::from_secs(1));
+        job.dividend_partial(&(2, 4), 1, std::time::Duration::from_secs(1));
+        job.stop(reason).unwrap();
+        assert!(job.is_status(Status::TimedOut));
+        assert!(job.complete().is_err());
+        assert_eq!(job.get_progress().pending(), vec![(2, 4)]);
+        assert_eq!(job.get_progress().output_len(), 2);
+
+        let filepath = dir.join("report.json");
+        job.report_save_partial(&dir, &filepath).unwrap();
+        let report = crate::app::chem::openbabel::substructure::Report::ser_from(&std::fs::read_to_string(&filepath).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(report.output.results.len(), 2);
+
+        let mut job_cancelled = Job::new(Requirement::default());
+        job_cancelled.start().unwrap();
+        job_cancelled.stop(StopReason::Cancelled).unwrap();
+        assert!(job_cancelled.is_status(Status::Cancelled));
+        assert_eq!(job_cancelled.cancel_token().stop_reason(), None);
+    }
+}
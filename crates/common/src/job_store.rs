@@ -0,0 +1,290 @@
+//! Job Store
+//!     jobs kept across restarts of the server
+//!
+//! FileJobStore writes every change as a line of an append-only log, and compacts the log into a snapshot
+//! once it grows past the snapshot interval. At startup the snapshot is loaded and the log replayed on top of it,
+//! a torn last line left by a crash is ignored.
+//!
+
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+const SNAPSHOT_FILENAME: &str = "jobs.snapshot";
+const LOG_FILENAME: &str = "jobs.log";
+const SNAPSHOT_INTERVAL: usize = 1000;
+
+#[derive(Error, Debug)]
+pub enum JobStoreError {
+    #[error("job store io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("job store snapshot corrupted: {0}")]
+    CorruptedSnapshot(serde_json::Error),
+    #[error("job store log corrupted at line {0}: {1}")]
+    CorruptedLog(usize, serde_json::Error)
+}
+
+/// What becomes of the jobs which were processing when the server stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// back to Created, to be processed again
+    Requeue,
+    /// no more processing, jobs fail with an interrupted error
+    Fail
+}
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Put(Box<crate::job::Job>),
+    Remove(crate::job::ID)
+}
+
+impl Record {
+    fn apply(self, jobs: &mut crate::job::Jobs) {
+        match self {
+            Self::Put(job) => { jobs.insert(job.get_id().to_owned(), *job); }
+            Self::Remove(id) => { jobs.remove(&id); }
+        }
+    }
+}
+
+/// Jobs in memory only, lost with the process
+#[derive(Default)]
+pub struct MemoryJobStore {
+    jobs: crate::job::Jobs
+}
+
+impl crate::traits::TraitJobStore for MemoryJobStore {
+    fn put(&mut self, job: &crate::job::Job) -> Result<(), JobStoreError> {
+        self.jobs.insert(job.get_id().to_owned(), job.to_owned());
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &crate::job::ID) -> Result<Option<crate::job::Job>, JobStoreError> {
+        Ok(self.jobs.remove(id))
+    }
+
+    fn get(&self, id: &crate::job::ID) -> Option<&crate::job::Job> { self.jobs.get(id) }
+    fn jobs(&self) -> &crate::job::Jobs { &self.jobs }
+}
+
+/// Jobs in a directory, as a snapshot and an append-only log of the changes since
+pub struct FileJobStore {
+    dir: std::path::PathBuf,
+    jobs: crate::job::Jobs,
+    log: std::fs::File,
+    log_len: usize, // records in the log since the last snapshot
+    snapshot_interval: usize
+}
+
+impl FileJobStore {
+    /// Load the jobs of the directory, created if missing, and recover the interrupted ones
+    pub fn open(dir: &std::path::Path, recovery: Recovery) -> Result<Self, JobStoreError> {
+        std::fs::create_dir_all(dir)?;
+        let mut jobs = match std::fs::read_to_string(dir.join(SNAPSHOT_FILENAME)) {
+            Ok(content) => serde_json::from_str::<Vec<crate::job::Job>>(&content)
+                .map_err(JobStoreError::CorruptedSnapshot)?
+                .into_iter()
+                .map(|job| (job.get_id().to_owned(), job))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => crate::job::Jobs::new(),
+            Err(e) => return Err(e.into())
+        };
+
+        let log_filepath = dir.join(LOG_FILENAME);
+        match std::fs::read_to_string(&log_filepath) {
+            Ok(content) => Self::replay(&content, &mut jobs)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into())
+        }
+
+        for job in jobs.values_mut().filter(|job| job.is_status(crate::job::Status::Processing)) {
+            crate::logging::warn(format!("job {} interrupted, recovery: {:?}", job.get_id(), recovery).as_str());
+            let recovered = match recovery {
+                Recovery::Requeue => job.requeue(),
+                Recovery::Fail => job.fail(crate::job::JobError::new(crate::job::ErrorKind::Interrupted, "server stopped while processing"))
+            };
+            if let Err(e) = recovered {
+                crate::logging::error(e.to_string().as_str());
+            }
+        }
+
+        let log = std::fs::OpenOptions::new().create(true).append(true).open(&log_filepath)?;
+        let mut store = Self { dir: dir.to_path_buf(), jobs, log, log_len: 0, snapshot_interval: SNAPSHOT_INTERVAL };
+        store.snapshot()?;
+        Ok(store)
+    }
+
+    pub fn set_snapshot_interval(&mut self, interval: usize) {
+        self.snapshot_interval = interval;
+    }
+
+    fn replay(content: &str, jobs: &mut crate::job::Jobs) -> Result<(), JobStoreError> {
+        let lines: Vec<&str> = content.lines().collect();
+        for (index, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<Record>(line) {
+                Ok(record) => record.apply(jobs),
+                // a crash may leave the last record half written
+                Err(e) if index == lines.len() - 1 && !content.ends_with('\n') => {
+                    crate::logging::warn(format!("job store log: incomplete last record ignored, {}", e).as_str());
+                }
+                Err(e) => return Err(JobStoreError::CorruptedLog(index + 1, e))
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, record: &Record) -> Result<(), JobStoreError> {
+        use std::io::Write;
+        let mut line = serde_json::to_string(record).unwrap();
+        line.push('\n');
+        self.log.write_all(line.as_bytes())?;
+        self.log.sync_data()?;
+        self.log_len += 1;
+        Ok(())
+    }
+
+    /// Compact the log once it is long enough, after the change of its last record is applied
+    fn compact(&mut self) -> Result<(), JobStoreError> {
+        if self.log_len >= self.snapshot_interval {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    /// Write every job to the snapshot, and empty the log
+    ///     replaying a log already contained in the snapshot gives the same jobs, so a crash in between loses nothing
+    pub fn snapshot(&mut self) -> Result<(), JobStoreError> {
+        let mut jobs: Vec<&crate::job::Job> = self.jobs.values().collect();
+        jobs.sort_by(|j1, j2| j1.get_id().cmp(j2.get_id()));
+        let filepath = self.dir.join(SNAPSHOT_FILENAME);
+        let filepath_tmp = filepath.with_extension(format!("snapshot.{}", crate::utils::generate_id(8)));
+        std::fs::write(&filepath_tmp, serde_json::to_string(&jobs).unwrap())?;
+        std::fs::File::open(&filepath_tmp)?.sync_all()?;
+        std::fs::rename(&filepath_tmp, &filepath)?;
+        self.log.set_len(0)?;
+        self.log.sync_all()?;
+        self.log_len = 0;
+        Ok(())
+    }
+}
+
+impl crate::traits::TraitJobStore for FileJobStore {
+    fn put(&mut self, job: &crate::job::Job) -> Result<(), JobStoreError> {
+        self.append(&Record::Put(Box::new(job.to_owned())))?;
+        self.jobs.insert(job.get_id().to_owned(), job.to_owned());
+        self.compact()
+    }
+
+    fn remove(&mut self, id: &crate::job::ID) -> Result<Option<crate::job::Job>, JobStoreError> {
+        if !self.jobs.contains_key(id) {
+            return Ok(None);
+        }
+        self.append(&Record::Remove(id.to_owned()))?;
+        let job = self.jobs.remove(id);
+        self.compact()?;
+        Ok(job)
+    }
+
+    fn get(&self, id: &crate::job::ID) -> Option<&crate::job::Job> { self.jobs.get(id) }
+    fn jobs(&self) -> &crate::job::Jobs { &self.jobs }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::TraitJobStore;
+
+    fn new_job() -> crate::job::Job {
+        crate::job::Job::new(crate::job::Requirement::default())
+    }
+
+    /// job as read back from the store, the start time is kept in milliseconds
+    fn persisted(job: &crate::job::Job) -> crate::job::Job {
+        use crate::traits::Serialization;
+        crate::job::Job::ser_from(&job.ser_to())
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = crate::utils::tests::temp_dir();
+        let mut store = FileJobStore::open(&dir, Recovery::Requeue).unwrap();
+        assert!(store.jobs().is_empty());
+        let job_created = new_job();
+        let mut job_processing = new_job();
+        job_processing.start().unwrap();
+        let mut job_completed = new_job();
+        job_completed.start().unwrap();
+        job_completed.complete().unwrap();
+        job_completed.report_done();
+        let job_removed = new_job();
+        for job in [&job_created, &job_processing, &job_completed, &job_removed] {
+            store.put(job).unwrap();
+        }
+        assert_eq!(store.remove(job_removed.get_id()).unwrap(), Some(job_removed.to_owned()));
+        assert_eq!(store.remove(job_removed.get_id()).unwrap(), None);
+        drop(store);
+
+        // processing jobs are requeued at restart
+        let store = FileJobStore::open(&dir, Recovery::Requeue).unwrap();
+        assert_eq!(store.jobs().len(), 3);
+        assert_eq!(store.get(job_created.get_id()), Some(&persisted(&job_created)));
+        assert_eq!(store.get(job_completed.get_id()), Some(&persisted(&job_completed)));
+        assert!(store.get(job_completed.get_id()).unwrap().is_report_ready());
+        assert!(store.get(job_processing.get_id()).unwrap().is_status(crate::job::Status::Created));
+        assert!(store.get(job_removed.get_id()).is_none());
+        assert_eq!(store.history(None), vec![&persisted(&job_completed)]);
+        drop(store);
+
+        // or given up
+        let mut store = FileJobStore::open(&dir, Recovery::Fail).unwrap();
+        let mut job_interrupted = new_job();
+        job_interrupted.start().unwrap();
+        store.put(&job_interrupted).unwrap();
+        drop(store);
+        let store = FileJobStore::open(&dir, Recovery::Fail).unwrap();
+        let status = store.get(job_interrupted.get_id()).unwrap().get_status();
+        assert!(matches!(status, crate::job::Status::Failed(error) if error.kind == crate::job::ErrorKind::Interrupted));
+        assert_eq!(store.history(Some(1)).len(), 1);
+        assert_eq!(store.history(None).len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_and_log() {
+        let dir = crate::utils::tests::temp_dir();
+        let mut store = FileJobStore::open(&dir, Recovery::Requeue).unwrap();
+        store.set_snapshot_interval(3);
+        let jobs: Vec<crate::job::Job> = (0..5).map(|_| new_job()).collect();
+        jobs.iter().for_each(|job| store.put(job).unwrap());
+        // 3 records compacted into the snapshot, 2 left in the log
+        assert_eq!(std::fs::read_to_string(dir.join(LOG_FILENAME)).unwrap().lines().count(), 2);
+        drop(store);
+
+        // a torn last record is ignored
+        let mut log = std::fs::OpenOptions::new().append(true).open(dir.join(LOG_FILENAME)).unwrap();
+        std::io::Write::write_all(&mut log, br#"{"Remove":"#).unwrap();
+        let store = FileJobStore::open(&dir, Recovery::Requeue).unwrap();
+        assert_eq!(store.jobs().len(), 5);
+        assert!(jobs.iter().all(|job| store.get(job.get_id()) == Some(&persisted(job))));
+        assert_eq!(std::fs::read_to_string(dir.join(LOG_FILENAME)).unwrap(), "");
+        drop(store);
+
+        // corruption before the last record is an error
+        std::fs::write(dir.join(LOG_FILENAME), format!("garbage\n{}\n", serde_json::to_string(&Record::Remove(jobs[0].get_id().to_owned())).unwrap())).unwrap();
+        assert!(matches!(FileJobStore::open(&dir, Recovery::Requeue), Err(JobStoreError::CorruptedLog(1, _))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let mut store = MemoryJobStore::default();
+        let job = new_job();
+        store.put(&job).unwrap();
+        assert_eq!(store.get(job.get_id()), Some(&job));
+        assert!(store.history(None).is_empty());
+        assert_eq!(store.remove(job.get_id()).unwrap(), Some(job));
+        assert!(store.jobs().is_empty());
+    }
+}
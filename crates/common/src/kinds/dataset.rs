@@ -3,22 +3,35 @@
 use serde::{Serialize, Deserialize};
 use chiral_derive::Serialization;
 use crate::traits::{Serialization, SerializedFormat};
-use strum_macros::{EnumString, Display};
+use strum_macros::{EnumString, AsRefStr};
 
-#[derive(Serialize, Deserialize, Serialization, Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display)]  
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq, Eq, Hash, EnumString, AsRefStr)]  
 #[strum(serialize_all = "snake_case")]
 pub enum Kind {
     Empty,
     Dummy,
     TestChembl,
     Chembl30,
-    PubChem
+    PubChem,
+    /// dataset registered by name in the dataset registry, resolved by DatasetRegistry::resolve instead of parsed
+    #[strum(disabled)]
+    Custom(String)
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Custom(name) => f.pad(name),
+            _ => f.pad(self.as_ref())
+        }
+    }
 }
 
 impl Kind {
     // pub fn serialize(&self) -> crate::SerializedFormat { serde_json::to_string(self).unwrap() }
     // pub fn deserialize(content: &crate::SerializedFormat) -> Self { serde_json::from_str(content).unwrap() }
 
+    /// entries of the built-in datasets, a custom dataset is sized by its loaded file
     pub fn size(&self) -> usize {
         match self {
             Kind::Empty => 0,
@@ -26,6 +39,7 @@ impl Kind {
             Kind::TestChembl => 10000,
             Kind::Chembl30 => 2136187,
             Kind::PubChem => 160000000, // rough figure, PubChem does not have a version 
+            Kind::Custom(_) => 0
         }
     }
 
@@ -34,27 +48,28 @@ impl Kind {
             Kind::Empty => unimplemented!(),
             Kind::Dummy => unimplemented!(),
             Kind::TestChembl | Kind::Chembl30 => "DATASET_CHEMBL_PATH",
-            Kind::PubChem => ""
+            Kind::PubChem => "DATASET_PUBCHEM_PATH",
+            Kind::Custom(_) => "DATASET_CUSTOM_PATH"
         }
     }
 
-    pub fn source_url(&self) -> &str {
+    /// download location of the public datasets
+    pub fn source_url(&self) -> Option<&str> {
         match self {
-            Kind::Empty => unimplemented!(),
-            Kind::Dummy => unimplemented!(),
-            Kind::TestChembl => "https://github.com/chiral-data/chiral-db-example-data/blob/main/ChEMBL/chembl_30_chemreps_10k.txt?raw=true",
-            Kind::Chembl30 => "https://ftp.ebi.ac.uk/pub/databases/chembl/ChEMBLdb/releases/chembl_30/chembl_30_chemreps.txt.gz",
-            Kind::PubChem => "https://ftp.ncbi.nlm.nih.gov/pubchem/Compound/Extras/CID-SMILES.gz"
+            Kind::TestChembl => Some("https://github.com/chiral-data/chiral-db-example-data/blob/main/ChEMBL/chembl_30_chemreps_10k.txt?raw=true"),
+            Kind::Chembl30 => Some("https://ftp.ebi.ac.uk/pub/databases/chembl/ChEMBLdb/releases/chembl_30/chembl_30_chemreps.txt.gz"),
+            Kind::PubChem => Some("https://ftp.ncbi.nlm.nih.gov/pubchem/Compound/Extras/CID-SMILES.gz"),
+            Kind::Empty | Kind::Dummy | Kind::Custom(_) => None
         }
     }
 
-    pub fn filename(&self) -> &str {
+    /// file of a built-in dataset in its data directory, a custom dataset has its path in the dataset registry
+    pub fn filename(&self) -> Option<&str> {
         match self {
-            Kind::Empty => unimplemented!(),
-            Kind::Dummy => unimplemented!(),
-            Kind::TestChembl => "chembl_30_chemreps_10k.txt",
-            Kind::Chembl30 => "chembl_30_chemreps.txt",
-            Kind::PubChem => "CID-SMILES.gz"
+            Kind::TestChembl => Some("chembl_30_chemreps_10k.txt"),
+            Kind::Chembl30 => Some("chembl_30_chemreps.txt"),
+            Kind::PubChem => Some("CID-SMILES.gz"),
+            Kind::Empty | Kind::Dummy | Kind::Custom(_) => None
         }
     }
 }
@@ -69,6 +84,10 @@ mod tests {
     fn test_kind() {
         let kind = Kind::TestChembl;
         assert_eq!(kind.size(), 10000);
+        assert!(kind.source_url().is_some());
+        assert_eq!(Kind::Custom("my_library".to_string()).source_url(), None);
+        assert_eq!(Kind::PubChem.filename(), Some("CID-SMILES.gz"));
+        assert_eq!(Kind::Custom("my_library".to_string()).filename(), None);
     }
 
     #[test]
@@ -79,6 +98,10 @@ mod tests {
         assert_eq!(Kind::Empty.to_string(), "empty");
         assert_eq!(Kind::TestChembl.to_string(), "test_chembl");
         assert_eq!(Kind::Chembl30.to_string(), "chembl30");
+        assert!(Kind::from_str("my_library").is_err());
+        assert!(Kind::from_str("test_chemb").is_err());
+        assert_eq!(Kind::Custom("my_library".to_string()).to_string(), "my_library");
+        assert_eq!(format!("{:12}|", Kind::PubChem), "pub_chem    |");
     }
 
 }
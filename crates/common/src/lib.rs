@@ -3,6 +3,11 @@ pub mod logging;
 pub mod kinds;
 pub mod utils;
 pub mod job;
+pub mod job_store;
+pub mod scheduler;
+pub mod planner;
+pub mod worker;
+pub mod event;
 pub mod command;
 pub mod app;
 pub mod data;
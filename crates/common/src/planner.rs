@@ -0,0 +1,201 @@
+//! Dividend planning
+//!
+//! A job requirement is split into dividends of the dataset from the dataset size, the cost class of the
+//! operator and the budget of one computing unit run.
+//!
+
+use crate::job::{DividendIndex, DividendSize, Requirement};
+
+/// Rough cost of processing one dataset entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostClass {
+    /// comparison of precomputed data, e.g. fingerprints
+    Light,
+    /// per entry generation of data, e.g. fingerprint generation for similarity searching
+    Medium,
+    /// per entry graph algorithms, e.g. substructure matching
+    Heavy
+}
+
+impl CostClass {
+    pub fn time_per_entry(&self) -> std::time::Duration {
+        match self {
+            Self::Light => std::time::Duration::from_micros(20),
+            Self::Medium => std::time::Duration::from_micros(200),
+            Self::Heavy => std::time::Duration::from_millis(2)
+        }
+    }
+
+    /// bytes held in memory per entry of a dividend
+    pub fn memory_per_entry(&self) -> usize {
+        match self {
+            Self::Light => 1024,
+            Self::Medium => 2048,
+            Self::Heavy => 4096
+        }
+    }
+}
+
+/// Budget of one computing unit run on a dividend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Budget {
+    pub duration: Option<std::time::Duration>,
+    pub memory: Option<usize>
+}
+
+impl std::default::Default for Budget {
+    /// one minute and 1 GiB per dividend
+    fn default() -> Self {
+        Self { duration: Some(std::time::Duration::from_secs(60)), memory: Some(1 << 30) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostEstimate {
+    pub entries: usize,
+    pub dividends: usize,
+    /// computing time summed over all dividends
+    pub duration: std::time::Duration,
+    /// peak memory of the largest dividend
+    pub memory: usize
+}
+
+impl std::fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} entries in {} dividends, {:.1}s computing time, {:.1} MiB per dividend", self.entries, self.dividends, self.duration.as_secs_f32(), self.memory as f32 / (1 << 20) as f32)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Planner {
+    budget: Budget
+}
+
+impl Planner {
+    pub fn new(budget: Budget) -> Self {
+        Self { budget }
+    }
+
+    /// maximum number of entries of a dividend within the budget, at least 1
+    pub fn max_dividend_size(&self, cost: &CostClass) -> DividendSize {
+        let by_duration = self.budget.duration
+            .map(|d| (d.as_nanos() / cost.time_per_entry().as_nanos()) as usize);
+        let by_memory = self.budget.memory
+            .map(|m| m / cost.memory_per_entry());
+        by_duration.into_iter()
+            .chain(by_memory)
+            .min()
+            .unwrap_or(usize::MAX)
+            .max(1)
+    }
+
+    /// dividends covering 0..size of the dataset with sizes differing by at most 1
+    pub fn dividends(&self, req: &Requirement) -> Vec<DividendIndex> {
+        self.dividends_of_size(req, req.get_dsk().size())
+    }
+
+    /// dividends of a dataset whose size is known from its loaded file, e.g. a custom dataset
+    pub fn dividends_of_size(&self, req: &Requirement, size: usize) -> Vec<DividendIndex> {
+        if size == 0 {
+            // a job without dataset entries still runs once
+            return vec![(0, 0)];
+        }
+        let count = size.div_ceil(self.max_dividend_size(&req.get_opk().cost_class()));
+        let (base, remainder) = (size / count, size % count);
+        let mut start = 0;
+        (0..count)
+            .map(|i| {
+                let end = start + base + usize::from(i < remainder);
+                let div_index = (start, end);
+                start = end;
+                div_index
+            })
+            .collect()
+    }
+
+    pub fn estimate(&self, req: &Requirement) -> CostEstimate {
+        self.estimate_of_size(req, req.get_dsk().size())
+    }
+
+    /// cost estimate over the entries of the loaded dataset
+    pub fn estimate_of_size(&self, req: &Requirement, entries: usize) -> CostEstimate {
+        let cost = req.get_opk().cost_class();
+        let dividends = self.dividends_of_size(req, entries);
+        let largest = dividends.iter().map(|(start, end)| end - start).max().unwrap_or(0);
+        CostEstimate {
+            entries,
+            dividends: dividends.len(),
+            duration: std::time::Duration::try_from_secs_f64(cost.time_per_entry().as_secs_f64() * entries as f64).unwrap_or(std::time::Duration::MAX),
+            memory: largest.saturating_mul(cost.memory_per_entry())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirement(opk: crate::kinds::Operator, dsk: crate::kinds::Dataset) -> Requirement {
+        Requirement::new("".to_string(), opk, dsk)
+    }
+
+    fn assert_exact_cover(dividends: &[DividendIndex], size: usize) {
+        assert_eq!(dividends.first().unwrap().0, 0);
+        assert_eq!(dividends.last().unwrap().1, size);
+        for pair in dividends.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        assert!(dividends.iter().all(|(start, end)| start < end));
+    }
+
+    #[test]
+    fn test_dividends() {
+        let fpk = crate::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+        let opks = [crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Operator::similarity_searching(fpk)];
+        let dsks = [crate::kinds::Dataset::Dummy, crate::kinds::Dataset::TestChembl, crate::kinds::Dataset::Chembl30, crate::kinds::Dataset::PubChem];
+        let budgets = [
+            Budget::default(),
+            Budget { duration: Some(std::time::Duration::from_secs(1)), memory: None },
+            Budget { duration: None, memory: Some(1 << 20) },
+            Budget { duration: Some(std::time::Duration::from_nanos(1)), memory: None },
+            Budget { duration: None, memory: None }
+        ];
+        for budget in budgets.iter() {
+            let planner = Planner::new(budget.to_owned());
+            for opk in opks.iter() {
+                let max_size = planner.max_dividend_size(&opk.cost_class());
+                // skip PubChem with one entry per dividend to keep the test quick
+                for dsk in dsks.iter().filter(|dsk| max_size > 1 || **dsk != crate::kinds::Dataset::PubChem) {
+                    let dividends = planner.dividends(&requirement(opk.to_owned(), dsk.to_owned()));
+                    assert_exact_cover(&dividends, dsk.size());
+                    let sizes: Vec<usize> = dividends.iter().map(|(start, end)| end - start).collect();
+                    assert!(sizes.iter().all(|s| *s <= max_size));
+                    assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_budget() {
+        let planner = Planner::new(Budget { duration: Some(std::time::Duration::from_secs(1)), memory: Some(1 << 20) });
+        // substructure matching: 500 entries per second, 256 entries per MiB
+        assert_eq!(planner.max_dividend_size(&CostClass::Heavy), 256);
+        assert_eq!(planner.dividends(&requirement(crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::TestChembl)).len(), 40);
+        assert_eq!(planner.dividends(&requirement(crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::Dummy)), vec![(0, 4)]);
+        assert_eq!(planner.dividends(&requirement(crate::kinds::Operator::ReCGenBuild, crate::kinds::Dataset::Empty)), vec![(0, 0)]);
+        let req_custom = requirement(crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::Custom("my_library".to_string()));
+        assert_eq!(planner.dividends_of_size(&req_custom, 300), vec![(0, 150), (150, 300)]);
+        assert_eq!(Planner::new(Budget { duration: None, memory: None }).max_dividend_size(&CostClass::Light), usize::MAX);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let planner = Planner::new(Budget { duration: Some(std::time::Duration::from_secs(1)), memory: Some(1 << 20) });
+        let estimate = planner.estimate(&requirement(crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::TestChembl));
+        assert_eq!(estimate, CostEstimate { entries: 10000, dividends: 40, duration: std::time::Duration::from_secs(20), memory: 250 * 4096 });
+        assert_eq!(estimate.to_string(), "10000 entries in 40 dividends, 20.0s computing time, 1.0 MiB per dividend");
+        let estimate_loaded = planner.estimate_of_size(&requirement(crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::TestChembl), 500);
+        assert_eq!(estimate_loaded, CostEstimate { entries: 500, dividends: 2, duration: std::time::Duration::from_secs(1), memory: 250 * 4096 });
+    }
+}
@@ -0,0 +1,202 @@
+//! Scheduler queue handing out the dividends of jobs to workers
+//!
+//! Jobs are ordered by priority, then by submission (FIFO). A waiting job gains one priority level
+//! for every aging interval spent in the queue, so low priority jobs are not starved.
+//!
+
+use crate::job::{Job, ID, DividendIndex};
+
+/// Work item for a worker: the job and one of its dividends
+pub type WorkItem = (Job, DividendIndex);
+
+struct Entry {
+    job: Job,
+    dividends: std::collections::VecDeque<DividendIndex>,
+    time_submit: chrono::DateTime<chrono::Utc>,
+    seq: u64
+}
+
+impl Entry {
+    fn effective_level(&self, aging: &Option<std::time::Duration>, now: &chrono::DateTime<chrono::Utc>) -> u64 {
+        let waited = (*now - self.time_submit).to_std().unwrap_or_default();
+        let bonus = match aging {
+            Some(interval) if !interval.is_zero() => (waited.as_millis() / interval.as_millis().max(1)) as u64,
+            _ => 0
+        };
+        self.job.get_priority().level() + bonus
+    }
+}
+
+pub struct Scheduler {
+    entries: Vec<Entry>,
+    aging: Option<std::time::Duration>,
+    seq: u64
+}
+
+impl Scheduler {
+    /// aging: waiting time for a job to gain one priority level, None to disable aging
+    pub fn new(aging: Option<std::time::Duration>) -> Self {
+        Self { entries: vec![], aging, seq: 0 }
+    }
+
+    pub fn push(&mut self, job: Job, dividends: Vec<DividendIndex>) {
+        self.push_at(job, dividends, chrono::Utc::now())
+    }
+
+    fn push_at(&mut self, job: Job, dividends: Vec<DividendIndex>, time_submit: chrono::DateTime<chrono::Utc>) {
+        if dividends.is_empty() {
+            return;
+        }
+        self.seq += 1;
+        self.entries.push(Entry { job, dividends: dividends.into(), time_submit, seq: self.seq });
+    }
+
+    /// next dividend of the job with the highest effective priority, the earliest submitted among equals
+    pub fn pop(&mut self) -> Option<WorkItem> {
+        self.pop_at(chrono::Utc::now(), |_| true)
+    }
+
+    /// next dividend among the jobs accepted by the filter, e.g. those of the computing units a worker serves
+    pub fn pop_for<F: Fn(&Job) -> bool>(&mut self, filter: F) -> Option<WorkItem> {
+        self.pop_at(chrono::Utc::now(), filter)
+    }
+
+    fn pop_at<F: Fn(&Job) -> bool>(&mut self, now: chrono::DateTime<chrono::Utc>, filter: F) -> Option<WorkItem> {
+        let pos = self.entries.iter()
+            .enumerate()
+            .filter(|(_, e)| filter(&e.job))
+            .max_by(|(_, e1), (_, e2)| {
+                e1.effective_level(&self.aging, &now).cmp(&e2.effective_level(&self.aging, &now))
+                    .then_with(|| e2.seq.cmp(&e1.seq))
+            })
+            .map(|(pos, _)| pos)?;
+        let entry = &mut self.entries[pos];
+        let div_index = entry.dividends.pop_front()?;
+        let item = (entry.job.to_owned(), div_index);
+        if entry.dividends.is_empty() {
+            self.entries.remove(pos);
+        }
+        Some(item)
+    }
+
+    /// remove a job with its pending dividends, e.g. when cancelled
+    pub fn remove(&mut self, id: &ID) -> Option<(Job, Vec<DividendIndex>)> {
+        let pos = self.entries.iter().position(|e| e.job.get_id() == id)?;
+        let entry = self.entries.remove(pos);
+        Some((entry.job, entry.dividends.into()))
+    }
+
+    /// number of pending dividends of a job
+    pub fn pending(&self, id: &ID) -> usize {
+        self.entries.iter()
+            .find(|e| e.job.get_id() == id)
+            .map_or(0, |e| e.dividends.len())
+    }
+
+    /// number of queued jobs
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl std::default::Default for Scheduler {
+    /// a waiting job gains one priority level every 10 minutes
+    fn default() -> Self {
+        Self::new(Some(std::time::Duration::from_secs(600)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{Requirement, Priority};
+
+    fn job(priority: Priority) -> Job {
+        Job::new(Requirement::default().with_priority(priority))
+    }
+
+    #[test]
+    fn test_priority_and_fifo() {
+        let now = chrono::Utc::now();
+        let mut sch = Scheduler::new(None);
+        let job_scan = job(Priority::Low);
+        let job_normal_1 = job(Priority::Normal);
+        let job_normal_2 = job(Priority::Normal);
+        let job_query = job(Priority::High);
+        sch.push_at(job_scan.clone(), vec![(0, 10), (10, 20)], now);
+        sch.push_at(job_normal_1.clone(), vec![(0, 5), (5, 10)], now);
+        sch.push_at(job_normal_2.clone(), vec![(0, 5)], now);
+        sch.push_at(job_query.clone(), vec![(0, 100)], now);
+        sch.push_at(job(Priority::High), vec![], now);
+        assert_eq!(sch.len(), 4);
+
+        let order: Vec<(&ID, DividendIndex)> = vec![
+            (job_query.get_id(), (0, 100)),
+            (job_normal_1.get_id(), (0, 5)),
+            (job_normal_1.get_id(), (5, 10)),
+            (job_normal_2.get_id(), (0, 5)),
+            (job_scan.get_id(), (0, 10)),
+            (job_scan.get_id(), (10, 20))
+        ];
+        for (id, div_index) in order {
+            let (job_popped, div_popped) = sch.pop_at(now, |_| true).unwrap();
+            assert_eq!(job_popped.get_id(), id);
+            assert_eq!(div_popped, div_index);
+        }
+        assert!(sch.pop_at(now, |_| true).is_none());
+        assert!(sch.is_empty());
+    }
+
+    #[test]
+    fn test_aging() {
+        let now = chrono::Utc::now();
+        let mut sch = Scheduler::new(Some(std::time::Duration::from_secs(60)));
+        let job_old = job(Priority::Low);
+        let job_new = job(Priority::Normal);
+        sch.push_at(job_old.clone(), vec![(0, 10), (10, 20)], now - chrono::Duration::seconds(90));
+        sch.push_at(job_new.clone(), vec![(0, 10)], now);
+        // equal effective levels after one interval, the earlier submission goes first
+        assert_eq!(sch.pop_at(now, |_| true).unwrap().0.get_id(), job_old.get_id());
+        let job_query = job(Priority::High);
+        sch.push_at(job_query.clone(), vec![(0, 10)], now);
+        assert_eq!(sch.pop_at(now, |_| true).unwrap().0.get_id(), job_query.get_id());
+        // 30 seconds later the low priority job has waited two intervals, one level above the normal one
+        let later = now + chrono::Duration::seconds(30);
+        assert_eq!(sch.pop_at(later, |_| true).unwrap().0.get_id(), job_old.get_id());
+        assert_eq!(sch.pop_at(later, |_| true).unwrap().0.get_id(), job_new.get_id());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut sch = Scheduler::default();
+        let job_1 = job(Priority::Normal);
+        let job_2 = job(Priority::Normal);
+        sch.push(job_1.clone(), vec![(0, 5), (5, 10), (10, 15)]);
+        sch.push(job_2.clone(), vec![(0, 5)]);
+        assert_eq!(sch.pop().unwrap().1, (0, 5));
+        assert_eq!(sch.pending(job_1.get_id()), 2);
+        let (job_removed, dividends) = sch.remove(job_1.get_id()).unwrap();
+        assert_eq!(job_removed.get_id(), job_1.get_id());
+        assert_eq!(dividends, vec![(5, 10), (10, 15)]);
+        assert!(sch.remove(job_1.get_id()).is_none());
+        assert_eq!(sch.pending(job_1.get_id()), 0);
+        assert_eq!(sch.pop().unwrap().0.get_id(), job_2.get_id());
+    }
+
+    #[test]
+    fn test_pop_for() {
+        let mut sch = Scheduler::new(None);
+        let job_dummy = Job::new(Requirement::new("".to_string(), crate::kinds::Operator::OpenBabelSSMatching, crate::kinds::Dataset::Dummy).with_priority(Priority::High));
+        let job_chembl = job(Priority::Normal);
+        sch.push(job_dummy.clone(), vec![(0, 4)]);
+        sch.push(job_chembl.clone(), vec![(0, 5)]);
+        let serves_chembl = |job: &Job| job.get_req().get_dsk() == &crate::kinds::Dataset::TestChembl;
+        assert_eq!(sch.pop_for(serves_chembl).unwrap().0.get_id(), job_chembl.get_id());
+        assert!(sch.pop_for(serves_chembl).is_none());
+        assert_eq!(sch.pop().unwrap().0.get_id(), job_dummy.get_id());
+    }
+}
@@ -13,8 +13,38 @@ pub trait TraitData {
     fn len(&self) -> usize;
 }
 
-pub trait TraitDataStore {
+pub trait TraitDataStore: Send {
     fn get_id_smiles_pairs(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex) -> Option<crate::app::chem::types::IdSmilesPairs>;
+    /// entries of a dividend in batches, independent of the store so it can be released while they are read
+    ///     a read error of the source file ends the batches
+    fn get_id_smiles_batches(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex, _batch_size: usize) -> Option<Box<dyn Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send>> {
+        self.get_id_smiles_pairs(dsk, div_index)
+            .map(|pairs| Box::new(std::iter::once(Ok(pairs))) as Box<dyn Iterator<Item = std::io::Result<crate::app::chem::types::IdSmilesPairs>> + Send>)
+    }
+    /// entries of the loaded dataset, None if unknown without reading it through
+    fn get_size(&self, _dsk: &crate::kinds::Dataset) -> Option<usize> { None }
+    /// source file of the dataset, persistent indices are invalidated when it changes
+    fn get_source_path(&self, _dsk: &crate::kinds::Dataset) -> Option<std::path::PathBuf> { None }
+    /// directory of persistent indices, None to disable them
+    fn get_index_dir(&self) -> Option<std::path::PathBuf> { None }
+}
+
+pub trait TraitJobStore {
+    /// insert a new job or replace the stored state of an existing one
+    fn put(&mut self, job: &crate::job::Job) -> Result<(), crate::job_store::JobStoreError>;
+    fn remove(&mut self, id: &crate::job::ID) -> Result<Option<crate::job::Job>, crate::job_store::JobStoreError>;
+    fn get(&self, id: &crate::job::ID) -> Option<&crate::job::Job>;
+    fn jobs(&self) -> &crate::job::Jobs;
+
+    /// finished jobs, latest first
+    fn history(&self, limit: Option<usize>) -> Vec<&crate::job::Job> {
+        let mut finished: Vec<&crate::job::Job> = self.jobs().values()
+            .filter(|job| job.get_status().is_final() && !job.is_status(crate::job::Status::ErrorJobIDNotFound))
+            .collect();
+        finished.sort_by(|j1, j2| j2.get_time_start().cmp(j1.get_time_start()).then_with(|| j1.get_id().cmp(j2.get_id())));
+        finished.truncate(limit.unwrap_or(usize::MAX));
+        finished
+    }
 }
 
 pub trait TraitOperator {
@@ -25,8 +55,10 @@ pub trait TraitOperator {
 
     fn new(opk: &crate::kinds::Operator) -> Self;
     fn get_kind(&self) -> crate::kinds::Operator;
-    fn prepare_data(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>) -> Option<Self::DataType>;
-    fn compute(&self, input: &Self::InputType, data: &Self::DataType, div_index: &crate::job::DividendIndex) -> Self::OutputType;
+    /// stops early with the entries prepared so far once the token is stopped
+    fn prepare_data(&self, dsk: &crate::kinds::Dataset, div_index: &crate::job::DividendIndex, ds: std::sync::Arc<std::sync::Mutex<dyn TraitDataStore>>, token: &crate::job::CancelToken) -> Result<Self::DataType, crate::job::JobError>;
+    /// stops early with the output gathered so far once the token is stopped
+    fn compute(&self, input: &Self::InputType, data: &Self::DataType, div_index: &crate::job::DividendIndex, token: &crate::job::CancelToken) -> Result<Self::OutputType, crate::job::JobError>;
     fn report(&self, job_id: crate::job::ID, input: Self::InputType, data: &Self::DataType, output: Self::OutputType) -> Self::ReportType;
 }
 
@@ -39,6 +71,8 @@ pub trait TraitOutput: Serialization {
     fn clear(&mut self);
     fn append(&mut self, other: &mut Self);
     fn len(&self) -> usize;
+    /// no further dividend can change the output, the job may stop early
+    fn is_complete(&self) -> bool { false }
 }
 
 pub trait TraitReport: Serialization {
@@ -8,4 +8,13 @@ const ALPHABET: [char; 36] = [
 
 pub fn generate_id(length: usize) -> String {
     nanoid::nanoid!(length, &ALPHABET)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    /// new directory under the system temporary directory, shared by the tests of the crate
+    pub(crate) fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("chiral_{}", super::generate_id(8)));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
@@ -0,0 +1,69 @@
+//! Coordinator/worker protocol
+//!
+//! Remote workers register the computing units they serve, lease dividends of matching jobs, keep their
+//! leases alive with heartbeats and upload the serialized outputs. A lease not renewed within the lease
+//! duration expires and its dividend is handed to another worker.
+//!
+
+use serde::{Serialize, Deserialize};
+use crate::traits::{Serialization, SerializedFormat};
+use chiral_derive::Serialization;
+
+pub type WorkerID = String;
+pub type LeaseID = String;
+
+/// Computing units a worker serves
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq)]
+pub struct Registration {
+    pub cuks: Vec<crate::kinds::ComputingUnit>,
+    /// entries of the datasets loaded by the worker, sizing the jobs on custom datasets
+    #[serde(default)]
+    pub sizes: Vec<(crate::kinds::Dataset, usize)>
+}
+
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq)]
+pub struct Registered {
+    pub worker_id: WorkerID,
+    /// a worker sends heartbeats within this duration to keep its leases
+    pub lease_duration: std::time::Duration
+}
+
+/// One dividend of a job handed to a worker
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq)]
+pub struct Lease {
+    pub lease_id: LeaseID,
+    pub job_id: crate::job::ID,
+    pub req: crate::job::Requirement,
+    pub div_index: crate::job::DividendIndex
+}
+
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq, Default)]
+pub struct HeartbeatReply {
+    /// leases of the worker whose job was cancelled or timed out, their computation can stop
+    pub stopped: Vec<LeaseID>
+}
+
+/// Result of a leased dividend: the serialized output with the number of results, or the error
+#[derive(Serialize, Deserialize, Serialization, Debug, Clone, PartialEq)]
+pub struct Upload {
+    pub result: Result<(SerializedFormat, usize), crate::job::JobError>,
+    pub duration: std::time::Duration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialization() {
+        let lease = Lease { lease_id: "l1".to_string(), job_id: "j1".to_string(), req: crate::job::Requirement::default(), div_index: (0, 10) };
+        assert_eq!(Lease::ser_from(&lease.ser_to()), lease);
+        let upload = Upload { result: Ok(("{}".to_string(), 0)), duration: std::time::Duration::from_millis(1500) };
+        assert_eq!(Upload::ser_from(&upload.ser_to()), upload);
+        let error = crate::job::JobError::new(crate::job::ErrorKind::Computation, "panic").at(&(0, 10));
+        let upload = Upload { result: Err(error), duration: std::time::Duration::ZERO };
+        assert_eq!(Upload::ser_from(&upload.ser_to()), upload);
+        let registration = Registration { cuks: vec![crate::kinds::ComputingUnit::default()], sizes: vec![(crate::kinds::Dataset::Custom("my_library".to_string()), 10)] };
+        assert_eq!(Registration::ser_from(&registration.ser_to()), registration);
+    }
+}
@@ -0,0 +1,66 @@
+//! Chiral remote worker
+//!
+
+use anyhow::Context;
+
+fn command() -> clap::Command {
+    clap::Command::new("chiral-worker")
+        .about("compute dividends leased from a chiral job server")
+        .arg(
+            clap::Arg::new("coordinator")
+                .long("coordinator")
+                .default_value("http://127.0.0.1:8080"),
+        )
+        .arg(
+            clap::Arg::new("datasets")
+                .long("datasets")
+                .default_value("dummy")
+                .help("comma separated datasets to load, e.g. dummy,test_chembl,pub_chem or the name of a registered dataset"),
+        )
+        .arg(
+            clap::Arg::new("data_dir")
+                .long("data-dir")
+                .help("directory of the dataset files and of the custom datasets registry, DATASET_CHEMBL_PATH, DATASET_PUBCHEM_PATH or DATASET_CUSTOM_PATH by default"),
+        )
+        .arg(
+            clap::Arg::new("index_dir")
+                .long("index-dir")
+                .help("directory persisting the fingerprints of the dividends, computed on each job if absent"),
+        )
+        .arg(
+            clap::Arg::new("operators")
+                .long("operators")
+                .required(true)
+                .help("JSON array of the operators served on each dataset, e.g. [\"OpenBabelSSMatching\"]"),
+        )
+        .arg(
+            clap::Arg::new("poll")
+                .long("poll")
+                .default_value("1000")
+                .help("milliseconds between requests for work"),
+        )
+        .help_template(chiral_common::command::HELP_TEMPLATE)
+}
+
+fn main() -> anyhow::Result<()> {
+    let matches = command().get_matches();
+    let datasets = chiral_server::parse_datasets(matches.get_one::<String>("datasets").unwrap(), matches.get_one::<String>("data_dir"))?;
+    let operators: Vec<chiral_common::kinds::Operator> = serde_json::from_str(matches.get_one::<String>("operators").unwrap()).context("invalid operators")?;
+    let poll = matches.get_one::<String>("poll").unwrap().parse::<u64>().context("invalid poll interval")?;
+
+    let cuks: Vec<chiral_common::kinds::ComputingUnit> = operators.iter()
+        .filter(|opk| {
+            let available = chiral_apps::runner::is_available(opk);
+            if !available {
+                chiral_common::logging::warn(format!("operator {} is not available", opk).as_str());
+            }
+            available
+        })
+        .flat_map(|opk| datasets.iter().map(|dsk| chiral_common::kinds::ComputingUnit::new(opk.to_owned(), dsk.to_owned())))
+        .collect();
+    let ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(chiral_server::data_store(&datasets, matches.get_one::<String>("data_dir"), matches.get_one::<String>("index_dir"))?));
+    chiral_server::worker::RemoteWorker::new(matches.get_one::<String>("coordinator").unwrap(), cuks, ds)
+        .with_poll_interval(std::time::Duration::from_millis(poll))
+        .run()?;
+    Ok(())
+}
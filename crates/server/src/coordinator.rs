@@ -0,0 +1,159 @@
+//! Registered remote workers and their leases
+//!
+
+use chiral_common::worker::{WorkerID, LeaseID};
+
+struct WorkerEntry {
+    cuks: Vec<chiral_common::kinds::ComputingUnit>,
+    sizes: Vec<(chiral_common::kinds::Dataset, usize)>,
+    last_seen: std::time::Instant
+}
+
+struct LeaseEntry {
+    worker_id: WorkerID,
+    job_id: chiral_common::job::ID,
+    div_index: chiral_common::job::DividendIndex,
+    expires: std::time::Instant
+}
+
+pub struct Coordinator {
+    lease_duration: std::time::Duration,
+    workers: std::collections::HashMap<WorkerID, WorkerEntry>,
+    leases: std::collections::HashMap<LeaseID, LeaseEntry>
+}
+
+impl Coordinator {
+    pub fn new(lease_duration: std::time::Duration) -> Self {
+        Self { lease_duration, workers: std::collections::HashMap::new(), leases: std::collections::HashMap::new() }
+    }
+
+    pub fn lease_duration(&self) -> std::time::Duration {
+        self.lease_duration
+    }
+
+    pub fn register(&mut self, registration: chiral_common::worker::Registration) -> chiral_common::worker::Registered {
+        self.register_at(registration, std::time::Instant::now())
+    }
+
+    fn register_at(&mut self, registration: chiral_common::worker::Registration, now: std::time::Instant) -> chiral_common::worker::Registered {
+        let worker_id = format!("worker_{}", chiral_common::utils::generate_id(8));
+        self.workers.insert(worker_id.to_owned(), WorkerEntry { cuks: registration.cuks, sizes: registration.sizes, last_seen: now });
+        chiral_common::worker::Registered { worker_id, lease_duration: self.lease_duration }
+    }
+
+    /// computing units served by a registered worker
+    pub fn cuks(&self, worker_id: &WorkerID) -> Option<&Vec<chiral_common::kinds::ComputingUnit>> {
+        self.workers.get(worker_id).map(|w| &w.cuks)
+    }
+
+    /// whether any registered worker serves the computing unit
+    pub fn serves(&self, cuk: &chiral_common::kinds::ComputingUnit) -> bool {
+        self.workers.values().any(|w| w.cuks.contains(cuk))
+    }
+
+    /// entries of the dataset loaded by a registered worker
+    pub fn size(&self, dsk: &chiral_common::kinds::Dataset) -> Option<usize> {
+        self.workers.values()
+            .flat_map(|w| w.sizes.iter())
+            .find(|(d, _)| d == dsk)
+            .map(|(_, size)| *size)
+    }
+
+    pub fn grant(&mut self, worker_id: &WorkerID, job_id: &chiral_common::job::ID, div_index: &chiral_common::job::DividendIndex) -> LeaseID {
+        self.grant_at(worker_id, job_id, div_index, std::time::Instant::now())
+    }
+
+    fn grant_at(&mut self, worker_id: &WorkerID, job_id: &chiral_common::job::ID, div_index: &chiral_common::job::DividendIndex, now: std::time::Instant) -> LeaseID {
+        let lease_id = format!("lease_{}", chiral_common::utils::generate_id(8));
+        self.leases.insert(lease_id.to_owned(), LeaseEntry { worker_id: worker_id.to_owned(), job_id: job_id.to_owned(), div_index: div_index.to_owned(), expires: now + self.lease_duration });
+        lease_id
+    }
+
+    /// renew the worker and its leases, None for a worker unknown or expired
+    pub fn heartbeat(&mut self, worker_id: &WorkerID) -> Option<Vec<(LeaseID, chiral_common::job::ID)>> {
+        self.heartbeat_at(worker_id, std::time::Instant::now())
+    }
+
+    fn heartbeat_at(&mut self, worker_id: &WorkerID, now: std::time::Instant) -> Option<Vec<(LeaseID, chiral_common::job::ID)>> {
+        self.workers.get_mut(worker_id)?.last_seen = now;
+        let expires = now + self.lease_duration;
+        Some(self.leases.iter_mut()
+            .filter(|(_, lease)| &lease.worker_id == worker_id)
+            .map(|(lease_id, lease)| {
+                lease.expires = expires;
+                (lease_id.to_owned(), lease.job_id.to_owned())
+            })
+            .collect())
+    }
+
+    /// end a lease held by the worker, returning its job and dividend
+    pub fn release(&mut self, worker_id: &WorkerID, lease_id: &LeaseID) -> Option<(chiral_common::job::ID, chiral_common::job::DividendIndex)> {
+        match self.leases.get(lease_id) {
+            Some(lease) if &lease.worker_id == worker_id => self.leases.remove(lease_id).map(|lease| (lease.job_id, lease.div_index)),
+            _ => None
+        }
+    }
+
+    /// drop the workers without heartbeat and the expired leases, returning the dividends to reassign
+    pub fn expire(&mut self) -> Vec<(chiral_common::job::ID, chiral_common::job::DividendIndex)> {
+        self.expire_at(std::time::Instant::now())
+    }
+
+    fn expire_at(&mut self, now: std::time::Instant) -> Vec<(chiral_common::job::ID, chiral_common::job::DividendIndex)> {
+        let lease_duration = self.lease_duration;
+        self.workers.retain(|_, w| now.duration_since(w.last_seen) < lease_duration);
+        let expired: Vec<LeaseID> = self.leases.iter()
+            .filter(|(_, lease)| lease.expires <= now || !self.workers.contains_key(&lease.worker_id))
+            .map(|(lease_id, _)| lease_id.to_owned())
+            .collect();
+        expired.iter()
+            .filter_map(|lease_id| self.leases.remove(lease_id))
+            .map(|lease| (lease.job_id, lease.div_index))
+            .collect()
+    }
+}
+
+impl std::default::Default for Coordinator {
+    /// leases of 30 seconds
+    fn default() -> Self {
+        Self::new(std::time::Duration::from_secs(30))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leases() {
+        let now = std::time::Instant::now();
+        let second = std::time::Duration::from_secs(1);
+        let mut coord = Coordinator::new(10 * second);
+        let cuk = chiral_common::kinds::ComputingUnit::new(chiral_common::kinds::Operator::OpenBabelSSMatching, chiral_common::kinds::Dataset::Dummy);
+        let worker_1 = coord.register_at(chiral_common::worker::Registration { cuks: vec![cuk.to_owned()], sizes: vec![(chiral_common::kinds::Dataset::Dummy, 4)] }, now).worker_id;
+        let worker_2 = coord.register_at(chiral_common::worker::Registration { cuks: vec![], sizes: vec![] }, now).worker_id;
+        assert!(coord.serves(&cuk));
+        assert!(!coord.serves(&chiral_common::kinds::ComputingUnit::default()));
+        assert_eq!(coord.cuks(&worker_2), Some(&vec![]));
+        assert_eq!(coord.size(&chiral_common::kinds::Dataset::Dummy), Some(4));
+        assert_eq!(coord.size(&chiral_common::kinds::Dataset::TestChembl), None);
+
+        let job_id = "job".to_string();
+        let lease_1 = coord.grant_at(&worker_1, &job_id, &(0, 2), now);
+        let lease_2 = coord.grant_at(&worker_1, &job_id, &(2, 4), now);
+        assert!(coord.release(&worker_2, &lease_1).is_none());
+        assert_eq!(coord.release(&worker_1, &lease_1), Some((job_id.to_owned(), (0, 2))));
+        assert!(coord.release(&worker_1, &lease_1).is_none());
+
+        // the heartbeat keeps worker 1 and its lease, worker 2 expires
+        assert_eq!(coord.heartbeat_at(&worker_1, now + 8 * second), Some(vec![(lease_2.to_owned(), job_id.to_owned())]));
+        assert!(coord.expire_at(now + 12 * second).is_empty());
+        assert!(coord.heartbeat_at(&worker_2, now + 12 * second).is_none());
+        assert!(coord.cuks(&worker_2).is_none());
+
+        // without heartbeats, the lease of worker 1 expires with the worker
+        assert_eq!(coord.expire_at(now + 18 * second), vec![(job_id.to_owned(), (2, 4))]);
+        assert!(coord.release(&worker_1, &lease_2).is_none());
+        assert!(!coord.serves(&cuk));
+    }
+}
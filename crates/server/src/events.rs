@@ -0,0 +1,96 @@
+//! Subscribers of the progress events, per job or global
+//!
+
+struct Subscriber {
+    /// None for all the jobs
+    job_id: Option<chiral_common::job::ID>,
+    sender: std::sync::mpsc::Sender<chiral_common::event::Event>
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    seq: u64,
+    subscribers: Vec<Subscriber>
+}
+
+impl EventBus {
+    fn event(&mut self, job_id: &chiral_common::job::ID, kind: chiral_common::event::EventKind) -> chiral_common::event::Event {
+        self.seq += 1;
+        chiral_common::event::Event { seq: self.seq, job_id: job_id.to_owned(), time: chrono::Utc::now(), kind }
+    }
+
+    /// events of all jobs from now on
+    pub fn subscribe(&mut self) -> std::sync::mpsc::Receiver<chiral_common::event::Event> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.subscribers.push(Subscriber { job_id: None, sender });
+        receiver
+    }
+
+    /// the history of the job then its next events, the stream ends once the job is settled
+    pub fn subscribe_job(&mut self, job: &chiral_common::job::Job) -> std::sync::mpsc::Receiver<chiral_common::event::Event> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for kind in chiral_common::event::changes(None, job) {
+            sender.send(self.event(job.get_id(), kind)).ok();
+        }
+        if !chiral_common::event::is_settled(job) {
+            self.subscribers.push(Subscriber { job_id: Some(job.get_id().to_owned()), sender });
+        }
+        receiver
+    }
+
+    /// publish the changes of a job, dropping the subscribers gone
+    pub fn publish(&mut self, previous: Option<&chiral_common::job::Job>, job: &chiral_common::job::Job) {
+        for kind in chiral_common::event::changes(previous, job) {
+            let event = self.event(job.get_id(), kind);
+            self.subscribers.retain(|s| {
+                s.job_id.as_ref().is_some_and(|id| id != job.get_id()) || s.sender.send(event.to_owned()).is_ok()
+            });
+        }
+        if chiral_common::event::is_settled(job) {
+            self.subscribers.retain(|s| s.job_id.as_ref() != Some(job.get_id()));
+        }
+    }
+
+    /// end all the streams
+    pub fn close(&mut self) {
+        self.subscribers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bus() {
+        let mut bus = EventBus::default();
+        let mut job = chiral_common::job::Job::new(chiral_common::job::Requirement::default());
+        job.plan(vec![(0, 10)]);
+        let job_other = chiral_common::job::Job::new(chiral_common::job::Requirement::default());
+        let all = bus.subscribe();
+        let one = bus.subscribe_job(&job);
+        let dropped = bus.subscribe_job(&job);
+        drop(dropped);
+
+        bus.publish(None, &job_other);
+        let previous = job.to_owned();
+        job.start().unwrap();
+        job.dividend_done(&(0, 10), 7, std::time::Duration::from_secs(1));
+        job.complete().unwrap();
+        job.report_done();
+        bus.publish(Some(&previous), &job);
+        assert_eq!(bus.subscribers.len(), 1);
+
+        let events: Vec<chiral_common::event::Event> = one.iter().collect();
+        assert_eq!(events.iter().map(|e| e.kind.name()).collect::<Vec<&str>>(), vec!["status", "status", "dividend_done", "status", "report_ready"]);
+        assert!(events.iter().all(|e| e.job_id == *job.get_id()));
+        assert!(events.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+
+        let events: Vec<chiral_common::event::Event> = all.try_iter().collect();
+        assert_eq!(events.len(), 5);
+        assert_eq!(&events[0].job_id, job_other.get_id());
+        assert_eq!(events[2].kind, chiral_common::event::EventKind::DividendDone { div_index: (0, 10), output_len: 7, done: 1, planned: 1, hits: 7 });
+        bus.close();
+        assert!(all.recv().is_err());
+    }
+}
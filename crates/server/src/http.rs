@@ -0,0 +1,436 @@
+//! REST endpoints of the job service
+//!     POST /jobs                  submit a Requirement, returns the Job
+//!     GET  /jobs                  list the Jobs
+//!     GET  /jobs/{id}             query a Job
+//!     POST /jobs/{id}/cancel      cancel a Job
+//!     GET  /jobs/{id}/report      download the serialized Report
+//!     GET  /jobs/{id}/events      server-sent progress events of a Job, ending once it is settled
+//!     GET  /events                server-sent progress events of all the Jobs
+//!
+//! Remote workers (see chiral_common::worker):
+//!     POST /workers                               register the computing units of a worker
+//!     POST /workers/{id}/lease                    lease a dividend, 204 without work
+//!     POST /workers/{id}/heartbeat                renew the leases of the worker
+//!     POST /workers/{id}/leases/{lease_id}        upload the output of a leased dividend
+//!
+//! Bodies are the JSON forms of the Serialization trait, up to 64 MiB, errors are {"error": message}.
+//!
+
+use chiral_common::traits::Serialization;
+
+type Response = (u16, String);
+
+/// largest request body, an upload holds the serialized output of a dividend
+const MAX_BODY: u64 = 64 << 20;
+
+fn error_response(code: u16, message: &str) -> Response {
+    (code, serde_json::json!({ "error": message }).to_string())
+}
+
+impl From<crate::service::ServiceError> for Response {
+    fn from(e: crate::service::ServiceError) -> Self {
+        let code = match e {
+            crate::service::ServiceError::JobNotFound(_) | crate::service::ServiceError::WorkerNotFound(_) => 404,
+            crate::service::ServiceError::InvalidInput(..) | crate::service::ServiceError::OperatorNotAvailable(_) | crate::service::ServiceError::DatasetNotAvailable(_) => 400,
+            crate::service::ServiceError::ReportNotReady(_) | crate::service::ServiceError::LeaseExpired(_) | crate::service::ServiceError::Transition(_) => 409,
+            crate::service::ServiceError::JobStore(_) | crate::service::ServiceError::Io(_) => 500
+        };
+        error_response(code, e.to_string().as_str())
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(body: &str, what: &str) -> Result<T, Response> {
+    serde_json::from_str(body).map_err(|e| error_response(400, format!("invalid {}: {}", what, e).as_str()))
+}
+
+fn route(service: &std::sync::Arc<crate::service::Service>, method: &tiny_http::Method, url: &str, body: &str) -> Result<Response, Response> {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (method, segments.as_slice()) {
+        (tiny_http::Method::Post, ["jobs"]) => {
+            let req: chiral_common::job::Requirement = parse(body, "requirement")?;
+            Ok((201, service.submit(req)?.ser_to()))
+        }
+        (tiny_http::Method::Get, ["jobs"]) => Ok((200, serde_json::to_string(&service.list()).unwrap())),
+        (tiny_http::Method::Get, ["jobs", id]) => Ok((200, service.get(&id.to_string())?.ser_to())),
+        (tiny_http::Method::Post, ["jobs", id, "cancel"]) => Ok((200, service.cancel(&id.to_string())?.ser_to())),
+        (tiny_http::Method::Get, ["jobs", id, "report"]) => Ok((200, service.report(&id.to_string())?)),
+        (tiny_http::Method::Post, ["workers"]) => {
+            let registration: chiral_common::worker::Registration = parse(body, "registration")?;
+            Ok((201, service.register_worker(registration).ser_to()))
+        }
+        (tiny_http::Method::Post, ["workers", id, "lease"]) => match service.lease(&id.to_string())? {
+            Some(lease) => Ok((200, lease.ser_to())),
+            None => Ok((204, String::new()))
+        },
+        (tiny_http::Method::Post, ["workers", id, "heartbeat"]) => Ok((200, service.heartbeat(&id.to_string())?.ser_to())),
+        (tiny_http::Method::Post, ["workers", id, "leases", lease_id]) => {
+            let upload: chiral_common::worker::Upload = parse(body, "upload")?;
+            Ok((200, service.upload(&id.to_string(), &lease_id.to_string(), upload)?.ser_to()))
+        }
+        _ => Err(error_response(404, format!("no endpoint {} {}", method, path).as_str()))
+    }
+}
+
+/// interval of the comments keeping an idle event stream open
+const KEEP_ALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
+fn subscription(service: &crate::service::Service, method: &tiny_http::Method, url: &str) -> Option<Result<std::sync::mpsc::Receiver<chiral_common::event::Event>, Response>> {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (method, segments.as_slice()) {
+        (tiny_http::Method::Get, ["events"]) => Some(service.subscribe(None).map_err(Response::from)),
+        (tiny_http::Method::Get, ["jobs", id, "events"]) => Some(service.subscribe(Some(&id.to_string())).map_err(Response::from)),
+        _ => None
+    }
+}
+
+/// write the events as server-sent events in HTTP chunks, until the stream ends or the client leaves
+fn stream_events(request: tiny_http::Request, events: std::sync::mpsc::Receiver<chiral_common::event::Event>) {
+    fn write_chunk(writer: &mut dyn std::io::Write, data: &str) -> std::io::Result<()> {
+        write!(writer, "{:x}\r\n{}\r\n", data.len(), data)?;
+        writer.flush()
+    }
+
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\r\n";
+    if writer.write_all(head.as_bytes()).and_then(|_| writer.flush()).is_err() {
+        return;
+    }
+    loop {
+        let data = match events.recv_timeout(KEEP_ALIVE) {
+            Ok(event) => format!("id: {}\nevent: {}\ndata: {}\n\n", event.seq, event.kind.name(), event.ser_to()),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => ": keep-alive\n\n".to_string(),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break
+        };
+        if write_chunk(&mut writer, &data).is_err() {
+            return;
+        }
+    }
+    write_chunk(&mut writer, "").ok();
+}
+
+/// body of a request up to the limit, 413 beyond it
+fn read_body<R: std::io::Read>(reader: R, limit: u64) -> Result<String, Response> {
+    use std::io::Read;
+    let mut body = String::new();
+    reader.take(limit + 1).read_to_string(&mut body).map_err(|e| error_response(400, e.to_string().as_str()))?;
+    if body.len() as u64 > limit {
+        return Err(error_response(413, format!("request body larger than {} bytes", limit).as_str()));
+    }
+    Ok(body)
+}
+
+fn handle(service: &std::sync::Arc<crate::service::Service>, mut request: tiny_http::Request) {
+    let response = match request.body_length() {
+        Some(len) if len as u64 > MAX_BODY => error_response(413, format!("request body larger than {} bytes", MAX_BODY).as_str()),
+        _ => read_body(request.as_reader(), MAX_BODY)
+            .and_then(|body| route(service, request.method(), request.url(), &body))
+            .unwrap_or_else(|e| e)
+    };
+    respond(request, response);
+}
+
+fn respond(request: tiny_http::Request, (code, content): Response) {
+    let header = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    let response = tiny_http::Response::from_string(content)
+        .with_status_code(code)
+        .with_header(header);
+    if let Err(e) = request.respond(response) {
+        chiral_common::logging::warn(format!("http response not sent: {}", e).as_str());
+    }
+}
+
+/// HTTP server with the worker threads of the job service
+pub struct ApiServer {
+    http: std::sync::Arc<tiny_http::Server>,
+    service: std::sync::Arc<crate::service::Service>,
+    threads: Vec<std::thread::JoinHandle<()>>
+}
+
+impl ApiServer {
+    /// listen on the address, e.g. "127.0.0.1:0" for a free port
+    pub fn start(address: &str, service: crate::service::Service, workers: usize) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let http = std::sync::Arc::new(tiny_http::Server::http(address)?);
+        let service = std::sync::Arc::new(service);
+        let mut threads = service.spawn_workers(workers);
+        threads.push(service.spawn_reaper());
+        let (http_listener, service_listener) = (http.clone(), service.clone());
+        threads.push(std::thread::spawn(move || {
+            for request in http_listener.incoming_requests() {
+                match subscription(&service_listener, request.method(), request.url()) {
+                    Some(Ok(events)) => { std::thread::spawn(move || stream_events(request, events)); }
+                    Some(Err(response)) => respond(request, response),
+                    None => handle(&service_listener, request)
+                }
+            }
+        }));
+        Ok(Self { http, service, threads })
+    }
+
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.http.server_addr().to_ip().expect("server listening on TCP")
+    }
+
+    pub fn service(&self) -> &crate::service::Service {
+        &self.service
+    }
+
+    /// stop accepting requests and wait for the workers to finish their running dividends
+    pub fn stop(self) {
+        self.service.shutdown();
+        self.http.unblock();
+        for thread in self.threads {
+            thread.join().ok();
+        }
+    }
+
+    /// serve until the process ends
+    pub fn join(self) {
+        for thread in self.threads {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    #[test]
+    fn test_end_to_end() {
+        let dir = temp_dir();
+        let server = start_dummy_server(&dir, 2);
+        let base = format!("http://{}", server.address());
+
+        let req = similarity_requirement("CC(=O)Nc1ccc(O)cc1");
+        let response = ureq::post(format!("{}/jobs", base).as_str()).send_string(&req.ser_to()).unwrap();
+        assert_eq!(response.status(), 201);
+        let job = chiral_common::job::Job::ser_from(&response.into_string().unwrap());
+        assert_eq!(job.get_req(), &req);
+        assert_eq!(job.get_progress().get_planned().len(), 4);
+
+        let job = wait_final(&base, job.get_id());
+        assert!(job.is_status(chiral_common::job::Status::Completed));
+        assert_eq!(job.get_progress().get_done().len(), 4);
+        // dividend outputs are saved next to the report, not in the job
+        let output_dir = server.service().output_dir(job.get_id());
+        assert!(job.get_progress().get_planned().iter().all(|div_index| chiral_common::job::dividend_output_path(&output_dir, div_index).exists()));
+        assert!(!job.ser_to().contains("label_4"));
+        let report_ser = ureq::get(format!("{}/jobs/{}/report", base, job.get_id()).as_str()).call().unwrap().into_string().unwrap();
+        let report = chiral_common::app::chem::openbabel::similarity::Report::ser_from(&report_ser);
+        assert_eq!(&report.job_id, job.get_id());
+        assert_eq!(report.output.results.len(), 2);
+        assert_eq!(report.output.results[0], (1.0, "label_4".to_string()));
+
+        let jobs: Vec<chiral_common::job::Job> = serde_json::from_str(&ureq::get(format!("{}/jobs", base).as_str()).call().unwrap().into_string().unwrap()).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(status_code(ureq::post(format!("{}/jobs/{}/cancel", base, job.get_id()).as_str()).call()), 409);
+
+        // an invalid query SMILES fails the job without retries, and without report
+        let job_bad = submit(&base, &similarity_requirement("C1CC"));
+        let mut status = job_bad.get_status().to_owned();
+        for _ in 0..200 {
+            status = server.service().get(job_bad.get_id()).unwrap().get_status().to_owned();
+            if status.is_final() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert!(matches!(status, chiral_common::job::Status::Failed(error) if error.kind == chiral_common::job::ErrorKind::InvalidInput));
+        assert_eq!(status_code(ureq::get(format!("{}/jobs/{}/report", base, job_bad.get_id()).as_str()).call()), 409);
+
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_hits() {
+        let dir = temp_dir();
+        // one entry per dividend for every operator, a single worker computes them in order
+        let server = start_dummy_server_with(&dir, 1, chiral_common::planner::Budget { duration: Some(std::time::Duration::from_nanos(1)), memory: None });
+        let base = format!("http://{}", server.address());
+        let input = chiral_common::app::chem::openbabel::substructure::Input { max_hits: Some(1), ..chiral_common::app::chem::openbabel::substructure::Input::new("c1ccccc1".to_string()) };
+        let req = chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::OpenBabelSSMatching, chiral_common::kinds::Dataset::Dummy);
+        let job = submit(&base, &req);
+        assert_eq!(job.get_progress().get_planned().len(), 4);
+
+        // every entry matches, the job completes after its first dividend without computing the others
+        let job = wait_final(&base, job.get_id());
+        assert!(job.is_status(chiral_common::job::Status::Completed));
+        assert_eq!(job.get_progress().get_done().len(), 1);
+        assert_eq!(job.get_progress().pending().len(), 3);
+        let report_ser = ureq::get(format!("{}/jobs/{}/report", base, job.get_id()).as_str()).call().unwrap().into_string().unwrap();
+        assert_eq!(chiral_common::app::chem::openbabel::substructure::Report::ser_from(&report_ser).output.results.len(), 1);
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_custom_dataset() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("library.smi"), "CCO ethanol\nCC(=O)Nc1ccc(O)cc1 paracetamol\nc1ccccc1 benzene\n").unwrap();
+        let dsk = chiral_common::kinds::Dataset::Custom("my_library".to_string());
+        let data_dir = dir.to_str().unwrap().to_string();
+        let index_dir = dir.join("index").to_str().unwrap().to_string();
+        let mut registry = chiral_common::data::DatasetRegistry::open(&crate::registry_path(Some(&data_dir)).unwrap()).unwrap();
+        registry.register(chiral_common::data::CustomDataset::new("my_library", dir.join("library.smi"), chiral_common::data::Format::Smi, 1, 0)).unwrap();
+        assert!(crate::data_store(&[chiral_common::kinds::Dataset::Custom("unknown".to_string())], Some(&data_dir), None).is_err());
+        assert_eq!(crate::parse_datasets("dummy,my_library", Some(&data_dir)).unwrap(), vec![chiral_common::kinds::Dataset::Dummy, dsk.to_owned()]);
+        assert!(crate::parse_datasets("dummy,my_libary", Some(&data_dir)).is_err());
+
+        // SD files are loaded with the IDs from their title lines
+        let record = |title: &str, element: &str| format!("{}\n  test\n\n  1  0  0  0  0  0  0  0  0  0999 V2000\n    0.0000    0.0000    0.0000 {:<3} 0  0  0  0  0  0  0  0  0  0  0  0\nM  END\n$$$$\n", title, element);
+        std::fs::write(dir.join("library.sdf"), format!("{}{}", record("methane", "C"), record("water", "O"))).unwrap();
+        registry.register(chiral_common::data::CustomDataset::new_sdf("my_sdf", dir.join("library.sdf"))).unwrap();
+        let dsk_sdf = chiral_common::kinds::Dataset::Custom("my_sdf".to_string());
+        let store = crate::data_store(&[dsk_sdf.to_owned()], Some(&data_dir), None).unwrap();
+        assert_eq!(chiral_common::traits::TraitDataStore::get_size(&store, &dsk_sdf), Some(2));
+        let div_index: chiral_common::job::DividendIndex = (0, 2);
+        let pairs = chiral_common::traits::TraitDataStore::get_id_smiles_pairs(&store, &dsk_sdf, &div_index).unwrap();
+        assert_eq!(pairs, (vec!["methane".to_string(), "water".to_string()], vec!["C".to_string(), "O".to_string()]));
+
+        let ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(crate::data_store(&[dsk.to_owned()], Some(&data_dir), Some(&index_dir)).unwrap()));
+        let planner = chiral_common::planner::Planner::new(chiral_common::planner::Budget { duration: None, memory: Some(2048) });
+        let service = crate::service::Service::new(Box::<chiral_common::job_store::MemoryJobStore>::default(), ds, vec![dsk.to_owned()], planner, dir.to_path_buf());
+        let server = ApiServer::start("127.0.0.1:0", service, 2).unwrap();
+        let base = format!("http://{}", server.address());
+
+        // sized by the loaded file, one entry per dividend
+        let req = similarity_requirement("CC(=O)Nc1ccc(O)cc1");
+        let req = chiral_common::job::Requirement::new(req.get_ji().to_owned(), req.get_opk().to_owned(), chiral_common::kinds::Dataset::Custom("my_library".to_string()));
+        let job = submit(&base, &req);
+        assert_eq!(job.get_progress().get_planned().len(), 3);
+        let job = wait_final(&base, job.get_id());
+        let report_ser = ureq::get(format!("{}/jobs/{}/report", base, job.get_id()).as_str()).call().unwrap().into_string().unwrap();
+        let report = chiral_common::app::chem::openbabel::similarity::Report::ser_from(&report_ser);
+        assert_eq!(report.output.results[0], (1.0, "paracetamol".to_string()));
+        // the fingerprints of every dividend are persisted
+        assert_eq!(std::fs::read_dir(&index_dir).unwrap().count(), 3);
+
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_chembl_dataset() {
+        let dir = temp_dir();
+        let data_dir = dir.to_str().unwrap().to_string();
+        let dsk = chiral_common::kinds::Dataset::TestChembl;
+        assert!(crate::data_store(&[dsk.to_owned()], Some(&data_dir), None).is_err());
+        let path = dir.join(dsk.filename().unwrap());
+        std::fs::write(&path, "chembl_id\tcanonical_smiles\tstandard_inchi\tstandard_inchi_key\nCHEMBL1\tCCO\tInChI=1S/C2H6O\tLFQSCWFLJHTTHZ-UHFFFAOYSA-N\nCHEMBL2\tCCN\t\t\n").unwrap();
+        // streamed from the indexed file instead of loaded
+        let store = crate::data_store(&[dsk.to_owned()], Some(&data_dir), None).unwrap();
+        assert_eq!(chiral_common::traits::TraitDataStore::get_size(&store, &dsk), Some(2));
+        assert_eq!(chiral_common::traits::TraitDataStore::get_source_path(&store, &dsk), Some(path));
+        let pairs = chiral_common::traits::TraitDataStore::get_id_smiles_pairs(&store, &dsk, &(1, 2)).unwrap();
+        assert_eq!(pairs, (vec!["CHEMBL2".to_string()], vec!["CCN".to_string()]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = temp_dir();
+        let server = start_dummy_server(&dir, 0);
+        let base = format!("http://{}", server.address());
+        assert_eq!(status_code(ureq::get(format!("{}/jobs/unknown", base).as_str()).call()), 404);
+        assert_eq!(status_code(ureq::get(format!("{}/jobs/unknown/report", base).as_str()).call()), 404);
+        assert_eq!(status_code(ureq::post(format!("{}/jobs/unknown/cancel", base).as_str()).call()), 404);
+        assert_eq!(status_code(ureq::delete(format!("{}/jobs", base).as_str()).call()), 404);
+        assert_eq!(status_code(ureq::post(format!("{}/jobs", base).as_str()).send_string("{}")), 400);
+        let req = similarity_requirement("CC");
+        let req_chembl = chiral_common::job::Requirement::new(req.get_ji().to_owned(), req.get_opk().to_owned(), chiral_common::kinds::Dataset::Chembl30);
+        assert_eq!(status_code(ureq::post(format!("{}/jobs", base).as_str()).send_string(&req_chembl.ser_to())), 400);
+        let req_invalid = chiral_common::job::Requirement::new("c1ccccc1".to_string(), req.get_opk().to_owned(), chiral_common::kinds::Dataset::Dummy);
+        assert_eq!(status_code(ureq::post(format!("{}/jobs", base).as_str()).send_string(&req_invalid.ser_to())), 400);
+        let req_recgen = chiral_common::job::Requirement::new("{\"mol\":\"CC\"}".to_string(), chiral_common::kinds::Operator::ReCGenBuild, chiral_common::kinds::Dataset::Dummy);
+        let response = ureq::post(format!("{}/jobs", base).as_str()).send_string(&req_recgen.ser_to()).unwrap_err().into_response().unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(response.into_string().unwrap().contains("not available"));
+        // OpenBabel does not generate a Rust fingerprint, the job is rejected at submission
+        let req_ob = chiral_common::job::Requirement::new(req.get_ji().to_owned(), chiral_common::kinds::Operator::OpenBabelSimilaritySearching(chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048)), chiral_common::kinds::Dataset::Dummy);
+        assert_eq!(status_code(ureq::post(format!("{}/jobs", base).as_str()).send_string(&req_ob.ser_to())), 400);
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // bodies are read up to the limit
+        assert_eq!(read_body(std::io::Cursor::new("{}"), 2), Ok("{}".to_string()));
+        assert_eq!(read_body(std::io::Cursor::new("{ }"), 2).unwrap_err().0, 413);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let dir = temp_dir();
+        // without workers the job stays queued
+        let server = start_dummy_server(&dir, 0);
+        let base = format!("http://{}", server.address());
+        let job = submit(&base, &similarity_requirement("CC(=O)Nc1ccc(O)cc1"));
+        let job_cancelled = chiral_common::job::Job::ser_from(&ureq::post(format!("{}/jobs/{}/cancel", base, job.get_id()).as_str()).call().unwrap().into_string().unwrap());
+        assert!(job_cancelled.is_status(chiral_common::job::Status::Cancelled));
+        assert!(job_cancelled.is_report_ready());
+        let report = chiral_common::app::chem::openbabel::similarity::Report::ser_from(&ureq::get(format!("{}/jobs/{}/report", base, job.get_id()).as_str()).call().unwrap().into_string().unwrap());
+        assert!(report.output.results.is_empty());
+        assert_eq!(status_code(ureq::post(format!("{}/jobs/{}/cancel", base, job.get_id()).as_str()).call()), 409);
+        server.stop();
+
+        // a deadline already passed times the job out before any computation
+        let server = start_dummy_server(&dir, 1);
+        let base = format!("http://{}", server.address());
+        let job_late = submit(&base, &similarity_requirement("CC(=O)Nc1ccc(O)cc1").with_deadline(chrono::Utc::now()));
+        let job_late = wait_final(&base, job_late.get_id());
+        assert!(job_late.is_status(chiral_common::job::Status::TimedOut));
+        assert!(job_late.get_progress().get_done().is_empty());
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// events of a server-sent event stream, until it ends or the predicate is met
+    fn read_events(response: ureq::Response, until: impl Fn(&chiral_common::event::Event) -> bool) -> Vec<chiral_common::event::Event> {
+        let mut events = vec![];
+        for line in std::io::BufRead::lines(std::io::BufReader::new(response.into_reader())) {
+            if let Some(data) = line.unwrap().strip_prefix("data: ") {
+                let event = chiral_common::event::Event::ser_from(&data.to_string());
+                let last = until(&event);
+                events.push(event);
+                if last {
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_events() {
+        let dir = temp_dir();
+        let server = start_dummy_server(&dir, 2);
+        let base = format!("http://{}", server.address());
+        assert_eq!(status_code(ureq::get(format!("{}/jobs/missing/events", base).as_str()).call()), 404);
+
+        let response_all = ureq::get(format!("{}/events", base).as_str()).call().unwrap();
+        assert_eq!(response_all.content_type(), "text/event-stream");
+        let job = submit(&base, &similarity_requirement("CC(=O)Nc1ccc(O)cc1"));
+        let job_id = job.get_id().to_owned();
+        let all = std::thread::spawn(move || read_events(response_all, |event| event.job_id == job_id && event.kind == chiral_common::event::EventKind::ReportReady));
+
+        // the stream of a job replays its history and ends once the report is ready
+        let events = read_events(ureq::get(format!("{}/jobs/{}/events", base, job.get_id()).as_str()).call().unwrap(), |_| false);
+        let statuses: Vec<&chiral_common::job::Status> = events.iter()
+            .filter_map(|event| match &event.kind { chiral_common::event::EventKind::Status { status } => Some(status), _ => None })
+            .collect();
+        assert_eq!(statuses, vec![&chiral_common::job::Status::Created, &chiral_common::job::Status::Processing, &chiral_common::job::Status::Completed]);
+        let done: Vec<&chiral_common::event::Event> = events.iter().filter(|event| event.kind.name() == "dividend_done").collect();
+        assert_eq!(done.len(), 4);
+        assert!(matches!(done[3].kind, chiral_common::event::EventKind::DividendDone { done: 4, planned: 4, hits: 4, .. }));
+        assert_eq!(events.last().unwrap().kind, chiral_common::event::EventKind::ReportReady);
+        assert!(events.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+
+        let events_all = all.join().unwrap();
+        assert_eq!(events_all.iter().filter(|event| event.kind.name() == "dividend_done").count(), 4);
+        assert!(events_all.iter().all(|event| &event.job_id == job.get_id()));
+        server.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
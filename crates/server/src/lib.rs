@@ -0,0 +1,96 @@
+pub mod service;
+pub mod coordinator;
+pub mod events;
+pub mod http;
+pub mod worker;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+
+use anyhow::Context;
+use std::str::FromStr;
+
+/// directory of the dataset files: data_dir, or the directory of the environment variable of the dataset
+fn dataset_dir(dsk: &chiral_common::kinds::Dataset, data_dir: Option<&String>) -> anyhow::Result<std::path::PathBuf> {
+    Ok(match (data_dir, dsk) {
+        (Some(dir), _) => std::path::PathBuf::from(dir),
+        (None, chiral_common::kinds::Dataset::Empty | chiral_common::kinds::Dataset::Dummy) => std::path::PathBuf::new(),
+        (None, _) => std::path::PathBuf::from(std::env::var(dsk.env_key()).with_context(|| format!("dataset {} requires --data-dir or {}", dsk, dsk.env_key()))?)
+    })
+}
+
+/// registry file of the custom datasets
+pub fn registry_path(data_dir: Option<&String>) -> anyhow::Result<std::path::PathBuf> {
+    let dsk = chiral_common::kinds::Dataset::Custom(String::new());
+    Ok(dataset_dir(&dsk, data_dir)?.join(chiral_common::data::DatasetRegistry::FILENAME))
+}
+
+/// comma separated names of built-in datasets or of custom datasets in the registry, unknown names are rejected
+pub fn parse_datasets(names: &str, data_dir: Option<&String>) -> anyhow::Result<Vec<chiral_common::kinds::Dataset>> {
+    let names: Vec<&str> = names.split(',').collect();
+    // the registry is only needed for names other than the built-in ones
+    let registry = match names.iter().all(|name| chiral_common::kinds::Dataset::from_str(name).is_ok()) {
+        true => chiral_common::data::DatasetRegistry::default(),
+        false => chiral_common::data::DatasetRegistry::open(&registry_path(data_dir)?)?
+    };
+    names.iter()
+        .map(|name| registry.resolve(name).with_context(|| format!("invalid dataset {}", name)))
+        .collect()
+}
+
+/// load the datasets from data_dir, or from the directory of their environment variable,
+/// ChEMBL and PubChem dividends are streamed from the file instead and custom datasets resolved in the dataset registry
+/// the fingerprints of the dividends are persisted in index_dir, if any
+pub fn data_store(datasets: &[chiral_common::kinds::Dataset], data_dir: Option<&String>, index_dir: Option<&String>) -> anyhow::Result<chiral_common::data::DataStore> {
+    let mut store = match index_dir {
+        Some(dir) => chiral_common::data::DataStore::new().with_index_dir(std::path::PathBuf::from(dir)),
+        None => chiral_common::data::DataStore::new()
+    };
+    for dsk in datasets.iter() {
+        let dir = dataset_dir(dsk, data_dir)?;
+        if let chiral_common::kinds::Dataset::Custom(name) = dsk {
+            let registry = chiral_common::data::DatasetRegistry::open(&dir.join(chiral_common::data::DatasetRegistry::FILENAME))?;
+            let dataset = registry.get(name)?;
+            if dataset.format == chiral_common::data::Format::Sdf {
+                // records span several lines, the entries are loaded
+                chiral_common::logging::info(format!("loading dataset {} from {:?}", dsk, dataset.path).as_str());
+                let batches = chiral_common::data::SourceSDF::from(dataset).batches(chiral_common::data::BATCH_SIZE)?.collect::<std::io::Result<_>>()?;
+                store.insert_doc_from(dsk.to_owned(), batches, dataset.path.to_owned());
+                continue;
+            }
+            let sd = chiral_common::data::SourceDelimited::from(dataset);
+            if !chiral_common::data::is_seekable(&dataset.path) {
+                // gzip files cannot seek, the entries are loaded instead
+                chiral_common::logging::info(format!("loading dataset {} from {:?}", dsk, dataset.path).as_str());
+                store.insert_doc_from(dsk.to_owned(), sd.batches(chiral_common::data::BATCH_SIZE)?.collect::<std::io::Result<_>>()?, dataset.path.to_owned());
+            } else {
+                let size = store.insert_indexed(dsk.to_owned(), sd)?;
+                chiral_common::logging::info(format!("streaming dataset {} of {} entries from {:?}", dsk, size, dataset.path).as_str());
+            }
+            continue;
+        }
+        if let (chiral_common::kinds::Dataset::TestChembl | chiral_common::kinds::Dataset::Chembl30, Some(filename)) = (dsk, dsk.filename()) {
+            let path = dir.join(filename);
+            let mut sd = chiral_common::data::SourceDelimited::chembl();
+            sd.set_path(path.as_os_str());
+            chiral_common::logging::info(format!("indexing dataset {} from {:?}", dsk, path).as_str());
+            let size = store.insert_indexed(dsk.to_owned(), sd).with_context(|| format!("dataset {} file {:?}", dsk, path))?;
+            chiral_common::logging::info(format!("streaming dataset {} of {} entries from {:?}", dsk, size, path).as_str());
+            continue;
+        }
+        if let (chiral_common::kinds::Dataset::PubChem, Some(filename)) = (dsk, dsk.filename()) {
+            let path = dir.join(filename);
+            chiral_common::logging::info(format!("indexing dataset {} from {:?}", dsk, path).as_str());
+            store.insert_file(dsk.to_owned(), path.to_owned()).with_context(|| format!("dataset {} file {:?}", dsk, path))?;
+            let size = chiral_common::traits::TraitDataStore::get_size(&store, dsk).unwrap_or_default();
+            chiral_common::logging::info(format!("streaming dataset {} of {} entries from {:?}", dsk, size, path).as_str());
+            continue;
+        }
+        chiral_common::logging::info(format!("loading dataset {}", dsk).as_str());
+        let doc = chiral_common::data::load_from_path(dsk, &dir);
+        match dsk.filename() {
+            Some(filename) => store.insert_doc_from(dsk.to_owned(), doc, dir.join(filename)),
+            None => store.insert_doc(dsk.to_owned(), doc)
+        }
+    }
+    Ok(store)
+}
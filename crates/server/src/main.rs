@@ -0,0 +1,192 @@
+//! Chiral job API server
+//!
+
+use std::str::FromStr;
+use anyhow::Context;
+
+fn command() -> clap::Command {
+    clap::Command::new("chiral-server")
+        .about("serve the chiral job API over HTTP")
+        .arg(
+            clap::Arg::new("address")
+                .long("address")
+                .default_value("127.0.0.1:8080"),
+        )
+        .arg(
+            clap::Arg::new("datasets")
+                .long("datasets")
+                .default_value("dummy")
+                .help("comma separated datasets to load, e.g. dummy,test_chembl,pub_chem or the name of a registered dataset"),
+        )
+        .arg(
+            clap::Arg::new("data_dir")
+                .long("data-dir")
+                .help("directory of the dataset files and of the custom datasets registry, DATASET_CHEMBL_PATH, DATASET_PUBCHEM_PATH or DATASET_CUSTOM_PATH by default"),
+        )
+        .arg(
+            clap::Arg::new("index_dir")
+                .long("index-dir")
+                .help("directory persisting the fingerprints of the dividends, computed on each job if absent, and the decompressed PubChem file, required by PubChem"),
+        )
+        .arg(
+            clap::Arg::new("store_dir")
+                .long("store-dir")
+                .help("directory persisting the jobs, in memory only if absent"),
+        )
+        .arg(
+            clap::Arg::new("report_dir")
+                .long("report-dir")
+                .help("directory of the reports, the system temporary directory by default"),
+        )
+        .arg(
+            clap::Arg::new("workers")
+                .long("workers")
+                .default_value("4")
+                .help("worker threads of the server, 0 to leave the jobs to remote workers"),
+        )
+        .arg(
+            clap::Arg::new("lease")
+                .long("lease")
+                .default_value("30")
+                .help("seconds for remote workers to renew their leases"),
+        )
+        .subcommand(
+            clap::Command::new("dataset")
+                .about("manage the registry of the custom datasets, in --data-dir or DATASET_CUSTOM_PATH")
+                .subcommand_required(true)
+                .arg(
+                    clap::Arg::new("data_dir")
+                        .long("data-dir"),
+                )
+                .subcommand(
+                    clap::Command::new("register")
+                        .about("register a file of SMILES or an SD file under a name, e.g. --name my_library --path library.csv --format csv --id-column id --smiles-column smiles")
+                        .arg(clap::Arg::new("name").long("name").required(true))
+                        .arg(clap::Arg::new("path").long("path").required(true))
+                        .arg(
+                            clap::Arg::new("format")
+                                .long("format")
+                                .default_value("smi")
+                                .help("smi, csv, tsv or sdf"),
+                        )
+                        .arg(
+                            clap::Arg::new("id_column")
+                                .long("id-column")
+                                .default_value("1")
+                                .help("column of the entry IDs, by name in the header or zero based index"),
+                        )
+                        .arg(
+                            clap::Arg::new("smiles_column")
+                                .long("smiles-column")
+                                .default_value("0")
+                                .help("column of the SMILES, by name in the header or zero based index"),
+                        )
+                        .arg(
+                            clap::Arg::new("header")
+                                .long("header")
+                                .action(clap::ArgAction::SetTrue)
+                                .conflicts_with("no_header")
+                                .help("the first line holds the column names, detected by default"),
+                        )
+                        .arg(
+                            clap::Arg::new("no_header")
+                                .long("no-header")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("the first line is an entry"),
+                        )
+                        .arg(
+                            clap::Arg::new("id_field")
+                                .long("id-field")
+                                .help("SD files: data field of the entry IDs, the title line by default"),
+                        )
+                        .arg(
+                            clap::Arg::new("sdf_smiles_field")
+                                .long("smiles-field")
+                                .help("SD files: data field of the SMILES, written from the connection table by default"),
+                        ),
+                )
+                .subcommand(clap::Command::new("list").about("list the registered datasets"))
+                .subcommand(
+                    clap::Command::new("remove")
+                        .about("remove a dataset from the registry, its file is kept")
+                        .arg(clap::Arg::new("name").long("name").required(true)),
+                )
+        )
+        .args_conflicts_with_subcommands(true)
+        .help_template(chiral_common::command::HELP_TEMPLATE)
+}
+
+fn dataset(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let mut registry = chiral_common::data::DatasetRegistry::open(&chiral_server::registry_path(matches.get_one::<String>("data_dir"))?)?;
+    match matches.subcommand() {
+        Some(("register", matches)) => {
+            let format = chiral_common::data::Format::from_str(matches.get_one::<String>("format").unwrap()).context("invalid format")?;
+            let id_column = chiral_common::data::Column::from_str(matches.get_one::<String>("id_column").unwrap())?;
+            let smiles_column = chiral_common::data::Column::from_str(matches.get_one::<String>("smiles_column").unwrap())?;
+            let mut dataset = chiral_common::data::CustomDataset::new(matches.get_one::<String>("name").unwrap(), std::path::PathBuf::from(matches.get_one::<String>("path").unwrap()), format, id_column, smiles_column);
+            if matches.get_flag("header") || matches.get_flag("no_header") {
+                dataset = dataset.with_header(matches.get_flag("header"));
+            }
+            if let Some(field) = matches.get_one::<String>("id_field") {
+                dataset = dataset.with_id_source(chiral_common::data::IdSource::Field(field.to_owned()));
+            }
+            if let Some(field) = matches.get_one::<String>("sdf_smiles_field") {
+                dataset = dataset.with_smiles_field(field);
+            }
+            let name = dataset.name.to_owned();
+            registry.register(dataset)?;
+            if format == chiral_common::data::Format::Sdf {
+                // malformed records are logged while counting, a read error fails the registration
+                let count = chiral_common::data::SourceSDF::from(registry.get(&name)?).entries()?.try_fold(0, |count, entry| entry.map(|_| count + 1))?;
+                chiral_common::logging::info(format!("dataset {} registered with {} entries", name, count).as_str());
+                return Ok(());
+            }
+            let sd = chiral_common::data::SourceDelimited::from(registry.get(&name)?);
+            let entries = sd.entries()?;
+            let (count, errors) = entries.fold((0, 0), |(count, errors), entry| match entry {
+                Ok(_) => (count + 1, errors),
+                Err(e) => {
+                    chiral_common::logging::warn(format!("{}", e).as_str());
+                    (count, errors + 1)
+                }
+            });
+            chiral_common::logging::info(format!("dataset {} registered with {} entries, {} malformed lines", name, count, errors).as_str());
+        }
+        Some(("list", _)) => {
+            for dataset in registry.datasets() {
+                println!("{:20} {:5} {:?}", dataset.name, dataset.format, dataset.path);
+            }
+        }
+        Some(("remove", matches)) => {
+            registry.unregister(matches.get_one::<String>("name").unwrap())?;
+        }
+        _ => unreachable!()
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let matches = command().get_matches();
+    if let Some(("dataset", matches)) = matches.subcommand() {
+        return dataset(matches);
+    }
+    let datasets = chiral_server::parse_datasets(matches.get_one::<String>("datasets").unwrap(), matches.get_one::<String>("data_dir"))?;
+    let workers = matches.get_one::<String>("workers").unwrap().parse::<usize>().context("invalid number of workers")?;
+    let lease = matches.get_one::<String>("lease").unwrap().parse::<u64>().context("invalid lease duration")?;
+    let ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(chiral_server::data_store(&datasets, matches.get_one::<String>("data_dir"), matches.get_one::<String>("index_dir"))?));
+
+    let store: Box<dyn chiral_common::traits::TraitJobStore + Send> = match matches.get_one::<String>("store_dir") {
+        Some(dir) => Box::new(chiral_common::job_store::FileJobStore::open(std::path::Path::new(dir), chiral_common::job_store::Recovery::Requeue)?),
+        None => Box::<chiral_common::job_store::MemoryJobStore>::default()
+    };
+    let report_dir = matches.get_one::<String>("report_dir").map_or_else(std::env::temp_dir, std::path::PathBuf::from);
+    std::fs::create_dir_all(&report_dir)?;
+
+    let service = chiral_server::service::Service::new(store, ds, datasets, chiral_common::planner::Planner::default(), report_dir)
+        .with_lease_duration(std::time::Duration::from_secs(lease));
+    let server = chiral_server::http::ApiServer::start(matches.get_one::<String>("address").unwrap(), service, workers)
+        .map_err(|e| anyhow::anyhow!(e))?;
+    chiral_common::logging::info(format!("chiral job API listening on http://{}", server.address()).as_str());
+    server.join();
+    Ok(())
+}
@@ -0,0 +1,441 @@
+//! Job service: accepts requirements, schedules their dividends and runs them on worker threads
+//!
+
+use chiral_common::traits::TraitJobStore;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ServiceError {
+    #[error("job {0} not found")]
+    JobNotFound(chiral_common::job::ID),
+    #[error("invalid input for operator {0}: {1}")]
+    InvalidInput(String, serde_json::Error),
+    #[error("operator {0} is not available")]
+    OperatorNotAvailable(String),
+    #[error("dataset {0} is not available")]
+    DatasetNotAvailable(String),
+    #[error("report of job {0} is not ready")]
+    ReportNotReady(chiral_common::job::ID),
+    #[error("worker {0} not registered or expired")]
+    WorkerNotFound(chiral_common::worker::WorkerID),
+    #[error("lease {0} expired")]
+    LeaseExpired(chiral_common::worker::LeaseID),
+    #[error(transparent)]
+    Transition(#[from] chiral_common::job::TransitionError),
+    #[error(transparent)]
+    JobStore(#[from] chiral_common::job_store::JobStoreError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error)
+}
+
+struct State {
+    jobs: chiral_common::job::Jobs,
+    scheduler: chiral_common::scheduler::Scheduler,
+    tokens: std::collections::HashMap<chiral_common::job::ID, chiral_common::job::CancelToken>,
+    coordinator: crate::coordinator::Coordinator,
+    events: crate::events::EventBus,
+    shutdown: bool,
+    // changed jobs to store and finished jobs to report once the state is unlocked
+    unsaved: Vec<chiral_common::job::Job>,
+    unreported: Vec<chiral_common::job::Job>
+}
+
+pub struct Service {
+    state: std::sync::Mutex<State>,
+    store: std::sync::Mutex<Box<dyn TraitJobStore + Send>>,
+    work_ready: std::sync::Condvar,
+    ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>>,
+    datasets: Vec<chiral_common::kinds::Dataset>,
+    planner: chiral_common::planner::Planner,
+    report_dir: std::path::PathBuf
+}
+
+impl Service {
+    /// jobs left in the store unfinished are queued again
+    pub fn new(store: Box<dyn TraitJobStore + Send>, ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>>, datasets: Vec<chiral_common::kinds::Dataset>, planner: chiral_common::planner::Planner, report_dir: std::path::PathBuf) -> Self {
+        let mut state = State { jobs: store.jobs().to_owned(), scheduler: chiral_common::scheduler::Scheduler::default(), tokens: std::collections::HashMap::new(), coordinator: crate::coordinator::Coordinator::default(), events: crate::events::EventBus::default(), shutdown: false, unsaved: vec![], unreported: vec![] };
+        let unfinished: Vec<chiral_common::job::Job> = state.jobs.values()
+            .filter(|job| job.is_status(chiral_common::job::Status::Created))
+            .cloned()
+            .collect();
+        for job in unfinished {
+            state.tokens.insert(job.get_id().to_owned(), job.cancel_token());
+            let pending = job.get_progress().pending();
+            state.scheduler.push(job, pending);
+        }
+        Self { state: std::sync::Mutex::new(state), store: std::sync::Mutex::new(store), work_ready: std::sync::Condvar::new(), ds, datasets, planner, report_dir }
+    }
+
+    /// leases of remote workers expire without heartbeat within this duration
+    pub fn with_lease_duration(mut self, lease_duration: std::time::Duration) -> Self {
+        self.state.get_mut().unwrap().coordinator = crate::coordinator::Coordinator::new(lease_duration);
+        self
+    }
+
+    /// computing units run by the worker threads of the service
+    fn runs_locally(&self, req: &chiral_common::job::Requirement) -> bool {
+        chiral_apps::runner::is_available(req.get_opk()) && self.datasets.contains(req.get_dsk())
+    }
+
+    pub fn report_path(&self, id: &chiral_common::job::ID) -> std::path::PathBuf {
+        self.report_dir.join(format!("{}.json", id))
+    }
+
+    /// directory of the serialized outputs of the dividends of a job
+    pub fn output_dir(&self, id: &chiral_common::job::ID) -> std::path::PathBuf {
+        self.report_dir.join(id)
+    }
+
+    pub fn submit(&self, req: chiral_common::job::Requirement) -> Result<chiral_common::job::Job, ServiceError> {
+        let opk = req.get_opk();
+        opk.validate_input(req.get_ji()).map_err(|e| ServiceError::InvalidInput(opk.to_string(), e))?;
+        let size_loaded = self.ds.lock().unwrap().get_size(req.get_dsk());
+        let size_remote = {
+            let state = self.state.lock().unwrap();
+            if !self.runs_locally(&req) && !state.coordinator.serves(&req.generate_cuk()) {
+                if !chiral_apps::runner::is_available(opk) {
+                    return Err(ServiceError::OperatorNotAvailable(opk.to_string()));
+                }
+                return Err(ServiceError::DatasetNotAvailable(req.get_dsk().to_string()));
+            }
+            state.coordinator.size(req.get_dsk())
+        };
+
+        // the size of the loaded dataset, locally or by a remote worker, over the nominal size of its kind
+        let size = size_loaded.or(size_remote).unwrap_or_else(|| req.get_dsk().size());
+        let dividends = self.planner.dividends_of_size(&req, size);
+        let estimate = self.planner.estimate_of_size(&req, size);
+        let mut job = chiral_common::job::Job::new(req);
+        chiral_common::logging::info(format!("job {}: {}", job.get_id(), estimate).as_str());
+        job.plan(dividends.to_owned());
+        // no other thread knows the job before it is stored
+        self.store.lock().unwrap().put(&job)?;
+        let mut state = self.state.lock().unwrap();
+        state.jobs.insert(job.get_id().to_owned(), job.to_owned());
+        state.events.publish(None, &job);
+        state.tokens.insert(job.get_id().to_owned(), job.cancel_token());
+        state.scheduler.push(job.to_owned(), dividends);
+        self.work_ready.notify_all();
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &chiral_common::job::ID) -> Result<chiral_common::job::Job, ServiceError> {
+        self.state.lock().unwrap().jobs.get(id)
+            .cloned()
+            .ok_or_else(|| ServiceError::JobNotFound(id.to_owned()))
+    }
+
+    /// jobs sorted by start time, latest first
+    pub fn list(&self) -> Vec<chiral_common::job::Job> {
+        let state = self.state.lock().unwrap();
+        let mut jobs: Vec<chiral_common::job::Job> = state.jobs.values().cloned().collect();
+        jobs.sort_by(|j1, j2| j2.get_time_start().cmp(j1.get_time_start()).then_with(|| j1.get_id().cmp(j2.get_id())));
+        jobs
+    }
+
+    /// stop the job, running dividends return their partial output to the report
+    pub fn cancel(&self, id: &chiral_common::job::ID) -> Result<chiral_common::job::Job, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        let mut job = state.jobs.get(id).cloned().ok_or_else(|| ServiceError::JobNotFound(id.to_owned()))?;
+        job.cancel()?;
+        if let Some(token) = state.tokens.get(id) {
+            token.cancel();
+        }
+        state.scheduler.remove(id);
+        Self::finish(&mut state, &job);
+        // with its report saved
+        self.unlock(state);
+        self.get(id)
+    }
+
+    pub fn report(&self, id: &chiral_common::job::ID) -> Result<chiral_common::traits::SerializedFormat, ServiceError> {
+        let job = self.get(id)?;
+        if !job.is_report_ready() {
+            return Err(ServiceError::ReportNotReady(id.to_owned()));
+        }
+        Ok(std::fs::read_to_string(self.report_path(id))?)
+    }
+
+    /// update the job and publish its changes, it is stored once the state is unlocked
+    fn put(state: &mut State, job: &chiral_common::job::Job) {
+        let previous = state.jobs.insert(job.get_id().to_owned(), job.to_owned());
+        state.events.publish(previous.as_ref(), job);
+        state.unsaved.push(job.to_owned());
+    }
+
+    /// release the state, then store the changed jobs and save the reports of the finished ones
+    ///     the store is locked before the state is released, so the changes are written in their order
+    fn unlock<'a>(&'a self, mut state: std::sync::MutexGuard<'a, State>) {
+        loop {
+            let unsaved = std::mem::take(&mut state.unsaved);
+            let unreported = std::mem::take(&mut state.unreported);
+            let mut store = self.store.lock().unwrap();
+            drop(state);
+            for job in unsaved.iter() {
+                if let Err(e) = store.put(job) {
+                    chiral_common::logging::error(format!("job {}: {}", job.get_id(), e).as_str());
+                }
+            }
+            let reported: Vec<&chiral_common::job::ID> = unreported.iter()
+                .filter(|job| match job.report_save_partial(&self.output_dir(job.get_id()), &self.report_path(job.get_id())) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        chiral_common::logging::error(format!("job {}: report not saved, {}", job.get_id(), e).as_str());
+                        false
+                    }
+                })
+                .map(|job| job.get_id())
+                .collect();
+            drop(store);
+            if reported.is_empty() {
+                return;
+            }
+
+            state = self.state.lock().unwrap();
+            for id in reported {
+                if let Some(mut job) = state.jobs.get(id).cloned() {
+                    job.report_done();
+                    Self::put(&mut state, &job);
+                }
+            }
+        }
+    }
+
+    /// progress events of a job from its creation, or of all the jobs from now on
+    pub fn subscribe(&self, id: Option<&chiral_common::job::ID>) -> Result<std::sync::mpsc::Receiver<chiral_common::event::Event>, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        match id {
+            Some(id) => {
+                let job = state.jobs.get(id).cloned().ok_or_else(|| ServiceError::JobNotFound(id.to_owned()))?;
+                Ok(state.events.subscribe_job(&job))
+            }
+            None => Ok(state.events.subscribe())
+        }
+    }
+
+    /// update the job, the report of a job reaching a final status is saved once the state is unlocked
+    fn finish(state: &mut State, job: &chiral_common::job::Job) {
+        if job.get_status().is_final() && !job.is_failed() {
+            state.unreported.push(job.to_owned());
+        }
+        if job.get_status().is_final() {
+            state.tokens.remove(job.get_id());
+        }
+        Self::put(state, job);
+    }
+
+    fn next(&self) -> Option<(chiral_common::scheduler::WorkItem, chiral_common::job::CancelToken)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.shutdown {
+                return None;
+            }
+            if let Some((job, div_index)) = Self::pop(&mut state, |job| self.runs_locally(job.get_req())) {
+                let token = state.tokens.entry(job.get_id().to_owned()).or_insert_with(|| job.cancel_token()).to_owned();
+                self.unlock(state);
+                return Some(((job, div_index), token));
+            }
+            state = self.work_ready.wait(state).unwrap();
+        }
+    }
+
+    /// next dividend of an unfinished job accepted by the filter, the job is started if needed
+    fn pop<F: Fn(&chiral_common::job::Job) -> bool>(state: &mut State, filter: F) -> Option<chiral_common::scheduler::WorkItem> {
+        while let Some((job, div_index)) = state.scheduler.pop_for(&filter) {
+            let mut job = match state.jobs.get(job.get_id()) {
+                Some(stored) if !stored.get_status().is_final() => stored.to_owned(),
+                _ => continue
+            };
+            if job.is_status(chiral_common::job::Status::Created) {
+                job.start().ok();
+                Self::put(state, &job);
+            }
+            return Some((job, div_index));
+        }
+        None
+    }
+
+    fn run(self: &std::sync::Arc<Self>, job: chiral_common::job::Job, div_index: chiral_common::job::DividendIndex, token: chiral_common::job::CancelToken) {
+        let time_start = std::time::Instant::now();
+        // a deadline passed while queued stops the job without computing
+        let result = match token.stop_reason() {
+            Some(_) => Ok(None),
+            None => chiral_apps::runner::run_dividend(job.get_req(), &div_index, self.ds.clone(), &token).map(Some)
+        };
+        let duration = time_start.elapsed();
+        let result = self.save_output(job.get_id(), &div_index, result);
+
+        let mut state = self.state.lock().unwrap();
+        self.record(&mut state, job.get_id(), div_index, result, duration, &token);
+        self.unlock(state);
+    }
+
+    /// save the output of a dividend to a file, only its length is recorded in the job
+    fn save_output(&self, job_id: &chiral_common::job::ID, div_index: &chiral_common::job::DividendIndex, result: Result<Option<chiral_apps::runner::DividendOutput>, chiral_common::job::JobError>) -> Result<Option<usize>, chiral_common::job::JobError> {
+        result.and_then(|output| match output {
+            Some((output_ser, len)) => chiral_common::job::save_dividend_output(&self.output_dir(job_id), div_index, &output_ser)
+                .map(|_| Some(len))
+                .map_err(|e| chiral_common::job::JobError::new(chiral_common::job::ErrorKind::Computation, format!("output not saved, {}", e).as_str()).at(div_index)),
+            None => Ok(None)
+        })
+    }
+
+    /// record the result of a dividend computed with the token of its job, its output already saved
+    fn record(self: &std::sync::Arc<Self>, state: &mut State, job_id: &chiral_common::job::ID, div_index: chiral_common::job::DividendIndex, result: Result<Option<usize>, chiral_common::job::JobError>, duration: std::time::Duration, token: &chiral_common::job::CancelToken) {
+        let mut job = match state.jobs.get(job_id) {
+            Some(stored) => stored.to_owned(),
+            None => return
+        };
+        match result {
+            Ok(output_len) => {
+                match (token.stop_reason(), output_len) {
+                    (None, Some(len)) => job.dividend_done(&div_index, len, duration),
+                    (reason, output_len) => {
+                        if let Some(len) = output_len {
+                            job.dividend_partial(&div_index, len, duration);
+                        }
+                        if let (Some(reason), false) = (reason, job.get_status().is_final()) {
+                            job.stop(reason).ok();
+                            state.scheduler.remove(job.get_id());
+                        }
+                    }
+                }
+            }
+            Err(_) if job.get_status().is_final() => (),
+            Err(error) => {
+                match job.dividend_failed(error) {
+                    Ok(chiral_common::job::FailureAction::Retry(backoff)) => {
+                        let service = self.clone();
+                        let job_retry = job.to_owned();
+                        std::thread::spawn(move || {
+                            std::thread::sleep(backoff);
+                            let mut state = service.state.lock().unwrap();
+                            state.scheduler.push(job_retry, vec![div_index]);
+                            service.work_ready.notify_all();
+                        });
+                    }
+                    Ok(chiral_common::job::FailureAction::Skip) => (),
+                    Ok(chiral_common::job::FailureAction::Fail) => {
+                        if let Some(token) = state.tokens.get(job.get_id()) {
+                            token.cancel();
+                        }
+                        state.scheduler.remove(job.get_id());
+                    }
+                    Err(e) => chiral_common::logging::error(e.to_string().as_str())
+                }
+            }
+        }
+        if job.is_status(chiral_common::job::Status::Processing) && job.is_output_complete() {
+            // enough hits, the running dividends stop and the queued ones are dropped
+            if let Some(token) = state.tokens.get(job.get_id()) {
+                token.cancel();
+            }
+            state.scheduler.remove(job.get_id());
+            job.complete().ok();
+        }
+        if job.is_status(chiral_common::job::Status::Processing) && job.get_progress().pending().is_empty() {
+            job.complete().ok();
+        }
+        Self::finish(state, &job);
+    }
+
+    /// start the worker threads, they end after shutdown
+    pub fn spawn_workers(self: &std::sync::Arc<Self>, count: usize) -> Vec<std::thread::JoinHandle<()>> {
+        (0..count)
+            .map(|_| {
+                let service = self.clone();
+                std::thread::spawn(move || {
+                    while let Some(((job, div_index), token)) = service.next() {
+                        service.run(job, div_index, token);
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub fn register_worker(&self, registration: chiral_common::worker::Registration) -> chiral_common::worker::Registered {
+        let mut state = self.state.lock().unwrap();
+        let registered = state.coordinator.register(registration);
+        chiral_common::logging::info(format!("worker {} registered", registered.worker_id).as_str());
+        registered
+    }
+
+    /// lease the next dividend of the computing units served by the worker, also renewing its leases
+    pub fn lease(self: &std::sync::Arc<Self>, worker_id: &chiral_common::worker::WorkerID) -> Result<Option<chiral_common::worker::Lease>, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state);
+        state.coordinator.heartbeat(worker_id).ok_or_else(|| ServiceError::WorkerNotFound(worker_id.to_owned()))?;
+        let cuks = state.coordinator.cuks(worker_id).cloned().unwrap_or_default();
+        let lease = Self::pop(&mut state, |job| cuks.contains(&job.get_req().generate_cuk()))
+            .map(|(job, div_index)| {
+                state.tokens.entry(job.get_id().to_owned()).or_insert_with(|| job.cancel_token());
+                let lease_id = state.coordinator.grant(worker_id, job.get_id(), &div_index);
+                chiral_common::worker::Lease { lease_id, job_id: job.get_id().to_owned(), req: job.get_req().to_owned(), div_index }
+            });
+        self.unlock(state);
+        Ok(lease)
+    }
+
+    pub fn heartbeat(self: &std::sync::Arc<Self>, worker_id: &chiral_common::worker::WorkerID) -> Result<chiral_common::worker::HeartbeatReply, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state);
+        let leases = state.coordinator.heartbeat(worker_id).ok_or_else(|| ServiceError::WorkerNotFound(worker_id.to_owned()))?;
+        let stopped = leases.into_iter()
+            .filter(|(_, job_id)| state.tokens.get(job_id).is_none_or(|token| token.is_stopped()))
+            .map(|(lease_id, _)| lease_id)
+            .collect();
+        self.unlock(state);
+        Ok(chiral_common::worker::HeartbeatReply { stopped })
+    }
+
+    /// record the output of a leased dividend
+    pub fn upload(self: &std::sync::Arc<Self>, worker_id: &chiral_common::worker::WorkerID, lease_id: &chiral_common::worker::LeaseID, upload: chiral_common::worker::Upload) -> Result<chiral_common::job::Job, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state);
+        if state.coordinator.cuks(worker_id).is_none() {
+            return Err(ServiceError::WorkerNotFound(worker_id.to_owned()));
+        }
+        let (job_id, div_index) = state.coordinator.release(worker_id, lease_id).ok_or_else(|| ServiceError::LeaseExpired(lease_id.to_owned()))?;
+        let token = state.tokens.get(&job_id).cloned().unwrap_or_default();
+        self.unlock(state);
+
+        let result = self.save_output(&job_id, &div_index, upload.result.map(Some));
+        let mut state = self.state.lock().unwrap();
+        self.record(&mut state, &job_id, div_index, result, upload.duration, &token);
+        let job = state.jobs.get(&job_id).cloned().ok_or(ServiceError::JobNotFound(job_id));
+        self.unlock(state);
+        job
+    }
+
+    /// count the dividends of expired leases as failed attempts, queued again as long as the retry policy allows
+    fn expire(self: &std::sync::Arc<Self>, state: &mut State) {
+        for (job_id, div_index) in state.coordinator.expire() {
+            chiral_common::logging::warn(format!("job {}: lease of dividend {:?} expired", job_id, div_index).as_str());
+            let error = chiral_common::job::JobError::new(chiral_common::job::ErrorKind::Interrupted, "worker lost, lease expired").at(&div_index);
+            let token = state.tokens.get(&job_id).cloned().unwrap_or_default();
+            self.record(state, &job_id, div_index, Err(error), std::time::Duration::ZERO, &token);
+        }
+    }
+
+    /// check the leases periodically, ends after shutdown
+    pub fn spawn_reaper(self: &std::sync::Arc<Self>) -> std::thread::JoinHandle<()> {
+        let service = self.clone();
+        std::thread::spawn(move || {
+            let mut state = service.state.lock().unwrap();
+            while !state.shutdown {
+                let interval = state.coordinator.lease_duration() / 2;
+                state = service.work_ready.wait_timeout(state, interval).unwrap().0;
+                service.expire(&mut state);
+                service.unlock(state);
+                state = service.state.lock().unwrap();
+            }
+        })
+    }
+
+    /// workers end after their running dividend, unfinished jobs stay in the store
+    pub fn shutdown(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.shutdown = true;
+        state.events.close();
+        self.work_ready.notify_all();
+    }
+}
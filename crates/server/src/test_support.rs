@@ -0,0 +1,57 @@
+//! Fixtures shared by the tests of the server, its workers and the client
+//!
+
+use chiral_common::data::Dummy;
+use chiral_common::traits::Serialization;
+
+pub fn temp_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("chiral_server_{}", chiral_common::utils::generate_id(8)));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// server on a free port of localhost serving the dummy dataset, one entry per dividend for similarity searching
+pub fn start_dummy_server(dir: &std::path::Path, workers: usize) -> crate::http::ApiServer {
+    start_dummy_server_with(dir, workers, chiral_common::planner::Budget { duration: None, memory: Some(2048) })
+}
+
+pub fn start_dummy_server_with(dir: &std::path::Path, workers: usize, budget: chiral_common::planner::Budget) -> crate::http::ApiServer {
+    let mut ds = chiral_common::data::DocStoreSMILES::new();
+    ds.insert(chiral_common::kinds::Dataset::Dummy, chiral_common::data::DocSMILES::dummy());
+    let ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(ds));
+    let planner = chiral_common::planner::Planner::new(budget);
+    let service = crate::service::Service::new(Box::<chiral_common::job_store::MemoryJobStore>::default(), ds, vec![chiral_common::kinds::Dataset::Dummy], planner, dir.to_path_buf());
+    crate::http::ApiServer::start("127.0.0.1:0", service, workers).unwrap()
+}
+
+/// similarity search of the 2 most similar entries of the dummy dataset
+pub fn similarity_requirement(smiles: &str) -> chiral_common::job::Requirement {
+    let fpk = chiral_common::app::chem::kinds::Fingerprint::kind_rust_ecfp(4, 2048);
+    let input = chiral_common::app::chem::openbabel::similarity::Input { smiles: smiles.to_string(), threshold: 0.0, top_k: Some(2), metric: chiral_common::app::chem::kinds::Metric::Tanimoto };
+    chiral_common::job::Requirement::new(input.ser_to(), chiral_common::kinds::Operator::similarity_searching(fpk), chiral_common::kinds::Dataset::Dummy)
+}
+
+pub fn submit(base: &str, req: &chiral_common::job::Requirement) -> chiral_common::job::Job {
+    chiral_common::job::Job::ser_from(&ureq::post(format!("{}/jobs", base).as_str()).send_string(&req.ser_to()).unwrap().into_string().unwrap())
+}
+
+/// job once final with its report saved
+pub fn wait_final(base: &str, id: &str) -> chiral_common::job::Job {
+    for _ in 0..500 {
+        let job = chiral_common::job::Job::ser_from(&ureq::get(format!("{}/jobs/{}", base, id).as_str()).call().unwrap().into_string().unwrap());
+        if job.get_status().is_final() && job.is_report_ready() {
+            return job;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    panic!("job {} not finished", id);
+}
+
+/// status code of a response, including the error ones
+pub fn status_code<E: Into<Box<ureq::Error>>>(result: Result<ureq::Response, E>) -> u16 {
+    match result.map_err(|e| *e.into()) {
+        Ok(response) => response.status(),
+        Err(ureq::Error::Status(code, _)) => code,
+        Err(e) => panic!("{}", e)
+    }
+}
@@ -0,0 +1,133 @@
+//! Remote worker: leases dividends from a coordinator, computes them on its local data and uploads the outputs
+//!
+
+use chiral_common::traits::Serialization;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WorkerError {
+    #[error("coordinator error {0}: {1}")]
+    Coordinator(u16, String),
+    #[error("coordinator not reachable: {0}")]
+    Transport(String),
+    #[error("invalid response: {0}")]
+    InvalidResponse(#[from] serde_json::Error)
+}
+
+impl From<ureq::Error> for WorkerError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(code, response) => Self::Coordinator(code, response.into_string().unwrap_or_default()),
+            ureq::Error::Transport(t) => Self::Transport(t.to_string())
+        }
+    }
+}
+
+pub struct RemoteWorker {
+    base_url: String,
+    agent: ureq::Agent,
+    cuks: Vec<chiral_common::kinds::ComputingUnit>,
+    ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>>,
+    poll_interval: std::time::Duration
+}
+
+impl RemoteWorker {
+    /// base url of the coordinator, e.g. "http://127.0.0.1:8080"
+    pub fn new(base_url: &str, cuks: Vec<chiral_common::kinds::ComputingUnit>, ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(std::time::Duration::from_secs(30)).build(),
+            cuks,
+            ds,
+            poll_interval: std::time::Duration::from_secs(1)
+        }
+    }
+
+    /// waiting time before asking again for work
+    pub fn with_poll_interval(mut self, interval: std::time::Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    fn post(&self, path: &str, body: &str) -> Result<ureq::Response, WorkerError> {
+        Ok(self.agent.post(&format!("{}{}", self.base_url, path))
+            .set("Content-Type", "application/json")
+            .send_string(body)?)
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(response: ureq::Response) -> Result<T, WorkerError> {
+        let body = response.into_string().map_err(|e| WorkerError::Transport(e.to_string()))?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    fn register(&self) -> Result<chiral_common::worker::Registered, WorkerError> {
+        let sizes = {
+            let ds = self.ds.lock().unwrap();
+            let mut sizes: Vec<(chiral_common::kinds::Dataset, usize)> = vec![];
+            for dsk in self.cuks.iter().map(|cuk| cuk.get_dsk()) {
+                if let Some(size) = ds.get_size(dsk).filter(|_| !sizes.iter().any(|(d, _)| d == dsk)) {
+                    sizes.push((dsk.to_owned(), size));
+                }
+            }
+            sizes
+        };
+        let registration = chiral_common::worker::Registration { cuks: self.cuks.to_owned(), sizes };
+        let registered: chiral_common::worker::Registered = Self::parse(self.post("/workers", &registration.ser_to())?)?;
+        chiral_common::logging::info(format!("registered as {}", registered.worker_id).as_str());
+        Ok(registered)
+    }
+
+    /// renew the lease while computing, stopping the token when the job is stopped or the lease is lost
+    fn keep_alive(&self, registered: &chiral_common::worker::Registered, lease_id: &chiral_common::worker::LeaseID, token: &chiral_common::job::CancelToken, done: std::sync::mpsc::Receiver<()>) {
+        let path = format!("/workers/{}/heartbeat", registered.worker_id);
+        while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = done.recv_timeout(registered.lease_duration / 3) {
+            let stopped = match self.post(&path, "").and_then(Self::parse::<chiral_common::worker::HeartbeatReply>) {
+                Ok(reply) => reply.stopped.contains(lease_id),
+                Err(e) => {
+                    chiral_common::logging::warn(format!("heartbeat failed: {}", e).as_str());
+                    matches!(e, WorkerError::Coordinator(404, _))
+                }
+            };
+            if stopped {
+                token.cancel();
+            }
+        }
+    }
+
+    fn compute(&self, registered: &chiral_common::worker::Registered, lease: &chiral_common::worker::Lease) -> chiral_common::worker::Upload {
+        let token = chiral_common::job::CancelToken::new(lease.req.get_deadline().to_owned());
+        let (done_sender, done) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| self.keep_alive(registered, &lease.lease_id, &token, done));
+            let time_start = std::time::Instant::now();
+            let result = chiral_apps::runner::run_dividend(&lease.req, &lease.div_index, self.ds.clone(), &token);
+            done_sender.send(()).ok();
+            chiral_common::worker::Upload { result, duration: time_start.elapsed() }
+        })
+    }
+
+    /// serve leases until the coordinator is not reachable
+    pub fn run(&self) -> Result<(), WorkerError> {
+        let mut registered = self.register()?;
+        loop {
+            let response = match self.post(&format!("/workers/{}/lease", registered.worker_id), "") {
+                Err(WorkerError::Coordinator(404, _)) => {
+                    // expired after a long pause, e.g. the coordinator restarted
+                    registered = self.register()?;
+                    continue;
+                }
+                result => result?
+            };
+            if response.status() == 204 {
+                std::thread::sleep(self.poll_interval);
+                continue;
+            }
+            let lease: chiral_common::worker::Lease = Self::parse(response)?;
+            chiral_common::logging::info(format!("job {}: computing dividend {:?}", lease.job_id, lease.div_index).as_str());
+            let upload = self.compute(&registered, &lease);
+            match self.post(&format!("/workers/{}/leases/{}", registered.worker_id, lease.lease_id), &upload.ser_to()) {
+                Err(WorkerError::Coordinator(code, message)) if code == 404 || code == 409 => chiral_common::logging::warn(format!("job {}: output dropped, {}", lease.job_id, message).as_str()),
+                result => { result?; }
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Coordinator with remote worker processes on localhost
+//!
+
+use chiral_common::traits::Serialization;
+use chiral_server::test_support::{similarity_requirement, status_code, submit, temp_dir, wait_final};
+
+const SMILES: &str = "CC(=O)Nc1ccc(O)cc1";
+
+fn start_coordinator(dir: &std::path::Path) -> chiral_server::http::ApiServer {
+    // the coordinator has neither local workers nor data
+    let ds: std::sync::Arc<std::sync::Mutex<dyn chiral_common::traits::TraitDataStore>> = std::sync::Arc::new(std::sync::Mutex::new(chiral_common::data::DocStoreSMILES::new()));
+    let planner = chiral_common::planner::Planner::new(chiral_common::planner::Budget { duration: None, memory: Some(2048) });
+    let service = chiral_server::service::Service::new(Box::<chiral_common::job_store::MemoryJobStore>::default(), ds, vec![], planner, dir.to_path_buf())
+        .with_lease_duration(std::time::Duration::from_millis(500));
+    chiral_server::http::ApiServer::start("127.0.0.1:0", service, 0).unwrap()
+}
+
+fn post(base: &str, path: &str, body: &str) -> Result<ureq::Response, Box<ureq::Error>> {
+    ureq::post(format!("{}{}", base, path).as_str()).send_string(body).map_err(Box::new)
+}
+
+/// a worker registered over HTTP by the test itself
+fn register(base: &str) -> chiral_common::worker::Registered {
+    let registration = chiral_common::worker::Registration { cuks: vec![similarity_requirement(SMILES).generate_cuk()], sizes: vec![] };
+    chiral_common::worker::Registered::ser_from(&post(base, "/workers", &registration.ser_to()).unwrap().into_string().unwrap())
+}
+
+fn lease(base: &str, worker_id: &str) -> chiral_common::worker::Lease {
+    chiral_common::worker::Lease::ser_from(&post(base, &format!("/workers/{}/lease", worker_id), "").unwrap().into_string().unwrap())
+}
+
+fn spawn_worker(base: &str) -> std::process::Child {
+    let operators = serde_json::to_string(&vec![similarity_requirement(SMILES).get_opk()]).unwrap();
+    std::process::Command::new(env!("CARGO_BIN_EXE_chiral-worker"))
+        .args(["--coordinator", base, "--datasets", "dummy", "--operators", &operators, "--poll", "20"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+#[test]
+fn test_dead_worker() {
+    let dir = temp_dir();
+    let server = start_coordinator(&dir);
+    let base = format!("http://{}", server.address());
+
+    // nothing serves the computing unit yet
+    assert_eq!(status_code(post(&base, "/jobs", &similarity_requirement(SMILES).ser_to())), 400);
+
+    // a worker leasing a dividend then going silent
+    let dead = register(&base);
+    let job = submit(&base, &similarity_requirement(SMILES));
+    assert_eq!(job.get_progress().get_planned().len(), 4);
+    let lease_dead = lease(&base, &dead.worker_id);
+
+    let mut workers: Vec<std::process::Child> = (0..2).map(|_| spawn_worker(&base)).collect();
+    let job = wait_final(&base, job.get_id());
+    assert!(job.is_status(chiral_common::job::Status::Completed));
+    assert_eq!(job.get_progress().get_done().len(), 4);
+    assert!(job.get_progress().get_done().iter().any(|record| record.div_index == lease_dead.div_index));
+    let report = chiral_common::app::chem::openbabel::similarity::Report::ser_from(&server.service().report(job.get_id()).unwrap());
+    assert_eq!(report.output.results[0], (1.0, "label_4".to_string()));
+
+    // the dead worker has lost its lease and registration
+    let upload = chiral_common::worker::Upload { result: Ok(("{}".to_string(), 0)), duration: std::time::Duration::ZERO };
+    assert_eq!(status_code(post(&base, &format!("/workers/{}/leases/{}", dead.worker_id, lease_dead.lease_id), &upload.ser_to())), 404);
+    assert_eq!(status_code(post(&base, &format!("/workers/{}/heartbeat", dead.worker_id), "")), 404);
+
+    server.stop();
+    for worker in workers.iter_mut() {
+        worker.kill().ok();
+        worker.wait().unwrap();
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_protocol() {
+    let dir = temp_dir();
+    let server = start_coordinator(&dir);
+    let base = format!("http://{}", server.address());
+    assert_eq!(status_code(post(&base, "/workers", "{}")), 400);
+    assert_eq!(status_code(post(&base, "/workers/unknown/lease", "")), 404);
+
+    let registered = register(&base);
+    assert_eq!(registered.lease_duration, std::time::Duration::from_millis(500));
+    assert_eq!(status_code(post(&base, &format!("/workers/{}/lease", registered.worker_id), "")), 204);
+
+    let job = submit(&base, &similarity_requirement(SMILES));
+    let lease_1 = lease(&base, &registered.worker_id);
+    assert_eq!(&lease_1.job_id, job.get_id());
+    assert_eq!(lease_1.div_index, (0, 1));
+    let lease_2 = lease(&base, &registered.worker_id);
+    let heartbeat = || chiral_common::worker::HeartbeatReply::ser_from(&post(&base, &format!("/workers/{}/heartbeat", registered.worker_id), "").unwrap().into_string().unwrap());
+    assert!(heartbeat().stopped.is_empty());
+
+    // an error is retried on another lease
+    let error = chiral_common::job::JobError::new(chiral_common::job::ErrorKind::Computation, "worker crashed").at(&lease_1.div_index);
+    let upload = chiral_common::worker::Upload { result: Err(error), duration: std::time::Duration::ZERO };
+    let path_1 = format!("/workers/{}/leases/{}", registered.worker_id, lease_1.lease_id);
+    assert_eq!(status_code(post(&base, &path_1, &upload.ser_to())), 200);
+    assert_eq!(status_code(post(&base, &path_1, &upload.ser_to())), 409);
+    assert_eq!(server.service().get(job.get_id()).unwrap().get_progress().attempts(&lease_1.div_index), 1);
+
+    // cancelling the job stops the running lease, its output is kept as partial
+    assert!(server.service().cancel(job.get_id()).unwrap().is_status(chiral_common::job::Status::Cancelled));
+    assert_eq!(heartbeat().stopped, vec![lease_2.lease_id.to_owned()]);
+    let output_ser = r#"{"results":[[0.5,"label_3"]]}"#.to_string();
+    let upload = chiral_common::worker::Upload { result: Ok((output_ser, 1)), duration: std::time::Duration::ZERO };
+    let job = chiral_common::job::Job::ser_from(&post(&base, &format!("/workers/{}/leases/{}", registered.worker_id, lease_2.lease_id), &upload.ser_to()).unwrap().into_string().unwrap());
+    assert!(job.is_status(chiral_common::job::Status::Cancelled));
+    assert_eq!(job.get_progress().output_len(), 1);
+
+    server.stop();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_lease_expired_out_of_attempts() {
+    let dir = temp_dir();
+    let server = start_coordinator(&dir);
+    let base = format!("http://{}", server.address());
+    let retry = chiral_common::job::RetryPolicy { max_attempts: 2, backoff: std::time::Duration::from_millis(10), max_backoff: std::time::Duration::from_millis(10), ..chiral_common::job::RetryPolicy::default() };
+    let dead = register(&base);
+    let job = submit(&base, &similarity_requirement(SMILES).with_retry(retry));
+    let planned = job.get_progress().get_planned().to_owned();
+    let attempts = |n: u32| {
+        for _ in 0..100 {
+            let job = server.service().get(job.get_id()).unwrap();
+            if job.get_status().is_final() || planned.iter().all(|div_index| job.get_progress().attempts(div_index) >= n) {
+                return job;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        panic!("leases of job {} not expired", job.get_id());
+    };
+
+    // each expired lease counts as a failed attempt, and the dividend is queued again
+    for _ in planned.iter() {
+        lease(&base, &dead.worker_id);
+    }
+    let job_retried = attempts(1);
+    assert!(job_retried.is_status(chiral_common::job::Status::Processing));
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // out of attempts, the job fails
+    let dead_again = register(&base);
+    for _ in planned.iter() {
+        lease(&base, &dead_again.worker_id);
+    }
+    let job_failed = attempts(2);
+    assert!(matches!(job_failed.get_status(), chiral_common::job::Status::Failed(error) if error.kind == chiral_common::job::ErrorKind::Interrupted));
+    assert_eq!(status_code(post(&base, &format!("/workers/{}/lease", register(&base).worker_id), "")), 204);
+
+    server.stop();
+    std::fs::remove_dir_all(&dir).unwrap();
+}